@@ -0,0 +1,99 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec4;
+use raycaster::{canvas::Canvas, primitives::VerticalLineDescriptor};
+
+const RESOLUTIONS: [(u32, u32); 3] = [(640, 360), (1280, 720), (1920, 1080)];
+
+/// The layout the canvas used before it went column-major, kept here so the
+/// two can be compared.
+fn draw_vertical_line_row_major(pixels: &mut [u8], width: u32, line: &VerticalLineDescriptor) {
+    let mut pixel_offset = line.top_x * width + line.y;
+    for _ in line.top_x..line.bottom_x {
+        let rgba_offset = (pixel_offset * 4) as usize;
+        let alpha_channel = (255.0 * line.color[3]) as u8;
+
+        if pixels[rgba_offset + 3] < alpha_channel {
+            pixels[rgba_offset] = (255.0 * line.color[2]) as u8;
+            pixels[rgba_offset + 1] = (255.0 * line.color[1]) as u8;
+            pixels[rgba_offset + 2] = (255.0 * line.color[0]) as u8;
+            pixels[rgba_offset + 3] = alpha_channel;
+            pixel_offset += width;
+        }
+    }
+}
+
+fn span(y: u32, height: u32) -> VerticalLineDescriptor {
+    // Vary the span length a little so it looks like a wall in perspective
+    let inset = (y % 64) * height / 256;
+    VerticalLineDescriptor {
+        y,
+        top_x: inset,
+        bottom_x: height - inset,
+        color: Vec4::new(0.5, 0.25, 1.0, 1.0),
+    }
+}
+
+fn vertical_spans(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vertical_spans");
+
+    for (width, height) in RESOLUTIONS {
+        let id = format!("{width}x{height}");
+
+        group.bench_function(BenchmarkId::new("row_major", &id), |b| {
+            let mut pixels = vec![0; (width * height * 4) as usize];
+            b.iter(|| {
+                pixels.fill(0);
+                for y in 0..width {
+                    draw_vertical_line_row_major(&mut pixels, width, &span(y, height));
+                }
+                black_box(&pixels);
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("column_major", &id), |b| {
+            let mut canvas = Canvas::new(width, height);
+            b.iter(|| {
+                canvas.clear();
+                for y in 0..width {
+                    canvas.draw_vertical_line(&span(y, height));
+                }
+                black_box(&canvas);
+            })
+        });
+
+        // The renderer uploads the canvas as a rotated texture instead, this
+        // is the cost of doing the transpose on the CPU.
+        group.bench_function(BenchmarkId::new("transpose", &id), |b| {
+            let mut canvas = Canvas::new(width, height);
+            for y in 0..width {
+                canvas.draw_vertical_line(&span(y, height));
+            }
+            let mut rows = vec![0; (width * height * 4) as usize];
+            b.iter(|| {
+                canvas.write_rows(&mut rows);
+                black_box(&rows);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn clear(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clear");
+
+    for (width, height) in RESOLUTIONS {
+        group.bench_function(format!("{width}x{height}"), |b| {
+            let mut canvas = Canvas::new(width, height);
+            b.iter(|| {
+                canvas.clear();
+                black_box(&canvas);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, vertical_spans, clear);
+criterion_main!(benches);
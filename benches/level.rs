@@ -0,0 +1,77 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec2;
+use raycaster::{geometry::intersection_distance, level::LevelState};
+
+const LEVEL_SIZES: [usize; 3] = [8, 32, 128];
+
+fn intersection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intersection_distance");
+
+    let start = Vec2::new(-1.0, 2.0);
+    let end = Vec2::new(1.0, 2.5);
+    group.bench_function("hit", |b| {
+        b.iter(|| intersection_distance(black_box(Vec2::ZERO), black_box(Vec2::Y), start, end))
+    });
+    group.bench_function("miss", |b| {
+        b.iter(|| intersection_distance(black_box(Vec2::ZERO), black_box(Vec2::NEG_Y), start, end))
+    });
+
+    group.finish();
+}
+
+fn point_location(c: &mut Criterion) {
+    let mut group = c.benchmark_group("point_location");
+
+    for size in LEVEL_SIZES {
+        let level = LevelState::grid(size, size);
+        // A spread of points over the level, some of them outside it
+        let points: Vec<Vec2> = (0..256)
+            .map(|i| {
+                let t = i as f32 / 256.0;
+                Vec2::new(
+                    t * (size as f32 + 2.0) - 1.0,
+                    (t * 37.0).fract() * size as f32,
+                )
+            })
+            .collect();
+
+        group.bench_function(BenchmarkId::new("sector_contains", size), |b| {
+            let sector = level.sector(0);
+            b.iter(|| {
+                points
+                    .iter()
+                    .filter(|point| sector.contains(**point))
+                    .count()
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("find_current_sector", size), |b| {
+            b.iter(|| {
+                points
+                    .iter()
+                    .filter_map(|point| level.find_current_sector(*point))
+                    .sum::<usize>()
+            })
+        });
+
+        // What find_current_sector did before the level had a sector index
+        group.bench_function(BenchmarkId::new("linear_scan", size), |b| {
+            b.iter(|| {
+                points
+                    .iter()
+                    .filter_map(|point| {
+                        level
+                            .sectors()
+                            .iter()
+                            .position(|sector| sector.contains(*point))
+                    })
+                    .sum::<usize>()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, intersection, point_location);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    canvas::Canvas,
+    game::GameState,
+    level::LevelState,
+    scene::{render_scene, SceneSettings},
+};
+
+const RESOLUTIONS: [(u32, u32); 3] = [(320, 200), (1280, 720), (1920, 1080)];
+const LEVEL_SIZES: [usize; 3] = [8, 32, 128];
+
+/// A player standing in the middle of a `size` by `size` grid level, looking
+/// along the diagonal so plenty of sectors are in view.
+fn game_state(size: usize) -> GameState {
+    let centre = Vec2::splat(size as f32 / 2.0 + 0.5);
+    GameState::new(
+        LevelState::grid(size, size),
+        centre,
+        Vec3::new(1.0, 1.0, 0.0).normalize(),
+    )
+}
+
+fn settings(threads: usize) -> SceneSettings {
+    SceneSettings {
+        fov_y: PI / 2.0,
+        render_distance: 16.0,
+        threads,
+        ..SceneSettings::default()
+    }
+}
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_scene");
+    group.sample_size(20);
+
+    for size in LEVEL_SIZES {
+        let game_state = game_state(size);
+
+        for (width, height) in RESOLUTIONS {
+            let id = format!("{size}x{size}/{width}x{height}");
+            let mut canvas = Canvas::new(width, height);
+
+            group.bench_function(BenchmarkId::new("parallel", &id), |b| {
+                let settings = SceneSettings {
+                    threads: SceneSettings::default().threads,
+                    ..settings(1)
+                };
+                b.iter(|| {
+                    canvas.clear();
+                    render_scene(&mut canvas, &game_state, &settings);
+                    black_box(&canvas);
+                })
+            });
+
+            group.bench_function(BenchmarkId::new("single_thread", &id), |b| {
+                let settings = settings(1);
+                b.iter(|| {
+                    canvas.clear();
+                    render_scene(&mut canvas, &game_state, &settings);
+                    black_box(&canvas);
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
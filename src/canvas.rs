@@ -0,0 +1,309 @@
+use std::cmp::{max, min};
+
+use glam::{Vec2, Vec3};
+
+use crate::{
+    font::Font,
+    primitives::{
+        CricleDescriptor, LineDescriptor, RectDescriptor, TextDescriptor, VerticalLineDescriptor,
+    },
+};
+
+/// Software framebuffer the game draws into before it is uploaded to the
+/// surface. Pixels are stored as BGRA column by column, so the vertical spans
+/// drawn by the scene renderer walk contiguous memory. The renderer uploads it
+/// as a texture rotated by 90 degrees, `write_rows` is there when a row-major
+/// copy is needed on the CPU.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn clear(&mut self) {
+        // todo: check the color format first
+        let colour: u32 = 0;
+
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&colour.to_be_bytes());
+        }
+    }
+
+    /// Splits the canvas into bands of `band_width` columns (the last one may
+    /// be narrower) which can be drawn into independently.
+    pub fn bands_mut(&mut self, band_width: u32) -> impl Iterator<Item = CanvasBand<'_>> {
+        let height = self.height;
+        let band_width = band_width.max(1);
+
+        self.pixels
+            .chunks_mut((band_width * height * 4) as usize)
+            .enumerate()
+            .map(move |(band, pixels)| CanvasBand {
+                first_column: band as u32 * band_width,
+                width: pixels.len() as u32 / (height * 4),
+                height,
+                pixels,
+            })
+    }
+
+    pub fn draw_vertical_line(&mut self, line: &VerticalLineDescriptor) {
+        let width = self.width;
+        if let Some(mut band) = self.bands_mut(width).next() {
+            band.draw_vertical_line(line);
+        }
+    }
+
+    /// Copies the canvas into `rows` in row-major order, one tile at a time so
+    /// both sides of the copy stay in cache.
+    pub fn write_rows(&self, rows: &mut [u8]) {
+        const TILE: usize = 16;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (rows, _) = rows.as_chunks_mut::<4>();
+        let (pixels, _) = self.pixels.as_chunks::<4>();
+
+        for tile_x in (0..width).step_by(TILE) {
+            for tile_y in (0..height).step_by(TILE) {
+                for x in tile_x..(tile_x + TILE).min(width) {
+                    for y in tile_y..(tile_y + TILE).min(height) {
+                        rows[y * width + x] = pixels[x * height + y];
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn draw_circle(&mut self, circle: &CricleDescriptor) {
+        let min_y = max((circle.centre.y - circle.radius) as i32, 0) as u32;
+        let max_y = min((circle.centre.y + circle.radius) as u32, self.height);
+
+        let min_x = max((circle.centre.x - circle.radius) as i32, 0) as u32;
+        let max_x = min((circle.centre.x + circle.radius) as u32, self.width);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let distance = circle.centre.distance(Vec2::new(x as f32, y as f32));
+                let _intensity = (circle.radius - distance) / circle.radius;
+                if distance < circle.radius {
+                    let color = circle.color * 128.0;
+                    self.plot_with_opacity(x, y, color.x as u8, color.y as u8, color.z as u8, 0.5);
+                }
+            }
+        }
+    }
+
+    pub fn draw_line(&mut self, line: &LineDescriptor) {
+        let start = (line.start.x as isize, line.start.y as isize);
+        let end = (line.end.x as isize, line.end.y as isize);
+
+        let stroke = line.stroke.max(1.0) as isize;
+
+        for (x, y) in bresenham::Bresenham::new(start, end) {
+            for dy in 0..stroke {
+                for dx in 0..stroke {
+                    let (x, y) = (x + dx - stroke / 2, y + dy - stroke / 2);
+                    if 0 < x && x < self.width as isize && 0 < y && y < self.height as isize {
+                        self.plot(x as u32, y as u32, line.color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills a rectangle, clipped to the canvas.
+    pub fn draw_rect(&mut self, rect: &RectDescriptor) {
+        let bottom_right = rect.top_left + rect.size;
+        let min_x = rect.top_left.x.max(0.0) as u32;
+        let min_y = rect.top_left.y.max(0.0) as u32;
+        let max_x = (bottom_right.x.max(0.0) as u32).min(self.width);
+        let max_y = (bottom_right.y.max(0.0) as u32).min(self.height);
+
+        for x in min_x..max_x {
+            for y in min_y..max_y {
+                self.plot(x, y, rect.color);
+            }
+        }
+    }
+
+    /// Draws `text` with `font`, a new line starts below the first character
+    /// of the previous one. Pixels outside the canvas are skipped.
+    pub fn draw_text(&mut self, font: &Font, text: &TextDescriptor) {
+        let scale = text.scale.max(1) as i64;
+        let (glyph_width, glyph_height) = (font.width() as i64, font.height() as i64);
+        let (left, top) = (text.top_left.x as i64, text.top_left.y as i64);
+
+        for (line_index, line) in text.text.lines().enumerate() {
+            let line_top = top + line_index as i64 * glyph_height * scale;
+
+            for (column, c) in line.chars().enumerate() {
+                let Some(glyph) = font.glyph(c) else {
+                    continue;
+                };
+                let glyph_left = left + column as i64 * glyph_width * scale;
+
+                for gy in 0..glyph_height {
+                    for gx in 0..glyph_width {
+                        if !font.pixel(glyph, gx as u32, gy as u32) {
+                            continue;
+                        }
+                        for sy in 0..scale {
+                            for sx in 0..scale {
+                                let x = glyph_left + gx * scale + sx;
+                                let y = line_top + gy * scale + sy;
+                                if (0..self.width as i64).contains(&x)
+                                    && (0..self.height as i64).contains(&y)
+                                {
+                                    self.plot_blended(x as u32, y as u32, text.color, text.alpha);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn plot(&mut self, x: u32, y: u32, color: Vec3) {
+        let rgba_offset = self.offset(x, y);
+        self.pixels[rgba_offset] = (color[2] * 255.0) as u8;
+        self.pixels[rgba_offset + 1] = (color[1] * 255.0) as u8;
+        self.pixels[rgba_offset + 2] = (color[0] * 255.0) as u8;
+    }
+
+    pub fn plot_with_opacity(&mut self, x: u32, y: u32, r: u8, g: u8, b: u8, opacity: f32) {
+        let rgba_offset = self.offset(x, y);
+
+        self.pixels[rgba_offset] = blend(self.pixels[rgba_offset], b, opacity);
+        self.pixels[rgba_offset + 1] = blend(self.pixels[rgba_offset + 1], g, opacity);
+        self.pixels[rgba_offset + 2] = blend(self.pixels[rgba_offset + 2], r, opacity);
+    }
+
+    /// Mixes `color` over the current pixel, `alpha` of one replaces it.
+    pub fn plot_blended(&mut self, x: u32, y: u32, color: Vec3, alpha: f32) {
+        let rgba_offset = self.offset(x, y);
+        let alpha = alpha.clamp(0.0, 1.0);
+
+        for (channel, value) in [color[2], color[1], color[0]].into_iter().enumerate() {
+            let current = self.pixels[rgba_offset + channel] as f32;
+            self.pixels[rgba_offset + channel] =
+                (current + (value * 255.0 - current) * alpha) as u8;
+        }
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        ((x * self.height + y) * 4) as usize
+    }
+}
+
+/// A run of whole columns borrowed from a [`Canvas`].
+pub struct CanvasBand<'a> {
+    first_column: u32,
+    width: u32,
+    height: u32,
+    pixels: &'a mut [u8],
+}
+
+impl CanvasBand<'_> {
+    pub fn first_column(&self) -> u32 {
+        self.first_column
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Draws a span in canvas coordinates, `line.y` must be one of this
+    /// band's columns.
+    pub fn draw_vertical_line(&mut self, line: &VerticalLineDescriptor) {
+        let column_offset = (line.y - self.first_column) * self.height;
+        for x in line.top_x..line.bottom_x {
+            // loop with if would be faster
+            let rgba_offset = ((column_offset + x) * 4) as usize;
+
+            let red_channel = (255.0 * line.color[0]) as u8;
+            let green_channel = (255.0 * line.color[1]) as u8;
+            let blue_channel = (255.0 * line.color[2]) as u8;
+            let alpha_channel = (255.0 * line.color[3]) as u8;
+
+            if self.pixels[rgba_offset + 3] < alpha_channel {
+                self.pixels[rgba_offset] = blue_channel;
+                self.pixels[rgba_offset + 1] = green_channel;
+                self.pixels[rgba_offset + 2] = red_channel;
+                self.pixels[rgba_offset + 3] = alpha_channel;
+            }
+        }
+    }
+
+    /// Like `draw_vertical_line` with its own colour for each row, given by
+    /// `color`, and `depth` in place of the colour's alpha.
+    pub fn draw_vertical_span(
+        &mut self,
+        y: u32,
+        top: u32,
+        bottom: u32,
+        depth: f32,
+        color: impl Fn(u32) -> Vec3,
+    ) {
+        let column_offset = (y - self.first_column) * self.height;
+        let alpha_channel = (255.0 * depth) as u8;
+        for x in top..bottom.min(self.height) {
+            let rgba_offset = ((column_offset + x) * 4) as usize;
+            if self.pixels[rgba_offset + 3] >= alpha_channel {
+                continue;
+            }
+            let color = color(x);
+            self.pixels[rgba_offset] = (color[2] * 255.0) as u8;
+            self.pixels[rgba_offset + 1] = (color[1] * 255.0) as u8;
+            self.pixels[rgba_offset + 2] = (color[0] * 255.0) as u8;
+            self.pixels[rgba_offset + 3] = alpha_channel;
+        }
+    }
+
+    /// Colours the rows from `top` to `bottom` of column `y` that nothing has
+    /// been drawn in yet, behind anything drawn there later. `color` gives
+    /// each row's colour.
+    pub fn draw_background(&mut self, y: u32, top: u32, bottom: u32, color: impl Fn(u32) -> Vec3) {
+        let column_offset = (y - self.first_column) * self.height;
+        for x in top..bottom.min(self.height) {
+            let rgba_offset = ((column_offset + x) * 4) as usize;
+            if self.pixels[rgba_offset + 3] > 0 {
+                continue;
+            }
+            let color = color(x);
+            self.pixels[rgba_offset] = (color[2] * 255.0) as u8;
+            self.pixels[rgba_offset + 1] = (color[1] * 255.0) as u8;
+            self.pixels[rgba_offset + 2] = (color[0] * 255.0) as u8;
+            self.pixels[rgba_offset + 3] = 1;
+        }
+    }
+}
+
+fn blend(current: u8, new: u8, opacity: f32) -> u8 {
+    let current = current as f32;
+    let new = new as f32 * opacity;
+
+    let hdr_color = current + new;
+
+    255.0_f32.min(hdr_color) as u8
+}
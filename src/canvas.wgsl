@@ -0,0 +1,18 @@
+// Draws the software canvas over the whole surface. The canvas is stored
+// column by column, so it is uploaded as a texture rotated by 90 degrees and
+// turned back the right way here.
+
+@group(0) @binding(0)
+var canvas: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle big enough to cover the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(canvas, vec2<i32>(i32(position.y), i32(position.x)), 0);
+}
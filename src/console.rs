@@ -0,0 +1,619 @@
+use std::{
+    collections::VecDeque,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use glam::{Vec2, Vec3};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    canvas::Canvas,
+    font::Font,
+    game::GameState,
+    generate::{self, Settings},
+    level::LevelState,
+    primitives::{RectDescriptor, TextDescriptor},
+    script::Scripts,
+    validate::{validate, Severity},
+    wad::Wad,
+};
+
+/// Lines of output kept for the console to scroll through.
+const SCROLLBACK: usize = 256;
+
+/// How far in front of the player `spawn` puts a monster.
+const SPAWN_DISTANCE: f32 = 2.0;
+
+/// Key that opens and closes the console. It never reaches the game.
+const TOGGLE_KEY: VirtualKeyCode = VirtualKeyCode::Grave;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CvarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Text(String),
+}
+
+impl CvarValue {
+    /// Parses `text` as the same type as `self`.
+    pub fn parse_as(&self, text: &str) -> Result<CvarValue, String> {
+        let invalid = |kind: &str| format!("expected {kind}, got \"{text}\"");
+
+        match self {
+            CvarValue::Bool(_) => match text {
+                "1" | "true" | "on" => Ok(CvarValue::Bool(true)),
+                "0" | "false" | "off" => Ok(CvarValue::Bool(false)),
+                _ => Err(invalid("a boolean")),
+            },
+            CvarValue::Int(_) => text
+                .parse()
+                .map(CvarValue::Int)
+                .map_err(|_| invalid("an integer")),
+            CvarValue::Float(_) => text
+                .parse()
+                .map(CvarValue::Float)
+                .map_err(|_| invalid("a number")),
+            CvarValue::Text(_) => Ok(CvarValue::Text(text.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CvarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CvarValue::Bool(value) => write!(f, "{}", *value as u8),
+            CvarValue::Int(value) => write!(f, "{value}"),
+            CvarValue::Float(value) => write!(f, "{value}"),
+            CvarValue::Text(value) => write!(f, "\"{value}\""),
+        }
+    }
+}
+
+/// A setting exposed to the console, with its current value.
+#[derive(Debug, Clone)]
+pub struct Cvar {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value: CvarValue,
+}
+
+/// Implemented by the parts of the game that own settings the console can
+/// read and change. The owner keeps the real value, so changes made outside
+/// the console (like Tab switching to the map) are always reflected.
+pub trait Cvars {
+    fn cvars(&self) -> Vec<Cvar>;
+
+    /// Called with a value of the same type `cvars` reported for `name`.
+    /// Returns false when `name` isn't one of this owner's cvars.
+    fn set_cvar(&mut self, name: &str, value: CvarValue) -> bool;
+}
+
+/// Drop-down developer console. Lines typed into it are queued by `input`
+/// and run by `run_pending`, `execute` runs a single line directly and works
+/// without a window.
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    line: String,
+    pending: Vec<String>,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    /// Scripts being run by `exec`, so one can't run itself.
+    executing: Vec<PathBuf>,
+}
+
+impl Console {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the console, used to get problems in front of the player.
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn output(&self) -> &VecDeque<String> {
+        &self.output
+    }
+
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            log::info!("console: {line}");
+            if self.output.len() == SCROLLBACK {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+    }
+
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(TOGGLE_KEY),
+                        ..
+                    },
+                ..
+            } => {
+                self.open = !self.open;
+                true
+            }
+            WindowEvent::KeyboardInput { input, .. } if self.open => {
+                if input.state == ElementState::Pressed {
+                    self.key_pressed(input.virtual_keycode);
+                }
+                true
+            }
+            WindowEvent::ReceivedCharacter(c) if self.open => {
+                if !c.is_control() && *c != '`' && *c != '~' {
+                    self.line.push(*c);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn key_pressed(&mut self, key: Option<VirtualKeyCode>) {
+        match key {
+            Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+                let line = std::mem::take(&mut self.line);
+                if !line.trim().is_empty() {
+                    self.history.push(line.clone());
+                }
+                self.history_index = None;
+                self.pending.push(line);
+            }
+            Some(VirtualKeyCode::Back) => {
+                self.line.pop();
+            }
+            Some(VirtualKeyCode::Escape) => self.open = false,
+            Some(VirtualKeyCode::Up) if !self.history.is_empty() => {
+                let index = self
+                    .history_index
+                    .map_or(self.history.len() - 1, |index| index.saturating_sub(1));
+                self.history_index = Some(index);
+                self.line = self.history[index].clone();
+            }
+            Some(VirtualKeyCode::Down) => {
+                match self.history_index {
+                    Some(index) if index + 1 < self.history.len() => {
+                        self.history_index = Some(index + 1);
+                        self.line = self.history[index + 1].clone();
+                    }
+                    _ => {
+                        self.history_index = None;
+                        self.line.clear();
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs the lines entered since the last call.
+    pub fn run_pending(&mut self, game_state: &mut GameState, cvars: &mut [&mut dyn Cvars]) {
+        for line in std::mem::take(&mut self.pending) {
+            self.print(&format!("> {line}"));
+            self.execute_and_print(&line, game_state, cvars);
+        }
+    }
+
+    /// Runs every line of a script, `//` starts a comment. A failing line is
+    /// reported and the rest of the script still runs. Scripts can `exec`
+    /// others but not one that's already running.
+    pub fn exec<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        game_state: &mut GameState,
+        cvars: &mut [&mut dyn Cvars],
+    ) -> io::Result<()> {
+        let script = fs::read_to_string(&path)?;
+        let path = fs::canonicalize(path)?;
+        if self.executing.contains(&path) {
+            return Err(io::Error::other("it is already running"));
+        }
+
+        self.executing.push(path);
+        for line in script.lines() {
+            let line = line.split("//").next().unwrap_or_default();
+            self.execute_and_print(line, game_state, cvars);
+        }
+        self.executing.pop();
+        Ok(())
+    }
+
+    fn execute_and_print(
+        &mut self,
+        line: &str,
+        game_state: &mut GameState,
+        cvars: &mut [&mut dyn Cvars],
+    ) {
+        match self.execute(line, game_state, cvars) {
+            Ok(text) => self.print(&text),
+            Err(e) => self.print(&format!("error: {e}")),
+        }
+    }
+
+    /// Runs one command, returning what it printed. `cvars` are the owners
+    /// besides `game_state`, pass none when running without a renderer.
+    pub fn execute(
+        &mut self,
+        line: &str,
+        game_state: &mut GameState,
+        cvars: &mut [&mut dyn Cvars],
+    ) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(String::new());
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (command, &args[..]) {
+            ("help", []) => Ok(HELP.to_string()),
+            ("clear", []) => {
+                self.output.clear();
+                Ok(String::new())
+            }
+            ("echo", words) => Ok(words.join(" ")),
+            ("exec", [path]) => {
+                self.exec(path, game_state, cvars)
+                    .map_err(|e| format!("could not run {path}: {e}"))?;
+                Ok(String::new())
+            }
+            ("map", [path]) => {
+                let level =
+                    LevelState::_load(path).map_err(|e| format!("could not load {path}: {e}"))?;
+                if let Some(issue) = validate(&level)
+                    .into_iter()
+                    .find(|issue| issue.severity == Severity::Error)
+                {
+                    return Err(format!("could not load {path}, {issue}"));
+                }
+                let scripts = Scripts::for_level(&level, Some(Path::new(path)));
+                game_state.set_level(level);
+                game_state.set_level_path(Some(path.into()));
+                game_state.monsters_mut().clear();
+                match scripts {
+                    Ok(scripts) => {
+                        game_state.set_scripts(scripts);
+                        Ok(format!("loaded {path}"))
+                    }
+                    Err(e) => {
+                        game_state.set_scripts(None);
+                        Ok(format!("loaded {path} without its script, {e}"))
+                    }
+                }
+            }
+            ("generate", [seed]) | ("generate", [seed, _]) => {
+                let seed = seed
+                    .parse()
+                    .map_err(|_| format!("expected a seed, got \"{seed}\""))?;
+                let mut settings = Settings::default();
+                if let Some(rooms) = args.get(1) {
+                    settings.rooms = rooms
+                        .parse()
+                        .map_err(|_| format!("expected a number of rooms, got \"{rooms}\""))?;
+                }
+                let level = generate::generate(seed, &settings)?;
+                if let Some(issue) = validate(&level)
+                    .into_iter()
+                    .find(|issue| issue.severity == Severity::Error)
+                {
+                    return Err(format!("generated an invalid level, {issue}"));
+                }
+                let sectors = level.sectors().len();
+                game_state.set_level(level);
+                game_state.set_level_path(None);
+                game_state.set_scripts(None);
+                game_state.teleport(Vec2::ZERO);
+                game_state.monsters_mut().clear();
+                Ok(format!("generated level {seed}, {sectors} sectors"))
+            }
+            ("wad", [path]) | ("wad", [path, _]) => {
+                let wad = Wad::load(path).map_err(|e| format!("could not load {path}: {e}"))?;
+                let name = match args.get(1) {
+                    Some(name) => name.to_uppercase(),
+                    None => wad
+                        .maps()
+                        .first()
+                        .ok_or_else(|| format!("{path} has no maps"))?
+                        .to_string(),
+                };
+                let map = wad
+                    .import_map(&name)
+                    .map_err(|e| format!("could not import {name}: {e}"))?;
+                let sectors = map.level.sectors().len();
+                let start = map.start;
+                game_state.set_level(map.level);
+                game_state.set_level_path(None);
+                game_state.set_scripts(None);
+                game_state.monsters_mut().clear();
+                if let Some((pos, rot)) = start {
+                    game_state.teleport(pos);
+                    game_state.set_rot(rot);
+                }
+                Ok(format!("imported {name} from {path}, {sectors} sectors"))
+            }
+            ("tp", [name]) => {
+                let pos = game_state
+                    .teleport_to_sector(name)
+                    .ok_or_else(|| format!("no sector called \"{name}\""))?;
+                Ok(format!("teleported to {name} at {} {}", pos.x, pos.y))
+            }
+            ("tp", [x, y]) => {
+                let pos = parse_vec2(x, y)?;
+                game_state.teleport(pos);
+                Ok(format!("teleported to {} {}", pos.x, pos.y))
+            }
+            ("walk", []) => {
+                game_state.stop_walking();
+                Ok("stopped walking".to_string())
+            }
+            ("walk", [name]) => {
+                let sector_id = game_state
+                    .level()
+                    .find_sector(name)
+                    .ok_or_else(|| format!("no sector called \"{name}\""))?;
+                let target = game_state.level().sector(sector_id).centroid();
+                walk_to(game_state, target)
+            }
+            ("walk", [x, y]) => walk_to(game_state, parse_vec2(x, y)?),
+            ("spawn", [kind]) => {
+                let facing = game_state.rot().truncate();
+                let pos = game_state.pos() + facing.normalize_or_zero() * SPAWN_DISTANCE;
+                spawn(game_state, kind, pos, -facing, None)
+            }
+            ("spawn", [kind, x, y]) | ("spawn", [kind, x, y, _]) => {
+                let pos = parse_vec2(x, y)?;
+                let facing = game_state.pos() - pos;
+                let tag = match args.get(3) {
+                    Some(tag) => Some(
+                        tag.parse()
+                            .map_err(|_| format!("expected a tag number, got \"{tag}\""))?,
+                    ),
+                    None => None,
+                };
+                spawn(game_state, kind, pos, facing, tag)
+            }
+            ("monsters", []) => {
+                let monsters = game_state.monsters();
+                let mut lines = vec![format!("kinds: {}", monsters.defs().kinds().join(" "))];
+                lines.extend(monsters.iter().map(|monster| {
+                    format!(
+                        "{} at {:.2} {:.2}, {} health, {}",
+                        monster.kind(),
+                        monster.pos().x,
+                        monster.pos().y,
+                        monster.health(),
+                        if monster.is_dead() {
+                            "dead"
+                        } else {
+                            monster.state()
+                        },
+                    )
+                }));
+                Ok(lines.join("\n"))
+            }
+            ("movers", []) => {
+                let lines: Vec<String> = game_state
+                    .movers()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, mover)| {
+                        let def = mover.def();
+                        format!(
+                            "{index}: {:?} in sector {}, {:?}, {:?}",
+                            def.kind,
+                            def.sector,
+                            def.trigger,
+                            mover.motion()
+                        )
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    return Ok("the level has no movers".to_string());
+                }
+                Ok(lines.join("\n"))
+            }
+            ("activate", [index]) => {
+                let index: usize = index
+                    .parse()
+                    .map_err(|_| format!("expected a mover number, got \"{index}\""))?;
+                if !game_state.activate_mover(index) {
+                    return Err(format!("there is no mover {index}"));
+                }
+                Ok(format!("activated mover {index}"))
+            }
+            ("noclip", []) => {
+                let noclip = !game_state.noclip();
+                game_state.set_noclip(noclip);
+                Ok(format!("noclip {}", if noclip { "on" } else { "off" }))
+            }
+            ("god", []) => {
+                let god = !game_state.god();
+                game_state.set_god(god);
+                Ok(format!("god mode {}", if god { "on" } else { "off" }))
+            }
+            ("fly", []) => {
+                let fly = !game_state.fly();
+                game_state.set_fly(fly);
+                Ok(format!("fly {}", if fly { "on" } else { "off" }))
+            }
+            ("script", []) => {
+                let scripts = Scripts::for_level(game_state.level(), game_state.level_path())?
+                    .ok_or_else(|| "the level has no script".to_string())?;
+                let name = scripts.name().to_string();
+                game_state.set_scripts(Some(scripts));
+                Ok(format!("reloaded {name}"))
+            }
+            ("where", []) => Ok(game_state.where_am_i()),
+            // `map` on its own loads a file, so the map view's cvar has a
+            // longer name that `toggle` doesn't need
+            ("toggle", ["map"]) => self.execute("toggle show_map", game_state, cvars),
+            ("toggle", [name]) => match find_cvar(name, game_state, cvars)? {
+                CvarValue::Bool(value) => {
+                    set_cvar(name, CvarValue::Bool(!value), game_state, cvars);
+                    Ok(format!("{name} {}", CvarValue::Bool(!value)))
+                }
+                _ => Err(format!("{name} is not a boolean")),
+            },
+            ("cvars", []) => {
+                let mut all = game_state.cvars();
+                for owner in cvars.iter() {
+                    all.extend(owner.cvars());
+                }
+                all.sort_by_key(|cvar| cvar.name);
+                Ok(all
+                    .iter()
+                    .map(|cvar| format!("{} {} - {}", cvar.name, cvar.value, cvar.description))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            (name, []) => {
+                let value = find_cvar(name, game_state, cvars)?;
+                Ok(format!("{name} {value}"))
+            }
+            (name, args) => {
+                let value = find_cvar(name, game_state, cvars)?.parse_as(&args.join(" "))?;
+                set_cvar(name, value.clone(), game_state, cvars);
+                Ok(format!("{name} {value}"))
+            }
+        }
+    }
+
+    /// Fills the top half of the screen with the most recent output and the
+    /// line being typed.
+    pub fn draw(&self, canvas: &mut Canvas, font: &Font) {
+        if !self.open {
+            return;
+        }
+
+        let height = canvas.height() as f32 / 2.0;
+        let line_height = font.height() as f32;
+        canvas.draw_rect(&RectDescriptor {
+            top_left: Vec2::ZERO,
+            size: Vec2::new(canvas.width() as f32, height),
+            color: Vec3::new(0.05, 0.05, 0.1),
+        });
+
+        let prompt = format!("> {}_", self.line);
+        let lines = std::iter::once(&prompt).chain(self.output.iter().rev());
+        for (index, text) in lines.enumerate() {
+            let top = height - 4.0 - (index + 1) as f32 * line_height;
+            if top < 0.0 {
+                break;
+            }
+            canvas.draw_text(
+                font,
+                &TextDescriptor {
+                    text,
+                    top_left: Vec2::new(4.0, top),
+                    color: Vec3::splat(if index == 0 { 1.0 } else { 0.8 }),
+                    scale: 1,
+                    alpha: 1.0,
+                },
+            );
+        }
+    }
+}
+
+const HELP: &str = "\
+help               this list
+cvars              list every cvar with its value
+<cvar> [value]     show or set a cvar
+toggle <cvar>      flip a boolean cvar, map is short for show_map
+map <file>         load a level, it reloads when the file changes
+wad <file> [map]   import a map from a Doom WAD, the first one by default
+generate <seed> [rooms]
+                   build a random level of rooms and corridors
+tp <x> <y>         move the player
+tp <sector>        move to the middle of a named sector
+walk <x> <y>       walk along the shortest path to a point
+walk <sector>      walk to the middle of a named sector
+walk               stop walking
+spawn <kind> [<x> <y> [tag]]
+                   add a monster, in front of the player by default
+monsters           list the monster kinds and every monster
+movers             list the level's doors, lifts and crushers
+activate <n>       set off a mover as if it had been triggered
+script             reload the level's script and run it from the start
+noclip             walk through walls and up any step
+god                take no damage
+fly                move up and down with space and control
+where              print the player's position and sector
+exec <file>        run a script
+echo <text>        print text
+clear              clear the console";
+
+fn spawn(
+    game_state: &mut GameState,
+    kind: &str,
+    pos: Vec2,
+    facing: Vec2,
+    tag: Option<u32>,
+) -> Result<String, String> {
+    game_state
+        .monsters_mut()
+        .spawn(kind, pos, facing)?
+        .set_tag(tag);
+    Ok(format!("spawned {kind} at {:.2} {:.2}", pos.x, pos.y))
+}
+
+fn walk_to(game_state: &mut GameState, target: Vec2) -> Result<String, String> {
+    let from = game_state.pos();
+    let path = game_state
+        .walk_to(target)
+        .ok_or_else(|| format!("no way to {} {}", target.x, target.y))?;
+    let length: f32 = std::iter::once(from)
+        .chain(path.iter().copied())
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum();
+    Ok(format!(
+        "walking {length:.2} units through {} waypoints",
+        path.len()
+    ))
+}
+
+fn set_cvar(
+    name: &str,
+    value: CvarValue,
+    game_state: &mut GameState,
+    cvars: &mut [&mut dyn Cvars],
+) {
+    if !game_state.set_cvar(name, value.clone()) {
+        for owner in cvars.iter_mut() {
+            if owner.set_cvar(name, value.clone()) {
+                break;
+            }
+        }
+    }
+}
+
+fn find_cvar(
+    name: &str,
+    game_state: &GameState,
+    cvars: &[&mut dyn Cvars],
+) -> Result<CvarValue, String> {
+    std::iter::once(game_state.cvars())
+        .chain(cvars.iter().map(|owner| owner.cvars()))
+        .flatten()
+        .find(|cvar| cvar.name == name)
+        .map(|cvar| cvar.value)
+        .ok_or_else(|| format!("unknown command or cvar \"{name}\""))
+}
+
+fn parse_vec2(x: &str, y: &str) -> Result<Vec2, String> {
+    let parse = |text: &str| {
+        text.parse::<f32>()
+            .map_err(|_| format!("expected a number, got \"{text}\""))
+    };
+    Ok(Vec2::new(parse(x)?, parse(y)?))
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3};
+
+use crate::level::{signed_area, Line, Sector, SectorId, Wall};
+
+/// Turns sharper than this count as a corner rather than a straight run.
+const EPSILON: f32 = 1e-5;
+
+/// A closed ring of points with a wall along every edge, the edge from
+/// `points[i]` to `points[i + 1]` is `walls[i]`.
+#[derive(Debug, Clone)]
+pub struct Outline {
+    pub points: Vec<Vec2>,
+    pub walls: Vec<Wall>,
+}
+
+impl Outline {
+    /// An outline with the same solid wall on every edge.
+    pub fn solid(points: Vec<Vec2>, color: Vec3) -> Self {
+        let walls = vec![Wall::Solid(color); points.len()];
+        Self { points, walls }
+    }
+
+    /// The same ring going the other way, each wall stays on its edge.
+    fn reversed(&self) -> Self {
+        let len = self.points.len();
+        Self {
+            points: self.points.iter().rev().copied().collect(),
+            walls: (0..len)
+                .map(|index| self.walls[(2 * len - 2 - index) % len])
+                .collect(),
+        }
+    }
+}
+
+/// A room of any simple shape, optionally with holes such as pillars.
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    pub outline: Outline,
+    pub holes: Vec<Outline>,
+    pub base_height: f32,
+    pub height: f32,
+}
+
+/// Splits `polygon` into convex sectors joined by portals, numbered from
+/// `first_id` so they can be appended to a level with that many sectors.
+///
+/// Holes are joined to the outline with bridges, the result is cut into
+/// triangles by ear clipping, and triangles are merged back together across
+/// any diagonal whose removal leaves the piece convex (Hertel-Mehlhorn). The
+/// outline's walls end up on the matching edges of the pieces, the edges
+/// between pieces become portals.
+pub fn decompose(polygon: &Polygon, first_id: SectorId) -> Result<Vec<Sector>, String> {
+    let mut rings = vec![&polygon.outline];
+    rings.extend(&polygon.holes);
+    for ring in &rings {
+        if ring.points.len() < 3 || ring.walls.len() != ring.points.len() {
+            return Err("every outline needs at least 3 points and a wall per edge".to_string());
+        }
+    }
+
+    // Work anticlockwise with y up, holes go the other way so the inside is
+    // always on the left of an edge
+    let outline = if signed_area(&polygon.outline.points) < 0.0 {
+        polygon.outline.reversed()
+    } else {
+        polygon.outline.clone()
+    };
+    let holes: Vec<Outline> = polygon
+        .holes
+        .iter()
+        .map(|hole| {
+            if signed_area(&hole.points) > 0.0 {
+                hole.reversed()
+            } else {
+                hole.clone()
+            }
+        })
+        .collect();
+
+    let mut vertices = vec![];
+    let mut walls = HashMap::new();
+    let mut rings = vec![];
+    for ring in std::iter::once(&outline).chain(&holes) {
+        let first = vertices.len();
+        let len = ring.points.len();
+        vertices.extend(&ring.points);
+        for index in 0..len {
+            walls.insert(
+                (first + index, first + (index + 1) % len),
+                ring.walls[index],
+            );
+        }
+        rings.push((first..first + len).collect::<Vec<usize>>());
+    }
+
+    let edges: Vec<(usize, usize)> = walls.keys().copied().collect();
+    for (index, (a, b)) in edges.iter().enumerate() {
+        for (c, d) in &edges[index + 1..] {
+            if segments_cross(vertices[*a], vertices[*b], vertices[*c], vertices[*d]) {
+                return Err("the outline crosses itself".to_string());
+            }
+        }
+    }
+
+    let boundary = bridge_holes(&vertices, rings)?;
+    let triangles = triangulate(&vertices, boundary)?;
+    let pieces = merge(&vertices, triangles);
+
+    let mut owners = HashMap::new();
+    for (piece_index, piece) in pieces.iter().enumerate() {
+        for index in 0..piece.len() {
+            owners.insert(
+                (piece[index], piece[(index + 1) % piece.len()]),
+                piece_index,
+            );
+        }
+    }
+
+    pieces
+        .iter()
+        .map(|piece| {
+            // Sectors go clockwise, so the points are reversed
+            let points: Vec<Vec2> = piece.iter().rev().map(|vertex| vertices[*vertex]).collect();
+            let len = piece.len();
+            let lines = (0..len)
+                .map(|index| {
+                    let (a, b) = (piece[index], piece[(index + 1) % len]);
+                    let wall = match walls.get(&(a, b)) {
+                        Some(wall) => *wall,
+                        None => {
+                            let neighbour = owners.get(&(b, a)).ok_or_else(|| {
+                                format!(
+                                    "nothing is on the other side of the edge from {} to {}",
+                                    vertices[a], vertices[b]
+                                )
+                            })?;
+                            Wall::Portal(first_id + neighbour)
+                        }
+                    };
+                    Ok(Line::new(
+                        wall,
+                        len - 1 - index,
+                        (2 * len - 2 - index) % len,
+                    ))
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(Sector::new(
+                points,
+                lines,
+                polygon.base_height,
+                polygon.height,
+            ))
+        })
+        .collect()
+}
+
+/// Cuts every hole into the outline along a line to a vertex it can see,
+/// leaving one ring that visits both ends of each cut twice.
+fn bridge_holes(vertices: &[Vec2], mut rings: Vec<Vec<usize>>) -> Result<Vec<usize>, String> {
+    let mut boundary = rings.remove(0);
+
+    // Rightmost holes first, so later cuts can't cross earlier ones
+    let rightmost = |ring: &Vec<usize>| {
+        ring.iter()
+            .copied()
+            .max_by(|a, b| vertices[*a].x.total_cmp(&vertices[*b].x))
+            .unwrap()
+    };
+    rings.sort_by(|a, b| {
+        vertices[rightmost(b)]
+            .x
+            .total_cmp(&vertices[rightmost(a)].x)
+    });
+
+    for (hole_index, hole) in rings.iter().enumerate() {
+        let start = rightmost(hole);
+        let from = vertices[start];
+
+        let mut candidates: Vec<usize> = (0..boundary.len()).collect();
+        candidates.sort_by(|a, b| {
+            from.distance(vertices[boundary[*a]])
+                .total_cmp(&from.distance(vertices[boundary[*b]]))
+        });
+
+        let visible = candidates.into_iter().find(|candidate| {
+            let to = vertices[boundary[*candidate]];
+            let edges = ring_edges(&boundary)
+                .chain(rings[hole_index..].iter().flat_map(|ring| ring_edges(ring)));
+            let blocked = edges.clone().any(|(a, b)| {
+                let (a, b) = (vertices[a], vertices[b]);
+                segments_cross(from, to, a, b)
+            });
+            let middle = (from + to) / 2.0;
+            !blocked
+                && inside(vertices, &boundary, middle)
+                && !rings[hole_index..]
+                    .iter()
+                    .any(|ring| inside(vertices, ring, middle))
+        });
+        let Some(candidate) = visible else {
+            return Err("a hole is not inside the outline".to_string());
+        };
+
+        let position = hole.iter().position(|vertex| *vertex == start).unwrap();
+        let mut cut = vec![];
+        for index in 0..=hole.len() {
+            cut.push(hole[(position + index) % hole.len()]);
+        }
+        cut.push(boundary[candidate]);
+        boundary.splice(candidate + 1..candidate + 1, cut);
+    }
+
+    Ok(boundary)
+}
+
+/// Ear clipping. Points sitting on an ear's corners, like the second visit
+/// to either end of a hole's bridge, don't stop it being clipped.
+fn triangulate(vertices: &[Vec2], mut ring: Vec<usize>) -> Result<Vec<[usize; 3]>, String> {
+    let mut triangles = vec![];
+
+    while ring.len() > 3 {
+        let len = ring.len();
+        let ear = (0..len).find(|index| {
+            let (a, b, c) = (
+                ring[(index + len - 1) % len],
+                ring[*index],
+                ring[(index + 1) % len],
+            );
+            let (pa, pb, pc) = (vertices[a], vertices[b], vertices[c]);
+            if (pb - pa).perp_dot(pc - pb) <= EPSILON {
+                return false;
+            }
+            !ring.iter().any(|other| {
+                let point = vertices[*other];
+                point != pa && point != pb && point != pc && in_triangle(point, pa, pb, pc)
+            })
+        });
+
+        match ear {
+            Some(index) => {
+                triangles.push([
+                    ring[(index + len - 1) % len],
+                    ring[index],
+                    ring[(index + 1) % len],
+                ]);
+                ring.remove(index);
+            }
+            None => {
+                // Only straight runs and slivers left, drop a vertex that
+                // doesn't turn
+                let flat = (0..len).find(|index| {
+                    let (pa, pb, pc) = (
+                        vertices[ring[(index + len - 1) % len]],
+                        vertices[ring[*index]],
+                        vertices[ring[(index + 1) % len]],
+                    );
+                    (pb - pa).perp_dot(pc - pb).abs() <= EPSILON
+                });
+                match flat {
+                    Some(index) => {
+                        ring.remove(index);
+                    }
+                    None => return Err("the outline crosses itself".to_string()),
+                }
+            }
+        }
+    }
+
+    if signed_area(
+        &ring
+            .iter()
+            .map(|vertex| vertices[*vertex])
+            .collect::<Vec<_>>(),
+    ) > EPSILON
+    {
+        triangles.push([ring[0], ring[1], ring[2]]);
+    }
+    Ok(triangles)
+}
+
+/// Joins neighbouring pieces whenever the result is still convex.
+fn merge(vertices: &[Vec2], triangles: Vec<[usize; 3]>) -> Vec<Vec<usize>> {
+    let mut pieces: Vec<Vec<usize>> = triangles.iter().map(|triangle| triangle.to_vec()).collect();
+
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'search: for first in 0..pieces.len() {
+            for second in first + 1..pieces.len() {
+                if let Some(piece) = join(&pieces[first], &pieces[second]) {
+                    if is_convex(vertices, &piece) {
+                        pieces[first] = piece;
+                        pieces.remove(second);
+                        merged = true;
+                        break 'search;
+                    }
+                }
+            }
+        }
+    }
+
+    pieces
+}
+
+/// The two pieces as one ring, if they share an edge.
+fn join(first: &[usize], second: &[usize]) -> Option<Vec<usize>> {
+    for index in 0..first.len() {
+        let (a, b) = (first[index], first[(index + 1) % first.len()]);
+        let Some(other) = (0..second.len())
+            .find(|other| second[*other] == b && second[(other + 1) % second.len()] == a)
+        else {
+            continue;
+        };
+
+        // Around the first piece from b back to a, then the second piece's
+        // vertices between a and b
+        let mut ring: Vec<usize> = (0..first.len())
+            .map(|offset| first[(index + 1 + offset) % first.len()])
+            .collect();
+        ring.extend((2..second.len()).map(|offset| second[(other + offset) % second.len()]));
+        return Some(ring);
+    }
+    None
+}
+
+/// Every corner turns left or goes straight on, and the ring doesn't double
+/// back on itself.
+fn is_convex(vertices: &[Vec2], ring: &[usize]) -> bool {
+    let len = ring.len();
+    (0..len).all(|index| {
+        let a = vertices[ring[index]];
+        let b = vertices[ring[(index + 1) % len]];
+        let c = vertices[ring[(index + 2) % len]];
+        let turn = (b - a).perp_dot(c - b);
+        turn > EPSILON || (turn.abs() <= EPSILON && (b - a).dot(c - b) > 0.0)
+    })
+}
+
+fn ring_edges(ring: &[usize]) -> impl Iterator<Item = (usize, usize)> + Clone + '_ {
+    (0..ring.len()).map(|index| (ring[index], ring[(index + 1) % ring.len()]))
+}
+
+/// True when the segments cross at a point that isn't an end of either.
+fn segments_cross(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    if a == c || a == d || b == c || b == d {
+        return false;
+    }
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+}
+
+fn in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(point - a) >= 0.0
+        && (c - b).perp_dot(point - b) >= 0.0
+        && (a - c).perp_dot(point - c) >= 0.0
+}
+
+/// Even-odd test against a ring.
+fn inside(vertices: &[Vec2], ring: &[usize], point: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in ring_edges(ring) {
+        let (a, b) = (vertices[a], vertices[b]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
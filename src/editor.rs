@@ -0,0 +1,963 @@
+use std::cmp::{Ordering, Reverse};
+
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use winit::{
+    dpi::PhysicalSize,
+    event::{
+        ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
+};
+
+use crate::{
+    canvas::Canvas,
+    console::{Cvar, CvarValue, Cvars},
+    decompose::{decompose, Outline, Polygon},
+    font::Font,
+    game::GameState,
+    level::{LevelState, Sector, SectorId, Wall},
+    mover::{MoverDef, Trigger},
+    primitives::{CricleDescriptor, LineDescriptor, RectDescriptor, TextDescriptor},
+    validate::{validate, Severity, ValidationIssue},
+};
+
+/// Edits kept for undo.
+const UNDO_LIMIT: usize = 100;
+
+/// How close, in pixels, the cursor has to be to pick a vertex or line.
+const PICK_DISTANCE: f32 = 8.0;
+
+/// Amount `base_height` and `height` change by per key press.
+const HEIGHT_STEP: f32 = 0.25;
+
+const NEW_SECTOR_HEIGHT: f32 = 2.5;
+
+/// Colours walls cycle through, the first is used for new walls.
+const PALETTE: [Vec3; 8] = [
+    Vec3::new(0.8, 0.8, 0.8),
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0),
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(0.0, 1.0, 1.0),
+    Vec3::new(1.0, 0.0, 1.0),
+    Vec3::new(1.0, 0.5, 0.0),
+];
+
+/// Textures lines cycle through after none, the built-in ones.
+const TEXTURES: [&str; 2] = ["bricks", "slime"];
+
+const HELP: &str = "N draw  Enter finish  S split  P portal  C colour  T texture  K sky  PgUp/PgDn floor  Home/End height  Del delete  G snap  Ctrl+Z/Y undo/redo  Ctrl+S save  E exit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    Sector(SectorId),
+    Line(SectorId, usize),
+}
+
+impl Selection {
+    fn sector(self) -> SectorId {
+        match self {
+            Selection::Sector(sector_id) | Selection::Line(sector_id, _) => sector_id,
+        }
+    }
+}
+
+/// The sectors and the movers that refer to them, what an undo step puts
+/// back.
+#[derive(Clone)]
+struct Snapshot {
+    sectors: Vec<Sector>,
+    movers: Vec<MoverDef>,
+}
+
+impl Snapshot {
+    fn of(level: &LevelState) -> Self {
+        Self {
+            sectors: level.sectors().clone(),
+            movers: level.movers().to_vec(),
+        }
+    }
+}
+
+/// A vertex being dragged. The level is only rebuilt and checked once it's
+/// let go, and only if it moved.
+struct Drag {
+    /// Every sector point being dragged, they all started in the same place.
+    points: Vec<(SectorId, usize)>,
+    from: Vec2,
+    to: Vec2,
+    /// The level when the drag started.
+    before: Snapshot,
+    /// The sectors with the points moved, drawn while dragging.
+    sectors: Vec<Sector>,
+}
+
+/// 2D level editor, opened with E from the map view. It edits the level in
+/// `GameState` directly, so leaving the editor shows the changes in 3D
+/// straight away, and checks the level with `validate` after every edit.
+pub struct Editor {
+    active: bool,
+    screen: Vec2,
+    /// World position in the middle of the screen.
+    centre: Vec2,
+    /// Pixels per world unit.
+    zoom: f32,
+    grid: f32,
+    snap: bool,
+    cursor: Vec2,
+    modifiers: ModifiersState,
+    panning: bool,
+    drag: Option<Drag>,
+    selection: Option<Selection>,
+    /// Points of the sector being drawn.
+    drawing: Option<Vec<Vec2>>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// `GameState::level_generation` of the level last edited, any other
+    /// level was swapped in from outside the editor.
+    generation: u64,
+    path: String,
+    issues: Vec<ValidationIssue>,
+    status: String,
+}
+
+impl Editor {
+    pub fn new(size: PhysicalSize<u32>) -> Self {
+        Self {
+            active: false,
+            screen: Vec2::new(size.width as f32, size.height as f32),
+            centre: Vec2::ZERO,
+            zoom: 100.0,
+            grid: 0.25,
+            snap: true,
+            cursor: Vec2::ZERO,
+            modifiers: ModifiersState::empty(),
+            panning: false,
+            drag: None,
+            selection: None,
+            drawing: None,
+            undo: vec![],
+            redo: vec![],
+            generation: 0,
+            path: "level.json".to_string(),
+            issues: vec![],
+            status: String::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        self.screen = Vec2::new(new_size.width as f32, new_size.height as f32);
+    }
+
+    /// `map_view` says whether the map is on screen, the editor can only be
+    /// opened from there.
+    pub fn input(
+        &mut self,
+        event: &WindowEvent,
+        game_state: &mut GameState,
+        map_view: bool,
+    ) -> bool {
+        if let WindowEvent::ModifiersChanged(modifiers) = event {
+            self.modifiers = *modifiers;
+            return false;
+        }
+        self.follow_level(game_state);
+
+        if !self.active {
+            if map_view && pressed(event) == Some(VirtualKeyCode::E) {
+                self.active = true;
+                self.centre = game_state.pos();
+                self.issues = validate(game_state.level());
+                self.status = "editing".to_string();
+                return true;
+            }
+            return false;
+        }
+
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let (ElementState::Pressed, Some(key)) = (input.state, input.virtual_keycode) {
+                    self.key_pressed(key, game_state);
+                }
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = Vec2::new(position.x as f32, position.y as f32);
+                if self.panning {
+                    self.centre -= (cursor - self.cursor) / self.zoom;
+                }
+                self.cursor = cursor;
+                self.drag();
+                true
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                match (button, state) {
+                    (MouseButton::Left, ElementState::Pressed) => self.click(game_state),
+                    (MouseButton::Left, ElementState::Released) => self.end_drag(game_state),
+                    (MouseButton::Right | MouseButton::Middle, state) => {
+                        self.panning = *state == ElementState::Pressed
+                    }
+                    _ => {}
+                }
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+                };
+                // Keep the point under the cursor where it is
+                let anchor = self.to_world(self.cursor);
+                self.zoom = (self.zoom * 1.1_f32.powf(steps)).clamp(5.0, 2000.0);
+                self.centre += anchor - self.to_world(self.cursor);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forgets everything about the last level once another one has been
+    /// swapped in, by the console or a level exit.
+    fn follow_level(&mut self, game_state: &GameState) {
+        if self.generation == game_state.level_generation() {
+            return;
+        }
+        self.generation = game_state.level_generation();
+        self.selection = None;
+        self.drag = None;
+        self.undo.clear();
+        self.redo.clear();
+        self.issues = validate(game_state.level());
+    }
+
+    fn key_pressed(&mut self, key: VirtualKeyCode, game_state: &mut GameState) {
+        let ctrl = self.modifiers.ctrl();
+
+        match key {
+            VirtualKeyCode::Escape if self.drawing.is_some() => self.drawing = None,
+            VirtualKeyCode::Escape | VirtualKeyCode::E => {
+                self.active = false;
+                self.drawing = None;
+                self.end_drag(game_state);
+            }
+            VirtualKeyCode::Z if ctrl && self.modifiers.shift() => self.redo(game_state),
+            VirtualKeyCode::Z if ctrl => self.undo(game_state),
+            VirtualKeyCode::Y if ctrl => self.redo(game_state),
+            VirtualKeyCode::S if ctrl => {
+                self.status = match game_state.level()._save(&self.path) {
+                    Ok(_) => format!("saved {}", self.path),
+                    Err(e) => format!("could not save {}: {e}", self.path),
+                };
+            }
+            VirtualKeyCode::N => {
+                self.drawing = Some(vec![]);
+                self.selection = None;
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => self.finish_sector(game_state),
+            VirtualKeyCode::Back => {
+                if let Some(points) = &mut self.drawing {
+                    points.pop();
+                }
+            }
+            VirtualKeyCode::G => self.snap = !self.snap,
+            VirtualKeyCode::S => self.split_line(game_state),
+            VirtualKeyCode::P => self.toggle_portal(game_state),
+            VirtualKeyCode::C => self.cycle_colour(game_state),
+            VirtualKeyCode::T => self.cycle_texture(game_state),
+            VirtualKeyCode::K => self.toggle_sky(game_state),
+            VirtualKeyCode::PageUp => self.change_heights(game_state, HEIGHT_STEP, 0.0),
+            VirtualKeyCode::PageDown => self.change_heights(game_state, -HEIGHT_STEP, 0.0),
+            VirtualKeyCode::Home => self.change_heights(game_state, 0.0, HEIGHT_STEP),
+            VirtualKeyCode::End => self.change_heights(game_state, 0.0, -HEIGHT_STEP),
+            VirtualKeyCode::Delete => self.delete_sector(game_state),
+            _ => {}
+        }
+    }
+
+    fn to_world(&self, screen: Vec2) -> Vec2 {
+        (screen - self.screen / 2.0) / self.zoom + self.centre
+    }
+
+    fn to_screen(&self, world: Vec2) -> Vec2 {
+        (world - self.centre) * self.zoom + self.screen / 2.0
+    }
+
+    fn cursor_world(&self) -> Vec2 {
+        let pos = self.to_world(self.cursor);
+        if self.snap {
+            (pos / self.grid).round() * self.grid
+        } else {
+            pos
+        }
+    }
+
+    /// Replaces the level's sectors and movers with `after`'s, keeping what
+    /// was there before for undo when `before` is given.
+    fn apply(&mut self, game_state: &mut GameState, after: Snapshot, before: Option<Snapshot>) {
+        if let Some(before) = before {
+            if self.undo.len() == UNDO_LIMIT {
+                self.undo.remove(0);
+            }
+            self.undo.push(before);
+            self.redo.clear();
+        }
+        let script = game_state.level().script().map(str::to_string);
+        game_state.set_level(
+            LevelState::new(after.sectors)
+                .with_movers(after.movers)
+                .with_script(script),
+        );
+        self.generation = game_state.level_generation();
+        self.issues = validate(game_state.level());
+    }
+
+    /// Runs `edit` on a copy of the sectors and applies it as one undo step
+    /// if it returns true.
+    fn edit(&mut self, game_state: &mut GameState, edit: impl FnOnce(&mut Vec<Sector>) -> bool) {
+        let before = Snapshot::of(game_state.level());
+        let mut after = before.clone();
+        if edit(&mut after.sectors) {
+            self.apply(game_state, after, Some(before));
+        }
+    }
+
+    fn undo(&mut self, game_state: &mut GameState) {
+        self.end_drag(game_state);
+        if let Some(snapshot) = self.undo.pop() {
+            self.redo.push(Snapshot::of(game_state.level()));
+            self.selection = None;
+            self.apply(game_state, snapshot, None);
+        }
+    }
+
+    fn redo(&mut self, game_state: &mut GameState) {
+        self.end_drag(game_state);
+        if let Some(snapshot) = self.redo.pop() {
+            self.undo.push(Snapshot::of(game_state.level()));
+            self.selection = None;
+            self.apply(game_state, snapshot, None);
+        }
+    }
+
+    fn click(&mut self, game_state: &mut GameState) {
+        if let Some(points) = &self.drawing {
+            let closes = points.len() >= 3
+                && self.to_screen(points[0]).distance(self.cursor) < PICK_DISTANCE;
+            if closes {
+                self.finish_sector(game_state);
+            } else {
+                let pos = self.cursor_world();
+                if let Some(points) = &mut self.drawing {
+                    points.push(pos);
+                }
+            }
+            return;
+        }
+
+        let level = game_state.level();
+        let vertex = level
+            .sectors()
+            .iter()
+            .flat_map(Sector::points)
+            .copied()
+            .find(|point| self.to_screen(*point).distance(self.cursor) < PICK_DISTANCE);
+        if let Some(vertex) = vertex {
+            self.drag = Some(Drag {
+                points: points_at(level.sectors(), vertex),
+                from: vertex,
+                to: vertex,
+                before: Snapshot::of(level),
+                sectors: level.sectors().clone(),
+            });
+            return;
+        }
+
+        let mut nearest: Option<(f32, Selection)> = None;
+        for (sector_id, sector) in level.sectors().iter().enumerate() {
+            for line_index in 0..sector.lines().len() {
+                let (start, end) = sector.line_points(line_index);
+                let distance =
+                    segment_distance(self.cursor, self.to_screen(start), self.to_screen(end));
+                if distance < PICK_DISTANCE && nearest.is_none_or(|(best, _)| distance < best) {
+                    nearest = Some((distance, Selection::Line(sector_id, line_index)));
+                }
+            }
+        }
+
+        self.selection = nearest.map(|(_, selection)| selection).or_else(|| {
+            level
+                .find_current_sector(self.to_world(self.cursor))
+                .map(Selection::Sector)
+        });
+    }
+
+    fn drag(&mut self) {
+        let pos = self.cursor_world();
+        let Some(drag) = &mut self.drag else {
+            return;
+        };
+        drag.to = pos;
+        for (sector_id, point_index) in &drag.points {
+            drag.sectors[*sector_id].set_point(*point_index, pos);
+        }
+    }
+
+    /// Applies a drag that moved its vertex as one undo step.
+    fn end_drag(&mut self, game_state: &mut GameState) {
+        if let Some(drag) = self.drag.take() {
+            if drag.to != drag.from {
+                let after = Snapshot {
+                    sectors: drag.sectors,
+                    movers: drag.before.movers.clone(),
+                };
+                self.apply(game_state, after, Some(drag.before));
+            }
+        }
+    }
+
+    fn finish_sector(&mut self, game_state: &mut GameState) {
+        let Some(points) = self.drawing.take() else {
+            return;
+        };
+        if points.len() < 3 {
+            self.status = "a sector needs at least 3 points".to_string();
+            return;
+        }
+
+        // Concave outlines are split into convex sectors joined by portals
+        let polygon = Polygon {
+            outline: Outline::solid(points, PALETTE[0]),
+            holes: vec![],
+            base_height: 0.0,
+            height: NEW_SECTOR_HEIGHT,
+        };
+        let first_id = game_state.level().sectors().len();
+        match decompose(&polygon, first_id) {
+            Ok(pieces) => {
+                self.status = format!("added {} sectors", pieces.len());
+                self.edit(game_state, |sectors| {
+                    sectors.extend(pieces);
+                    true
+                });
+                self.selection = Some(Selection::Sector(first_id));
+            }
+            Err(e) => self.status = format!("could not add sector: {e}"),
+        }
+    }
+
+    /// Splits the selected line at its middle, along with the line on the
+    /// other side of a portal so the two still match.
+    fn split_line(&mut self, game_state: &mut GameState) {
+        let Some(Selection::Line(sector_id, line_index)) = self.selection else {
+            return;
+        };
+
+        let (start, end) = game_state.level().sector(sector_id).line_points(line_index);
+        self.edit(game_state, |sectors| {
+            let mut lines = matching_lines(sectors, start, end);
+            // Later lines first so splitting doesn't move the ones still to do
+            lines.sort_by_key(|(_, line_index)| Reverse(*line_index));
+            let mut split = false;
+            for (sector_id, line_index) in lines {
+                split |= sectors[sector_id]
+                    .split_line(line_index, (start + end) / 2.0)
+                    .is_some();
+            }
+            split
+        });
+    }
+
+    /// Turns the selected wall into a portal to the sector sharing the same
+    /// edge, and back. Both sides are changed together.
+    fn toggle_portal(&mut self, game_state: &mut GameState) {
+        let Some(Selection::Line(sector_id, line_index)) = self.selection else {
+            return;
+        };
+
+        let sector = game_state.level().sector(sector_id);
+        let (start, end) = sector.line_points(line_index);
+        let portal = matches!(sector.lines()[line_index].wall_type(), Wall::Portal(_));
+        let others: Vec<_> = matching_lines(game_state.level().sectors(), start, end)
+            .into_iter()
+            .filter(|(other_id, _)| *other_id != sector_id)
+            .collect();
+
+        if !portal && others.is_empty() {
+            self.status = "no sector on the other side of this line".to_string();
+            return;
+        }
+
+        self.edit(game_state, |sectors| {
+            if portal {
+                sectors[sector_id].set_wall(line_index, Wall::Solid(PALETTE[0]));
+                for (other_id, other_line) in others {
+                    if sectors[other_id].lines()[other_line].wall_type() == Wall::Portal(sector_id)
+                    {
+                        sectors[other_id].set_wall(other_line, Wall::Solid(PALETTE[0]));
+                    }
+                }
+            } else {
+                let (other_id, other_line) = others[0];
+                sectors[sector_id].set_wall(line_index, Wall::Portal(other_id));
+                sectors[other_id].set_wall(other_line, Wall::Portal(sector_id));
+            }
+            true
+        });
+    }
+
+    /// Gives the selected wall, or every wall of the selected sector, the
+    /// next colour in the palette.
+    fn cycle_colour(&mut self, game_state: &mut GameState) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+
+        self.edit(game_state, |sectors| {
+            let sector = &mut sectors[selection.sector()];
+            let lines: Vec<usize> = match selection {
+                Selection::Line(_, line_index) => vec![line_index],
+                Selection::Sector(_) => (0..sector.lines().len()).collect(),
+            };
+            let mut changed = false;
+            for line_index in lines {
+                if let Wall::Solid(color) = sector.lines()[line_index].wall_type() {
+                    let next = PALETTE
+                        .iter()
+                        .position(|entry| *entry == color)
+                        .map_or(0, |index| (index + 1) % PALETTE.len());
+                    sector.set_wall(line_index, Wall::Solid(PALETTE[next]));
+                    changed = true;
+                }
+            }
+            changed
+        });
+    }
+
+    /// Gives the selected line, or every line of the selected sector, the
+    /// next built-in texture, or none after the last.
+    fn cycle_texture(&mut self, game_state: &mut GameState) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+
+        self.edit(game_state, |sectors| {
+            let sector = &mut sectors[selection.sector()];
+            let lines: Vec<usize> = match selection {
+                Selection::Line(_, line_index) => vec![line_index],
+                Selection::Sector(_) => (0..sector.lines().len()).collect(),
+            };
+            for line_index in lines {
+                let current = sector.lines()[line_index].texture();
+                let next = match TEXTURES.iter().position(|name| Some(*name) == current) {
+                    None => Some(TEXTURES[0]),
+                    Some(index) => TEXTURES.get(index + 1).copied(),
+                };
+                sector.set_texture(line_index, next);
+            }
+            true
+        });
+    }
+
+    fn change_heights(&mut self, game_state: &mut GameState, base_height: f32, height: f32) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+
+        self.edit(game_state, |sectors| {
+            let sector = &mut sectors[selection.sector()];
+            sector.set_base_height(sector.base_height() + base_height);
+            sector.set_height((sector.height() + height).max(HEIGHT_STEP));
+            true
+        });
+    }
+
+    /// Opens the selected sector's ceiling to the sky, or roofs it again.
+    fn toggle_sky(&mut self, game_state: &mut GameState) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+
+        self.edit(game_state, |sectors| {
+            let sector = &mut sectors[selection.sector()];
+            sector.set_sky(!sector.sky());
+            true
+        });
+    }
+
+    /// Removes the selected sector, portals into it become walls and movers
+    /// of it or set off by entering it go too.
+    fn delete_sector(&mut self, game_state: &mut GameState) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+        let removed = selection.sector();
+        // Sectors after the removed one move down a place
+        let renumber = |sector_id: SectorId| match sector_id.cmp(&removed) {
+            Ordering::Less => Some(sector_id),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(sector_id - 1),
+        };
+
+        let before = Snapshot::of(game_state.level());
+        let mut after = before.clone();
+        after.sectors.remove(removed);
+        for sector in after.sectors.iter_mut() {
+            for line_index in 0..sector.lines().len() {
+                if let Wall::Portal(target) = sector.lines()[line_index].wall_type() {
+                    let wall = renumber(target).map_or(Wall::Solid(PALETTE[0]), Wall::Portal);
+                    sector.set_wall(line_index, wall);
+                }
+            }
+        }
+        after.movers = after
+            .movers
+            .into_iter()
+            .filter_map(|mover| {
+                let trigger = match mover.trigger {
+                    Trigger::Use => Trigger::Use,
+                    Trigger::Enter(sector_id) => Trigger::Enter(renumber(sector_id)?),
+                };
+                Some(MoverDef {
+                    sector: renumber(mover.sector)?,
+                    trigger,
+                    ..mover
+                })
+            })
+            .collect();
+        self.apply(game_state, after, Some(before));
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, font: &Font, game_state: &GameState) {
+        self.draw_grid(canvas);
+
+        let level = game_state.level();
+        // Until the next input catches up with a level swapped in since
+        let current = self.generation == game_state.level_generation();
+        let selection = self.selection.filter(|_| current);
+        let drag = self.drag.as_ref().filter(|_| current);
+        let sectors = drag.map_or(level.sectors(), |drag| &drag.sectors);
+        for (sector_id, sector) in sectors.iter().enumerate() {
+            for (line_index, line) in sector.lines().iter().enumerate() {
+                let (start, end) = sector.line_points(line_index);
+                let selected = match selection {
+                    Some(Selection::Line(selected, selected_line)) => {
+                        selected == sector_id && selected_line == line_index
+                    }
+                    Some(Selection::Sector(selected)) => selected == sector_id,
+                    None => false,
+                };
+                let color = match (selected, line.wall_type()) {
+                    (true, _) => Vec3::splat(1.0),
+                    (false, Wall::Solid(color)) => color,
+                    (false, Wall::Portal(_)) => Vec3::new(0.5, 0.5, 0.2),
+                    (false, Wall::Mirror) => Vec3::new(0.6, 0.8, 1.0),
+                };
+                canvas.draw_line(&LineDescriptor {
+                    start: self.to_screen(start),
+                    end: self.to_screen(end),
+                    color,
+                    stroke: if selected { 3.0 } else { 1.0 },
+                });
+            }
+
+            for point in sector.points() {
+                let hovered = self.to_screen(*point).distance(self.cursor) < PICK_DISTANCE;
+                canvas.draw_rect(&RectDescriptor {
+                    top_left: self.to_screen(*point) - Vec2::splat(2.0),
+                    size: Vec2::splat(5.0),
+                    color: if hovered {
+                        Vec3::new(1.0, 1.0, 0.0)
+                    } else {
+                        Vec3::splat(0.9)
+                    },
+                });
+            }
+
+            let has_error = self
+                .issues
+                .iter()
+                .any(|issue| issue.sector == sector_id && issue.severity == Severity::Error);
+            let label = match sector.name() {
+                Some(name) => format!("{sector_id} {name}"),
+                None => sector_id.to_string(),
+            };
+            let size = font.measure(&label, 1);
+            canvas.draw_text(
+                font,
+                &TextDescriptor {
+                    text: &label,
+                    top_left: (self.to_screen(sector.centroid()) - size / 2.0).round(),
+                    color: if has_error {
+                        Vec3::new(1.0, 0.3, 0.3)
+                    } else {
+                        Vec3::splat(0.6)
+                    },
+                    scale: 1,
+                    alpha: 1.0,
+                },
+            );
+        }
+
+        if let Some(points) = &self.drawing {
+            let cursor = self.to_screen(self.cursor_world());
+            let mut previous = None;
+            for point in points
+                .iter()
+                .map(|point| self.to_screen(*point))
+                .chain([cursor])
+            {
+                if let Some(previous) = previous {
+                    canvas.draw_line(&LineDescriptor {
+                        start: previous,
+                        end: point,
+                        color: Vec3::new(0.2, 1.0, 0.2),
+                        stroke: 1.0,
+                    });
+                }
+                canvas.draw_rect(&RectDescriptor {
+                    top_left: point - Vec2::splat(2.0),
+                    size: Vec2::splat(5.0),
+                    color: Vec3::new(0.2, 1.0, 0.2),
+                });
+                previous = Some(point);
+            }
+        }
+
+        let player = self.to_screen(game_state.pos());
+        canvas.draw_circle(&CricleDescriptor {
+            centre: player,
+            radius: 6.0,
+            color: Vec3::new(1.0, 0.0, 0.0),
+        });
+        canvas.draw_line(&LineDescriptor {
+            start: player,
+            end: player + game_state.rot().xy() * 12.0,
+            color: Vec3::new(1.0, 0.0, 0.0),
+            stroke: 1.0,
+        });
+
+        self.draw_text(canvas, font, level, selection);
+    }
+
+    /// Lines every grid step, or every power of two steps when zoomed out
+    /// far enough that they would run together. The axes are brighter.
+    fn draw_grid(&self, canvas: &mut Canvas) {
+        let mut step = self.grid;
+        while step * self.zoom < 8.0 {
+            step *= 2.0;
+        }
+
+        let min = self.to_world(Vec2::ZERO);
+        let max = self.to_world(self.screen);
+        let grid_color = |value: f32| {
+            if value.abs() < step / 2.0 {
+                Vec3::splat(0.35)
+            } else {
+                Vec3::splat(0.15)
+            }
+        };
+
+        let mut x = (min.x / step).floor() * step;
+        while x <= max.x {
+            canvas.draw_rect(&RectDescriptor {
+                top_left: Vec2::new(self.to_screen(Vec2::new(x, 0.0)).x, 0.0),
+                size: Vec2::new(1.0, self.screen.y),
+                color: grid_color(x),
+            });
+            x += step;
+        }
+        let mut y = (min.y / step).floor() * step;
+        while y <= max.y {
+            canvas.draw_rect(&RectDescriptor {
+                top_left: Vec2::new(0.0, self.to_screen(Vec2::new(0.0, y)).y),
+                size: Vec2::new(self.screen.x, 1.0),
+                color: grid_color(y),
+            });
+            y += step;
+        }
+    }
+
+    /// Mode and selection along the top, validation issues and the key help
+    /// along the bottom.
+    fn draw_text(
+        &self,
+        canvas: &mut Canvas,
+        font: &Font,
+        level: &LevelState,
+        selection: Option<Selection>,
+    ) {
+        let line_height = font.height() as f32;
+        let mut text = |text: &str, row: f32, color: Vec3| {
+            canvas.draw_text(
+                font,
+                &TextDescriptor {
+                    text,
+                    top_left: Vec2::new(8.0, row * line_height + 8.0),
+                    color,
+                    scale: 1,
+                    alpha: 1.0,
+                },
+            );
+        };
+
+        let cursor = self.cursor_world();
+        let mode = if self.drawing.is_some() {
+            "draw"
+        } else {
+            "select"
+        };
+        text(
+            &format!(
+                "EDITOR {mode}  {}  grid {} snap {}  cursor {:.2} {:.2}  {}",
+                self.path,
+                self.grid,
+                if self.snap { "on" } else { "off" },
+                cursor.x,
+                cursor.y,
+                self.status,
+            ),
+            0.0,
+            Vec3::splat(1.0),
+        );
+
+        if let Some(selection) = selection {
+            let sector = level.sector(selection.sector());
+            let mut description = format!(
+                "sector {} {}floor {:.2} height {:.2}",
+                selection.sector(),
+                sector
+                    .name()
+                    .map_or(String::new(), |name| format!("\"{name}\" ")),
+                sector.base_height(),
+                sector.height(),
+            );
+            if sector.sky() {
+                description += " sky";
+            }
+            if let Selection::Line(_, line_index) = selection {
+                let line = &sector.lines()[line_index];
+                description += &match line.wall_type() {
+                    Wall::Solid(color) => format!(
+                        "  line {line_index} wall {:.2} {:.2} {:.2}",
+                        color.x, color.y, color.z
+                    ),
+                    Wall::Portal(target) if line.warp().is_some() => {
+                        format!("  line {line_index} warp to {target}")
+                    }
+                    Wall::Portal(target) => format!("  line {line_index} portal to {target}"),
+                    Wall::Mirror => format!("  line {line_index} mirror"),
+                };
+                if line.mask().is_some() {
+                    description += " masked";
+                }
+                if let Some(texture) = line.texture() {
+                    description += &format!(" {texture}");
+                }
+            }
+            text(&description, 1.0, Vec3::splat(0.8));
+        }
+
+        let rows = (self.screen.y / line_height) - 2.0;
+        let shown = self.issues.len().min(8);
+        for (index, issue) in self.issues.iter().take(shown).enumerate() {
+            let color = match issue.severity {
+                Severity::Warning => Vec3::new(1.0, 0.8, 0.2),
+                Severity::Error => Vec3::new(1.0, 0.3, 0.3),
+            };
+            text(&issue.to_string(), rows - (shown - index) as f32, color);
+        }
+        if self.issues.len() > shown {
+            text(
+                &format!("{} more issues", self.issues.len() - shown),
+                rows - shown as f32 - 1.0,
+                Vec3::new(1.0, 0.8, 0.2),
+            );
+        }
+        text(HELP, rows, Vec3::splat(0.6));
+    }
+}
+
+impl Cvars for Editor {
+    fn cvars(&self) -> Vec<Cvar> {
+        vec![
+            Cvar {
+                name: "editor_file",
+                description: "file the editor saves to",
+                value: CvarValue::Text(self.path.clone()),
+            },
+            Cvar {
+                name: "editor_grid",
+                description: "editor grid size",
+                value: CvarValue::Float(self.grid),
+            },
+        ]
+    }
+
+    fn set_cvar(&mut self, name: &str, value: CvarValue) -> bool {
+        match (name, value) {
+            ("editor_file", CvarValue::Text(value)) => self.path = value,
+            ("editor_grid", CvarValue::Float(value)) => self.grid = value.max(0.01),
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn pressed(event: &WindowEvent) -> Option<VirtualKeyCode> {
+    match event {
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode,
+                    ..
+                },
+            ..
+        } => *virtual_keycode,
+        _ => None,
+    }
+}
+
+/// Every sector point at `pos`, so shared corners move together.
+fn points_at(sectors: &[Sector], pos: Vec2) -> Vec<(SectorId, usize)> {
+    let mut points = vec![];
+    for (sector_id, sector) in sectors.iter().enumerate() {
+        for (point_index, point) in sector.points().iter().enumerate() {
+            if point.distance(pos) < 1e-4 {
+                points.push((sector_id, point_index));
+            }
+        }
+    }
+    points
+}
+
+/// Every line running between `start` and `end`, in either direction.
+fn matching_lines(sectors: &[Sector], start: Vec2, end: Vec2) -> Vec<(SectorId, usize)> {
+    let mut lines = vec![];
+    for (sector_id, sector) in sectors.iter().enumerate() {
+        for line_index in 0..sector.lines().len() {
+            let (a, b) = sector.line_points(line_index);
+            if (a.distance(start) < 1e-4 && b.distance(end) < 1e-4)
+                || (a.distance(end) < 1e-4 && b.distance(start) < 1e-4)
+            {
+                lines.push((sector_id, line_index));
+            }
+        }
+    }
+    lines
+}
+
+fn segment_distance(pos: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let line = end - start;
+    let t = ((pos - start).dot(line) / line.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+    pos.distance(start + line * t)
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use glam::Vec2;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02;
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+
+/// A monospaced bitmap font. Each glyph is `height` rows of
+/// `bytes_per_row` bytes, most significant bit on the left, the same layout
+/// PSF fonts use. The cell size includes any spacing between characters.
+pub struct Font {
+    width: u32,
+    height: u32,
+    bytes_per_row: usize,
+    glyphs: Vec<u8>,
+    chars: HashMap<char, usize>,
+}
+
+impl Font {
+    /// The 5x7 font that ships with the game, in a 6x8 cell.
+    pub fn builtin() -> Self {
+        let glyphs = BUILTIN_GLYPHS
+            .iter()
+            .flat_map(|rows| rows.iter().map(|row| row << 3).chain([0]))
+            .collect();
+        let chars = (' '..='~')
+            .enumerate()
+            .map(|(glyph, c)| (c, glyph))
+            .collect();
+
+        Self {
+            width: 6,
+            height: 8,
+            bytes_per_row: 1,
+            glyphs,
+            chars,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_psf(&fs::read(path)?)
+    }
+
+    /// Reads a PC Screen Font, either version 1 or 2. Fonts without a unicode
+    /// table map glyph `n` to the character with code point `n`.
+    pub fn from_psf(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.starts_with(&PSF1_MAGIC) {
+            Self::from_psf1(bytes)
+        } else if bytes.starts_with(&PSF2_MAGIC) {
+            Self::from_psf2(bytes)
+        } else {
+            Err(invalid("not a PSF font"))
+        }
+    }
+
+    fn from_psf1(bytes: &[u8]) -> io::Result<Self> {
+        let mode = *bytes.get(2).ok_or_else(|| invalid("truncated header"))?;
+        let height = *bytes.get(3).ok_or_else(|| invalid("truncated header"))? as usize;
+        let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+
+        let glyphs = bytes
+            .get(4..4 + count * height)
+            .ok_or_else(|| invalid("truncated glyphs"))?;
+
+        let mut chars = HashMap::new();
+        if mode & PSF1_MODE_HAS_TABLE != 0 {
+            let table = &bytes[4 + count * height..];
+            let mut glyph = 0;
+            let mut in_sequence = false;
+            for entry in table.chunks_exact(2) {
+                // Entries past the last glyph have nothing to map to
+                if glyph == count {
+                    break;
+                }
+                match u16::from_le_bytes([entry[0], entry[1]]) {
+                    0xffff => {
+                        glyph += 1;
+                        in_sequence = false;
+                    }
+                    0xfffe => in_sequence = true,
+                    code if !in_sequence => {
+                        if let Some(c) = char::from_u32(code as u32) {
+                            chars.entry(c).or_insert(glyph);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            chars = identity_map(count);
+        }
+
+        Ok(Self {
+            width: 8,
+            height: height as u32,
+            bytes_per_row: 1,
+            glyphs: glyphs.to_vec(),
+            chars,
+        })
+    }
+
+    fn from_psf2(bytes: &[u8]) -> io::Result<Self> {
+        let field = |index: usize| {
+            bytes
+                .get(index * 4..index * 4 + 4)
+                .map(|field| u32::from_le_bytes(field.try_into().unwrap()) as usize)
+                .ok_or_else(|| invalid("truncated header"))
+        };
+        let header_size = field(2)?;
+        let flags = field(3)? as u32;
+        let count = field(4)?;
+        let glyph_size = field(5)?;
+        let height = field(6)?;
+        let width = field(7)?;
+
+        if width == 0 || height == 0 {
+            return Err(invalid("glyphs have no size"));
+        }
+        let bytes_per_row = width.div_ceil(8);
+        if glyph_size < bytes_per_row * height {
+            return Err(invalid("glyphs are smaller than their dimensions"));
+        }
+
+        let glyphs_end = count
+            .checked_mul(glyph_size)
+            .and_then(|size| size.checked_add(header_size))
+            .ok_or_else(|| invalid("truncated glyphs"))?;
+        let glyph_bytes = bytes
+            .get(header_size..glyphs_end)
+            .ok_or_else(|| invalid("truncated glyphs"))?;
+        // Drop any padding so every glyph is exactly `height` rows
+        let glyphs = glyph_bytes
+            .chunks_exact(glyph_size)
+            .flat_map(|glyph| &glyph[..bytes_per_row * height])
+            .copied()
+            .collect();
+
+        let chars = if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+            let table = &bytes[glyphs_end..];
+            let mut chars = HashMap::new();
+            for (glyph, entry) in table.split(|byte| *byte == 0xff).take(count).enumerate() {
+                // Anything after 0xfe is a multi character sequence
+                let single = entry.split(|byte| *byte == 0xfe).next().unwrap_or(&[]);
+                for c in String::from_utf8_lossy(single).chars() {
+                    chars.entry(c).or_insert(glyph);
+                }
+            }
+            chars
+        } else {
+            identity_map(count)
+        };
+
+        Ok(Self {
+            width: width as u32,
+            height: height as u32,
+            bytes_per_row,
+            glyphs,
+            chars,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Size of `text` in pixels when drawn at `scale`.
+    pub fn measure(&self, text: &str, scale: u32) -> Vec2 {
+        let lines = text.lines().count().max(1);
+        let columns = text
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        Vec2::new(
+            (columns as u32 * self.width * scale) as f32,
+            (lines as u32 * self.height * scale) as f32,
+        )
+    }
+
+    /// The glyph for `c`, falling back to `?` for characters the font does
+    /// not have.
+    pub fn glyph(&self, c: char) -> Option<usize> {
+        self.chars.get(&c).or_else(|| self.chars.get(&'?')).copied()
+    }
+
+    /// Whether the pixel at `x`, `y` within `glyph` is set.
+    pub fn pixel(&self, glyph: usize, x: u32, y: u32) -> bool {
+        let row = (glyph * self.height as usize + y as usize) * self.bytes_per_row;
+        let byte = self.glyphs[row + x as usize / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+}
+
+fn identity_map(count: usize) -> HashMap<char, usize> {
+    (0..count)
+        .filter_map(|glyph| char::from_u32(glyph as u32).map(|c| (c, glyph)))
+        .collect()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// `' '` to `'~'`, seven rows of five pixels each, bit 4 on the left.
+#[rustfmt::skip]
+const BUILTIN_GLYPHS: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // '#'
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // '&'
+    [0x0c, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // '0'
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // '1'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // '2'
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // '3'
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // '4'
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // '5'
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // '6'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // '8'
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // '@'
+    [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11], // 'A'
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // 'B'
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // 'C'
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // 'D'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // 'E'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // 'F'
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // 'G'
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'H'
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // 'L'
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'O'
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'P'
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // 'Q'
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // 'R'
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // 'S'
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // 'W'
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // 'Y'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // 'Z'
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\'
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ']'
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // '_'
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // 'b'
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // 'c'
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // 'd'
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // 'e'
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // 'f'
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'l'
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // 'o'
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // 's'
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // 'w'
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'y'
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
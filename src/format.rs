@@ -0,0 +1,526 @@
+use std::io::{self, ErrorKind};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    level::{LevelState, Sector},
+    mover::MoverDef,
+};
+
+/// Schema version written into every new file. Bump it whenever `Sector`,
+/// `Line`, `Wall`, `MoverDef` or the level itself change shape and add a
+/// step to `MIGRATIONS`.
+pub const VERSION: u32 = 8;
+
+/// First bytes of a binary level, followed by the version as a little endian
+/// `u32` and then the body.
+const MAGIC: &[u8; 4] = b"RCLV";
+
+/// `MIGRATIONS[n]` turns a version `n` JSON level into a version `n + 1` one.
+const MIGRATIONS: [fn(Value) -> io::Result<Value>; VERSION as usize] = [
+    migrate_0_to_1,
+    migrate_1_to_2,
+    migrate_2_to_3,
+    migrate_3_to_4,
+    migrate_4_to_5,
+    migrate_5_to_6,
+    migrate_6_to_7,
+    migrate_7_to_8,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Readable and diffable, what the editor and generator write by default.
+    Json,
+    /// Much smaller and faster to read, for big imported maps.
+    Binary,
+}
+
+impl Encoding {
+    /// Binary for `.bin` files, JSON for anything else.
+    pub fn for_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("bin") => Encoding::Binary,
+            _ => Encoding::Json,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LevelFile<'a> {
+    version: u32,
+    sectors: &'a [Sector],
+    movers: &'a [MoverDef],
+    script: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct LevelBody {
+    sectors: Vec<Sector>,
+    movers: Vec<MoverDef>,
+    script: Option<String>,
+}
+
+pub fn encode(level: &LevelState, encoding: Encoding) -> io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Json => {
+            let file = LevelFile {
+                version: VERSION,
+                sectors: level.sectors(),
+                movers: level.movers(),
+                script: level.script(),
+            };
+            Ok(serde_json::to_vec_pretty(&file)?)
+        }
+        Encoding::Binary => {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend(VERSION.to_le_bytes());
+            let body = (level.sectors(), level.movers(), level.script());
+            binary_options()
+                .serialize_into(&mut bytes, &body)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Reads a level in either encoding, telling them apart by the magic
+/// number, and brings older versions up to date.
+pub fn decode(bytes: &[u8]) -> io::Result<LevelState> {
+    let body = match bytes.strip_prefix(MAGIC) {
+        Some(rest) => {
+            let (version, body) = rest
+                .split_first_chunk::<4>()
+                .ok_or_else(|| invalid("the binary header is cut short"))?;
+            decode_binary(u32::from_le_bytes(*version), body)?
+        }
+        None => {
+            let value: Value = serde_json::from_slice(bytes)?;
+            serde_json::from_value(migrate(value)?)?
+        }
+    };
+    Ok(LevelState::new(body.sectors)
+        .with_movers(body.movers)
+        .with_script(body.script))
+}
+
+/// Upgrades a JSON level one version at a time. Files from before versions
+/// were written count as version 0.
+pub fn migrate(mut value: Value) -> io::Result<Value> {
+    let version = match value.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| invalid("the version is not a number"))?,
+    };
+    if version > VERSION {
+        return Err(invalid(&format!(
+            "version {version} is newer than this build understands ({VERSION})"
+        )));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        value = migration(value)?;
+        value["version"] = Value::from(from as u32 + 1);
+    }
+    Ok(value)
+}
+
+/// The binary encoding started at version 1. When the schema changes, keep
+/// the old shapes around and decode older versions with them here.
+fn decode_binary(version: u32, body: &[u8]) -> io::Result<LevelBody> {
+    match version {
+        1 => Ok(LevelBody {
+            sectors: legacy::upgrade(deserialize(body)?),
+            movers: vec![],
+            script: None,
+        }),
+        2 => {
+            let (sectors, movers) = deserialize(body)?;
+            Ok(LevelBody {
+                sectors: legacy::upgrade(sectors),
+                movers,
+                script: None,
+            })
+        }
+        3 => {
+            let (sectors, movers) =
+                deserialize::<(Vec<legacy::TaggedSector<legacy::SpecialLine>>, _)>(body)?;
+            Ok(LevelBody {
+                sectors: legacy::upgrade_tagged(sectors),
+                movers,
+                script: None,
+            })
+        }
+        4 => {
+            let (sectors, movers, script) =
+                deserialize::<(Vec<legacy::TaggedSector<legacy::SpecialLine>>, _, _)>(body)?;
+            Ok(LevelBody {
+                sectors: legacy::upgrade_tagged(sectors),
+                movers,
+                script,
+            })
+        }
+        5 => {
+            let (sectors, movers, script) =
+                deserialize::<(Vec<legacy::TaggedSector<legacy::WarpLine>>, _, _)>(body)?;
+            Ok(LevelBody {
+                sectors: legacy::upgrade_tagged(sectors),
+                movers,
+                script,
+            })
+        }
+        6 => {
+            let (sectors, movers, script) =
+                deserialize::<(Vec<legacy::TaggedSector<legacy::MaskLine>>, _, _)>(body)?;
+            Ok(LevelBody {
+                sectors: legacy::upgrade_tagged(sectors),
+                movers,
+                script,
+            })
+        }
+        7 => {
+            let (sectors, movers, script) =
+                deserialize::<(Vec<legacy::TaggedSector<legacy::MaskLine, bool>>, _, _)>(body)?;
+            Ok(LevelBody {
+                sectors: legacy::upgrade_tagged(sectors),
+                movers,
+                script,
+            })
+        }
+        VERSION => {
+            let (sectors, movers, script) = deserialize(body)?;
+            Ok(LevelBody {
+                sectors,
+                movers,
+                script,
+            })
+        }
+        _ => Err(invalid(&format!(
+            "binary version {version} is not supported, this build reads {VERSION}"
+        ))),
+    }
+}
+
+fn deserialize<'a, T: Deserialize<'a>>(body: &'a [u8]) -> io::Result<T> {
+    binary_options()
+        .deserialize(body)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Variable length integers keep point and sector ids to a byte or two.
+fn binary_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// Version 0 was the bare `LevelState`, sectors had no names at first.
+fn migrate_0_to_1(mut value: Value) -> io::Result<Value> {
+    let sectors = value
+        .get_mut("sectors")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("there is no list of sectors"))?;
+    for sector in sectors {
+        let sector = sector
+            .as_object_mut()
+            .ok_or_else(|| invalid("a sector is not an object"))?;
+        sector.entry("name").or_insert(Value::Null);
+    }
+    Ok(value)
+}
+
+/// Version 2 added doors, lifts and crushers.
+fn migrate_1_to_2(mut value: Value) -> io::Result<Value> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| invalid("the level is not an object"))?
+        .entry("movers")
+        .or_insert(Value::Array(vec![]));
+    Ok(value)
+}
+
+/// Version 3 added sector tags and line specials.
+fn migrate_2_to_3(mut value: Value) -> io::Result<Value> {
+    let sectors = value
+        .get_mut("sectors")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("there is no list of sectors"))?;
+    for sector in sectors {
+        let sector = sector
+            .as_object_mut()
+            .ok_or_else(|| invalid("a sector is not an object"))?;
+        sector.entry("tag").or_insert(Value::Null);
+        let lines = sector
+            .get_mut("lines")
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| invalid("a sector has no list of lines"))?;
+        for line in lines {
+            line.as_object_mut()
+                .ok_or_else(|| invalid("a line is not an object"))?
+                .entry("special")
+                .or_insert(Value::Null);
+        }
+    }
+    Ok(value)
+}
+
+/// Version 4 added level scripts.
+fn migrate_3_to_4(mut value: Value) -> io::Result<Value> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| invalid("the level is not an object"))?
+        .entry("script")
+        .or_insert(Value::Null);
+    Ok(value)
+}
+
+/// Version 5 added warps to portal lines.
+fn migrate_4_to_5(mut value: Value) -> io::Result<Value> {
+    let sectors = value
+        .get_mut("sectors")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("there is no list of sectors"))?;
+    for sector in sectors {
+        let lines = sector
+            .get_mut("lines")
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| invalid("a sector has no list of lines"))?;
+        for line in lines {
+            line.as_object_mut()
+                .ok_or_else(|| invalid("a line is not an object"))?
+                .entry("warp")
+                .or_insert(Value::Null);
+        }
+    }
+    Ok(value)
+}
+
+/// Version 6 added masks to portal lines.
+fn migrate_5_to_6(mut value: Value) -> io::Result<Value> {
+    let sectors = value
+        .get_mut("sectors")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("there is no list of sectors"))?;
+    for sector in sectors {
+        let lines = sector
+            .get_mut("lines")
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| invalid("a sector has no list of lines"))?;
+        for line in lines {
+            line.as_object_mut()
+                .ok_or_else(|| invalid("a line is not an object"))?
+                .entry("mask")
+                .or_insert(Value::Null);
+        }
+    }
+    Ok(value)
+}
+
+/// Version 7 added sky ceilings.
+fn migrate_6_to_7(mut value: Value) -> io::Result<Value> {
+    let sectors = value
+        .get_mut("sectors")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("there is no list of sectors"))?;
+    for sector in sectors {
+        sector
+            .as_object_mut()
+            .ok_or_else(|| invalid("a sector is not an object"))?
+            .entry("sky")
+            .or_insert(Value::Bool(false));
+    }
+    Ok(value)
+}
+
+/// Version 8 added textures to lines.
+fn migrate_7_to_8(mut value: Value) -> io::Result<Value> {
+    let sectors = value
+        .get_mut("sectors")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| invalid("there is no list of sectors"))?;
+    for sector in sectors {
+        let lines = sector
+            .get_mut("lines")
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| invalid("a sector has no list of lines"))?;
+        for line in lines {
+            line.as_object_mut()
+                .ok_or_else(|| invalid("a line is not an object"))?
+                .entry("texture")
+                .or_insert(Value::Null);
+        }
+    }
+    Ok(value)
+}
+
+/// Binary sectors as older versions wrote them. What each version before
+/// the current one lacked:
+///
+/// - before 3, sector tags and line specials
+/// - before 5, line warps
+/// - before 6, line masks
+/// - before 7, the sector sky flag
+/// - before 8, line textures
+mod legacy {
+    use glam::Vec2;
+    use serde::Deserialize;
+
+    use crate::level::{self, Mask, Special, Wall, Warp};
+
+    #[derive(Deserialize)]
+    pub struct Sector {
+        points: Vec<Vec2>,
+        lines: Vec<Line>,
+        base_height: f32,
+        height: f32,
+        name: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Line {
+        wall_type: Wall,
+        point_1_id: usize,
+        point_2_id: usize,
+    }
+
+    /// Versions 3 to 7, their lines are `SpecialLine`s up to 4, `WarpLine`s
+    /// in 5 and `MaskLine`s after. `sky` is a `bool` from 7, before then
+    /// `NoSky` reads nothing.
+    #[derive(Deserialize)]
+    pub struct TaggedSector<L, S = NoSky> {
+        points: Vec<Vec2>,
+        lines: Vec<L>,
+        base_height: f32,
+        height: f32,
+        name: Option<String>,
+        tag: Option<u32>,
+        sky: S,
+    }
+
+    #[derive(Deserialize)]
+    pub struct NoSky;
+
+    impl From<NoSky> for bool {
+        fn from(_: NoSky) -> Self {
+            false
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct SpecialLine {
+        wall_type: Wall,
+        point_1_id: usize,
+        point_2_id: usize,
+        special: Option<Special>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct WarpLine {
+        wall_type: Wall,
+        point_1_id: usize,
+        point_2_id: usize,
+        special: Option<Special>,
+        warp: Option<Warp>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct MaskLine {
+        wall_type: Wall,
+        point_1_id: usize,
+        point_2_id: usize,
+        special: Option<Special>,
+        warp: Option<Warp>,
+        mask: Option<Mask>,
+    }
+
+    impl From<SpecialLine> for level::Line {
+        fn from(line: SpecialLine) -> Self {
+            WarpLine {
+                wall_type: line.wall_type,
+                point_1_id: line.point_1_id,
+                point_2_id: line.point_2_id,
+                special: line.special,
+                warp: None,
+            }
+            .into()
+        }
+    }
+
+    impl From<WarpLine> for level::Line {
+        fn from(line: WarpLine) -> Self {
+            MaskLine {
+                wall_type: line.wall_type,
+                point_1_id: line.point_1_id,
+                point_2_id: line.point_2_id,
+                special: line.special,
+                warp: line.warp,
+                mask: None,
+            }
+            .into()
+        }
+    }
+
+    impl From<MaskLine> for level::Line {
+        fn from(line: MaskLine) -> Self {
+            let mut upgraded = level::Line::new(line.wall_type, line.point_1_id, line.point_2_id);
+            if let Some(special) = line.special {
+                upgraded = upgraded.with_special(special);
+            }
+            if let Some(warp) = line.warp {
+                upgraded = upgraded.with_warp(warp);
+            }
+            if let Some(mask) = line.mask {
+                upgraded = upgraded.with_mask(mask);
+            }
+            upgraded
+        }
+    }
+
+    pub fn upgrade(sectors: Vec<Sector>) -> Vec<level::Sector> {
+        sectors
+            .into_iter()
+            .map(|sector| {
+                let lines = sector
+                    .lines
+                    .into_iter()
+                    .map(|line| level::Line::new(line.wall_type, line.point_1_id, line.point_2_id))
+                    .collect();
+                let upgraded =
+                    level::Sector::new(sector.points, lines, sector.base_height, sector.height);
+                match sector.name {
+                    Some(name) => upgraded.with_name(&name),
+                    None => upgraded,
+                }
+            })
+            .collect()
+    }
+
+    pub fn upgrade_tagged<L: Into<level::Line>, S: Into<bool>>(
+        sectors: Vec<TaggedSector<L, S>>,
+    ) -> Vec<level::Sector> {
+        sectors
+            .into_iter()
+            .map(|sector| {
+                let lines = sector.lines.into_iter().map(Into::into).collect();
+                let mut upgraded =
+                    level::Sector::new(sector.points, lines, sector.base_height, sector.height);
+                if let Some(name) = sector.name {
+                    upgraded = upgraded.with_name(&name);
+                }
+                if let Some(tag) = sector.tag {
+                    upgraded = upgraded.with_tag(tag);
+                }
+                if sector.sky.into() {
+                    upgraded = upgraded.with_sky();
+                }
+                upgraded
+            })
+            .collect()
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
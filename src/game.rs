@@ -1,29 +1,145 @@
 
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
 use glam::{Vec2, Vec3, Vec3Swizzles};
-use winit::event::{KeyboardInput, VirtualKeyCode, WindowEvent, ElementState};
+use rand::Rng;
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    console::{Cvar, CvarValue, Cvars},
+    geometry::intersection_distance,
+    input::{InputState, WeaponSwitch},
+    level::{Action, Activation, Decal, LevelState, SectorId, Special, TraceHit, Wall},
+    monster::Monsters,
+    mover::{Mover, Trigger},
+    nav::NavGraph,
+    player::PlayerStats,
+    scene::Sprite,
+    script::{Hook, ScriptCommand, Scripts},
+    weapon::{Fire, Projectile, Weapons},
+};
+
+/// Height of the player's eyes above the floor.
+pub const EYE_HEIGHT: f32 = 1.5;
+
+/// Shots leave from this far below the player's eyes.
+const SHOT_DROP: f32 = 0.2;
+
+/// Width and height of the holes hitscan weapons leave in walls.
+const BULLET_HOLE_SIZE: f32 = 0.08;
+
+/// Highest floor the player can step up onto without noclip.
+pub const MAX_STEP: f32 = 1.0;
+
+/// Space the player needs between a sector's floor and ceiling to fit in it,
+/// 56 units in Doom.
+pub const PLAYER_HEIGHT: f32 = 1.75;
 
-use crate::{input::InputState, level::{LevelState, SectorId}};
+/// How far away a line can be pressed with the use key.
+const USE_RANGE: f32 = 1.0;
+
+/// Seconds a script's message stays on screen.
+const MESSAGE_TIME: f32 = 3.0;
 
 pub struct GameState {
     pos: Vec2,
     rot: Vec3,
+    player: PlayerStats,
+    noclip: bool,
+    god: bool,
+    fly: bool,
+    /// Eye height while flying, otherwise it follows the floor.
+    fly_height: f32,
+    move_speed: f32,
+    turn_speed: f32,
 
     input: InputState,
-    level: LevelState
+    level: LevelState,
+    /// Goes up every time a level is swapped in, so anything keeping sector
+    /// ids or copies of the level can tell they're stale.
+    level_generation: u64,
+    /// File the level was loaded from, watched for changes.
+    level_path: Option<PathBuf>,
+    nav: NavGraph,
+    /// Waypoints the player is walking through on their own, see `walk_to`.
+    walk: Vec<Vec2>,
+    monsters: Monsters,
+    /// Monsters only think while this is on.
+    ai: bool,
+    weapons: Weapons,
+    projectiles: Vec<Projectile>,
+    /// Doors, lifts and crushers of the level, in the order it lists them.
+    movers: Vec<Mover>,
+    /// Sector the player was in last update, to notice them entering one.
+    last_sector: Option<SectorId>,
+    /// Lines whose special has run and doesn't repeat.
+    spent_specials: HashSet<(SectorId, usize)>,
+    secrets_found: usize,
+    /// What happened since the last `take_events`.
+    events: Vec<GameEvent>,
+    /// The level's script, dropped once it fails.
+    scripts: Option<Scripts>,
+    /// Message a script showed, with the seconds it has left.
+    message: Option<(String, f32)>,
+    /// Seconds of play so far, which animated textures follow.
+    time: f32,
+}
+
+/// Things line specials do that reach beyond the game state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// The level is over, with the file of the next one if there is one.
+    Exit(Option<String>),
+    /// A secret was found, `secrets_found` has the count.
+    Secret,
+    /// The level's script failed and was stopped.
+    ScriptError(String),
 }
 
 impl Default for GameState {
     fn default() -> Self {
-        Self {
-            pos: Vec2::splat(0.0),
-            rot: Vec3::NEG_Y,
-            input: InputState::default(),
-            level: LevelState::demo(),
-        }
+        Self::new(LevelState::demo(), Vec2::splat(0.0), Vec3::NEG_Y)
     }
 }
 
 impl GameState {
+    pub fn new(level: LevelState, pos: Vec2, rot: Vec3) -> Self {
+        let mut game_state = Self {
+            pos,
+            rot,
+            player: PlayerStats::default(),
+            noclip: false,
+            god: false,
+            fly: false,
+            fly_height: EYE_HEIGHT,
+            move_speed: 1.0,
+            turn_speed: 5.0,
+            input: InputState::default(),
+            nav: NavGraph::default(),
+            level: LevelState::default(),
+            level_generation: 0,
+            level_path: None,
+            walk: vec![],
+            monsters: Monsters::default(),
+            ai: true,
+            weapons: Weapons::default(),
+            projectiles: vec![],
+            movers: vec![],
+            last_sector: None,
+            spent_specials: HashSet::new(),
+            secrets_found: 0,
+            events: vec![],
+            scripts: None,
+            message: None,
+            time: 0.0,
+        };
+        game_state.set_level(level);
+        game_state
+    }
+
     pub fn pos(&self) -> Vec2 {
         self.pos
     }
@@ -32,10 +148,257 @@ impl GameState {
         self.rot
     }
 
+    pub fn player(&self) -> &PlayerStats {
+        &self.player
+    }
+
     pub fn level(&self) -> &LevelState {
         &self.level
     }
 
+    pub fn monsters(&self) -> &Monsters {
+        &self.monsters
+    }
+
+    pub fn monsters_mut(&mut self) -> &mut Monsters {
+        &mut self.monsters
+    }
+
+    pub fn weapons(&self) -> &Weapons {
+        &self.weapons
+    }
+
+    pub fn set_weapons(&mut self, weapons: Weapons) {
+        self.weapons = weapons;
+    }
+
+    pub fn projectiles(&self) -> &[Projectile] {
+        &self.projectiles
+    }
+
+    pub fn movers(&self) -> &[Mover] {
+        &self.movers
+    }
+
+    pub fn secrets_found(&self) -> usize {
+        self.secrets_found
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Events since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The held keys, for driving the game without a window, in tests and
+    /// scripts.
+    pub fn input_mut(&mut self) -> &mut InputState {
+        &mut self.input
+    }
+
+    pub fn scripts(&self) -> Option<&Scripts> {
+        self.scripts.as_ref()
+    }
+
+    /// Swaps in the level's script and runs its top level. Levels keep
+    /// whatever script they had when `set_level` changes them, since the
+    /// editor does that on every edit.
+    pub fn set_scripts(&mut self, scripts: Option<Scripts>) {
+        self.scripts = scripts;
+        self.message = None;
+        self.run_script(Hook::Load);
+    }
+
+    /// The message a script is showing, if there is one.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|(text, _)| text.as_str())
+    }
+
+    pub fn show_message(&mut self, text: &str) {
+        self.message = Some((text.to_string(), MESSAGE_TIME));
+    }
+
+    /// Sets off the level's `index`th mover as if its trigger had fired.
+    pub fn activate_mover(&mut self, index: usize) -> bool {
+        let Some(mover) = self.movers.get_mut(index) else {
+            return false;
+        };
+        mover.activate();
+        true
+    }
+
+    /// Everything drawn as a billboard in the 3D view.
+    pub fn sprites(&self) -> Vec<Sprite> {
+        let mut sprites = self.monsters.sprites(&self.level);
+        sprites.extend(self.projectiles.iter().map(Projectile::sprite));
+        sprites
+    }
+
+    /// Walkable connections between the level's sectors.
+    pub fn nav(&self) -> &NavGraph {
+        &self.nav
+    }
+
+    pub fn noclip(&self) -> bool {
+        self.noclip
+    }
+
+    pub fn set_noclip(&mut self, noclip: bool) {
+        self.noclip = noclip;
+    }
+
+    pub fn god(&self) -> bool {
+        self.god
+    }
+
+    pub fn set_god(&mut self, god: bool) {
+        self.god = god;
+    }
+
+    pub fn fly(&self) -> bool {
+        self.fly
+    }
+
+    /// Flying starts at the current eye height.
+    pub fn set_fly(&mut self, fly: bool) {
+        if fly && !self.fly {
+            self.fly_height = self.eye_height();
+        }
+        self.fly = fly;
+    }
+
+    /// Height the view is drawn from.
+    pub fn eye_height(&self) -> f32 {
+        if self.fly {
+            return self.fly_height;
+        }
+
+        self.floor_height(self.pos) + EYE_HEIGHT
+    }
+
+    /// Takes `amount` off the player's health unless god mode is on.
+    pub fn damage(&mut self, amount: i32) {
+        if !self.god {
+            self.player.take_damage(amount);
+        }
+    }
+
+    /// Swaps in a new level, the player stays where they were. Movers start
+    /// at rest, whatever heights their sectors were saved with.
+    pub fn set_level(&mut self, mut level: LevelState) {
+        self.movers = level
+            .movers()
+            .to_vec()
+            .into_iter()
+            .map(|def| Mover::new(def, &mut level))
+            .collect();
+        self.nav = NavGraph::new(&level, MAX_STEP);
+        self.level = level;
+        self.level_generation += 1;
+        self.last_sector = self.find_current_sector();
+        self.spent_specials.clear();
+        self.secrets_found = 0;
+        self.walk.clear();
+        self.projectiles.clear();
+    }
+
+    pub fn level_generation(&self) -> u64 {
+        self.level_generation
+    }
+
+    pub fn level_path(&self) -> Option<&Path> {
+        self.level_path.as_deref()
+    }
+
+    /// Remembers which file the level came from, `None` for levels that
+    /// weren't loaded from one.
+    pub fn set_level_path(&mut self, path: Option<PathBuf>) {
+        self.level_path = path;
+    }
+
+    /// Swaps in a new version of the current level. The player keeps their
+    /// position and facing if they're still inside a sector, otherwise they
+    /// move to the middle of the nearest sector, which is returned.
+    pub fn reload_level(&mut self, level: LevelState) -> Option<Vec2> {
+        self.set_level(level);
+        if self.find_current_sector().is_some() {
+            return None;
+        }
+
+        let pos = self
+            .level
+            .sectors()
+            .iter()
+            .map(|sector| sector.centroid())
+            .min_by(|a, b| a.distance(self.pos).total_cmp(&b.distance(self.pos)))?;
+        self.teleport(pos);
+        Some(pos)
+    }
+
+    pub fn set_rot(&mut self, rot: Vec3) {
+        self.rot = rot;
+    }
+
+    pub fn teleport(&mut self, pos: Vec2) {
+        self.pos = pos;
+        if self.fly {
+            self.fly_height = self.floor_height(pos) + EYE_HEIGHT;
+        }
+    }
+
+    /// Moves the player to the centroid of the first sector called `name`.
+    pub fn teleport_to_sector(&mut self, name: &str) -> Option<Vec2> {
+        let sector_id = self.level.find_sector(name)?;
+        let pos = self.level.sector(sector_id).centroid();
+        self.teleport(pos);
+        Some(pos)
+    }
+
+    /// Starts walking the player to `target` along the shortest path,
+    /// returning the waypoints, or `None` when there's no way there. Any
+    /// movement key takes control back.
+    pub fn walk_to(&mut self, target: Vec2) -> Option<&[Vec2]> {
+        self.walk = self.nav.find_path(&self.level, self.pos, target)?;
+        Some(&self.walk)
+    }
+
+    /// Waypoints left on the current walk, empty when not walking.
+    pub fn walk(&self) -> &[Vec2] {
+        &self.walk
+    }
+
+    pub fn stop_walking(&mut self) {
+        self.walk.clear();
+    }
+
+    /// Position, facing and sector of the player on one line.
+    pub fn where_am_i(&self) -> String {
+        let sector = match self.find_current_sector() {
+            Some(sector_id) => match self.level.sector(sector_id).name() {
+                Some(name) => format!("sector {sector_id} \"{name}\""),
+                None => format!("sector {sector_id}"),
+            },
+            None => "outside the level".to_string(),
+        };
+        format!(
+            "pos {:.2} {:.2}, rot {:.2} {:.2}, eye {:.2}, {sector}",
+            self.pos.x,
+            self.pos.y,
+            self.rot.x,
+            self.rot.y,
+            self.eye_height(),
+        )
+    }
+
+    /// Forgets which keys are held, used when something else takes the
+    /// keyboard and the release events won't arrive here.
+    pub fn release_input(&mut self) {
+        self.input = InputState::default();
+    }
+
     pub fn find_current_sector(&self) -> Option<SectorId> {
         self.level().find_current_sector(self.pos())   
     }
@@ -43,6 +406,14 @@ impl GameState {
     pub fn input(&mut self, event: &WindowEvent) -> bool {
         match event {
             WindowEvent::KeyboardInput { input, .. } => self.keyboard_input(input),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.input.fire(*state == ElementState::Pressed);
+                true
+            }
             _ => false,
         }
     }
@@ -59,6 +430,24 @@ impl GameState {
             Some(VirtualKeyCode::D) => self.input.right(movement),
             Some(VirtualKeyCode::Left) => self.input.rot_left(movement),
             Some(VirtualKeyCode::Right) => self.input.rot_right(movement),
+            Some(VirtualKeyCode::Space) => self.input.up(movement),
+            Some(VirtualKeyCode::LControl) => self.input.down(movement),
+            Some(VirtualKeyCode::F) => self.input.fire(movement),
+            Some(VirtualKeyCode::E) if movement => self.input.press_use(),
+            Some(VirtualKeyCode::Q) if movement => self.input.next_weapon(),
+            Some(
+                key @ (VirtualKeyCode::Key1
+                | VirtualKeyCode::Key2
+                | VirtualKeyCode::Key3
+                | VirtualKeyCode::Key4
+                | VirtualKeyCode::Key5
+                | VirtualKeyCode::Key6
+                | VirtualKeyCode::Key7
+                | VirtualKeyCode::Key8
+                | VirtualKeyCode::Key9),
+            ) if movement => self
+                .input
+                .select_weapon(key as usize - VirtualKeyCode::Key1 as usize),
             _ => handled = false,
         };
 
@@ -66,6 +455,7 @@ impl GameState {
     }
     
     pub fn update(&mut self, delta: f32) {
+        self.time += delta;
         let move_vec = self.input.move_vec();
 
         if let Some(rot_dir) = self.input.rot_dir() {
@@ -74,17 +464,555 @@ impl GameState {
                 crate::input::RotationDirection::Right => delta,
             };
 
-            let quat = glam::Quat::from_axis_angle(Vec3::Z, angle * 5.0);
+            let quat = glam::Quat::from_axis_angle(Vec3::Z, angle * self.turn_speed);
             self.rot = quat * self.rot;
         }
 
         let norm_xy_look = self.rot.xy().normalize();
         let corrected_move_vec = norm_xy_look.rotate(move_vec);
 
-        self.pos += scale(corrected_move_vec, delta);
+        let step = scale(corrected_move_vec, delta * self.move_speed);
+        if step != Vec2::ZERO {
+            self.walk.clear();
+        }
+        if self.noclip {
+            self.move_to(self.pos + step);
+        } else {
+            self.slide(step);
+        }
+        self.follow_walk(delta);
+
+        if self.fly {
+            self.fly_height += self.input.vertical() * delta * self.move_speed;
+        }
+        if let Some((_, left)) = &mut self.message {
+            *left -= delta;
+            if *left <= 0.0 {
+                self.message = None;
+            }
+        }
+
+        self.update_triggers();
+        self.run_script(Hook::Tick(delta));
+        self.update_movers(delta);
+        self.update_weapons(delta);
+        self.update_projectiles(delta);
+
+        if self.ai {
+            let player = self.pos.extend(self.eye_height());
+            let damage = self.monsters.update(delta, &self.level, &self.nav, player);
+            if damage > 0 {
+                self.damage(damage);
+            }
+        }
+    }
+
+    /// Sets off movers, specials and script hooks for the line the player
+    /// used and the sector they just walked into.
+    fn update_triggers(&mut self) {
+        let current = self.find_current_sector();
+        if current != self.last_sector {
+            self.last_sector = current;
+            if let Some(sector_id) = current {
+                self.trigger(Trigger::Enter(sector_id), None);
+                self.run_script(Hook::EnterSector(sector_id));
+            }
+        }
+
+        if self.input.take_use() && !self.player.is_dead() {
+            if let Some((sector_id, line_index)) = self.used_line() {
+                let used = match self.level.sector(sector_id).lines()[line_index].wall_type() {
+                    Wall::Portal(target) => target,
+                    Wall::Solid(_) | Wall::Mirror => sector_id,
+                };
+                self.trigger(Trigger::Use, Some(used));
+                self.activate_line(Activation::Use, sector_id, line_index);
+                self.run_script(Hook::UseLine(sector_id, line_index));
+            }
+        }
+    }
+
+    /// Line of the player's sector they're facing within `USE_RANGE`.
+    fn used_line(&self) -> Option<(SectorId, usize)> {
+        let sector_id = self.find_current_sector()?;
+        let sector = self.level.sector(sector_id);
+        let reach = self.rot.xy().normalize_or_zero() * USE_RANGE;
+        let (_, line_index) = (0..sector.lines().len())
+            .filter_map(|line_index| {
+                let (start, end) = sector.line_points(line_index);
+                intersection_distance(self.pos, reach, start, end)
+                    .map(|distance| (distance, line_index))
+            })
+            .filter(|(distance, _)| *distance <= 1.0)
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+        Some((sector_id, line_index))
+    }
+
+    /// Puts the player at `pos`, or where they come out when a warp portal
+    /// is on the way, and runs the walk-over specials of the lines crossed
+    /// getting there.
+    fn move_to(&mut self, pos: Vec2) {
+        let from = self.pos;
+        let Some(crossing) = self.level.crossed_warp(from, pos) else {
+            self.pos = pos;
+            self.cross_lines(from);
+            return;
+        };
+
+        let warp = crossing.warp;
+        self.pos = warp.apply(pos);
+        self.rot = warp.rotate(self.rot.xy()).extend(self.rot.z);
+        self.fly_height += warp.height;
+        self.walk.clear();
+        let (sector_id, line_index) = crossing.line;
+        self.activate_line(Activation::Cross, sector_id, line_index);
+    }
+
+    /// Runs the walk-over specials of the lines the player crossed moving
+    /// from `from` out of its sector.
+    fn cross_lines(&mut self, from: Vec2) {
+        let Some(sector_id) = self.level.find_current_sector(from) else {
+            return;
+        };
+        let sector = self.level.sector(sector_id);
+        if sector.contains(self.pos) {
+            return;
+        }
+
+        let crossed: Vec<usize> = (0..sector.lines().len())
+            .filter(|line_index| {
+                let (start, end) = sector.line_points(*line_index);
+                intersection_distance(from, self.pos - from, start, end)
+                    .is_some_and(|distance| distance <= 1.0)
+            })
+            .collect();
+        for line_index in crossed {
+            self.activate_line(Activation::Cross, sector_id, line_index);
+        }
+    }
+
+    /// Runs the special of a line, and of the line back from the other side
+    /// when it's a portal, if `activation` sets it off. Specials that don't
+    /// repeat only ever run once.
+    fn activate_line(&mut self, activation: Activation, sector_id: SectorId, line_index: usize) {
+        let lines = std::iter::once((sector_id, line_index))
+            .chain(self.level.opposite_line(sector_id, line_index));
+        let specials: Vec<((SectorId, usize), Special)> = lines
+            .filter(|line| !self.spent_specials.contains(line))
+            .filter_map(|(sector_id, line_index)| {
+                let special = self.level.sector(sector_id).lines()[line_index].special()?;
+                (special.activation == activation)
+                    .then(|| ((sector_id, line_index), special.clone()))
+            })
+            .collect();
+
+        for (line, special) in specials {
+            if !special.repeat {
+                self.spent_specials.insert(line);
+            }
+            self.run_special(&special);
+        }
+    }
+
+    fn run_special(&mut self, special: &Special) {
+        let tagged: Vec<SectorId> = special
+            .tag
+            .map(|tag| self.level.tagged(tag).collect())
+            .unwrap_or_default();
+
+        match &special.action {
+            Action::Movers => {
+                if let Some(tag) = special.tag {
+                    self.activate_tagged(tag);
+                }
+            }
+            Action::Teleport => {
+                if let Some(sector_id) = tagged.first() {
+                    let pos = self.level.sector(*sector_id).centroid();
+                    self.teleport(pos);
+                    self.walk.clear();
+                }
+            }
+            Action::Exit { next } => self.events.push(GameEvent::Exit(next.clone())),
+            Action::Secret => {
+                self.secrets_found += 1;
+                self.events.push(GameEvent::Secret);
+            }
+            Action::Alert => {
+                if let Some(tag) = special.tag {
+                    self.monsters.alert(tag);
+                }
+            }
+        }
+    }
+
+    /// Activates the movers of every sector tagged `tag`.
+    fn activate_tagged(&mut self, tag: u32) {
+        let tagged: Vec<SectorId> = self.level.tagged(tag).collect();
+        for mover in &mut self.movers {
+            if tagged.contains(&mover.def().sector) {
+                mover.activate();
+            }
+        }
+    }
+
+    /// Runs a hook of the level's script and makes the changes it asked
+    /// for. A script that fails is reported and stopped.
+    fn run_script(&mut self, hook: Hook) {
+        let Some(mut scripts) = self.scripts.take() else {
+            return;
+        };
+        match scripts.run(hook, self) {
+            Ok(commands) => {
+                self.scripts = Some(scripts);
+                for command in commands {
+                    self.run_script_command(command);
+                }
+            }
+            Err(e) => self.events.push(GameEvent::ScriptError(e)),
+        }
+    }
+
+    fn run_script_command(&mut self, command: ScriptCommand) {
+        match command {
+            ScriptCommand::MoveSector {
+                sector,
+                floor,
+                ceiling,
+            } => {
+                let moved_floor = self.level.sector(sector).base_height() != floor;
+                self.level.set_heights(sector, floor, ceiling - floor);
+                if moved_floor {
+                    self.nav = NavGraph::new(&self.level, MAX_STEP);
+                }
+            }
+            ScriptCommand::Activate(tag) => self.activate_tagged(tag),
+            ScriptCommand::Spawn { kind, pos } => {
+                if let Err(e) = self.monsters.spawn(&kind, pos, self.pos - pos) {
+                    let name = self
+                        .scripts
+                        .as_ref()
+                        .map_or("script", |scripts| scripts.name());
+                    self.events
+                        .push(GameEvent::ScriptError(format!("{name}: spawn: {e}")));
+                }
+            }
+            ScriptCommand::Teleport(pos) => {
+                self.teleport(pos);
+                self.walk.clear();
+            }
+            ScriptCommand::Message(text) => self.show_message(&text),
+            ScriptCommand::Decal {
+                sector,
+                line,
+                decal,
+            } => self.level.add_decal(sector, line, decal),
+        }
+    }
+
+    /// Activates every mover set off by `trigger`, only those moving
+    /// `sector_id` when given.
+    fn trigger(&mut self, trigger: Trigger, sector_id: Option<SectorId>) {
+        for mover in &mut self.movers {
+            let def = mover.def();
+            if def.trigger == trigger && sector_id.is_none_or(|sector_id| def.sector == sector_id) {
+                mover.activate();
+            }
+        }
+    }
+
+    /// Moves every mover's sector on, keeping doors off the player and
+    /// monsters and letting crushers hurt them. Paths are worked out again
+    /// once a lift stops.
+    fn update_movers(&mut self, delta: f32) {
+        let player_sector = self.find_current_sector();
+        let mut floors_moved = false;
+
+        for mover in &mut self.movers {
+            let sector_id = mover.def().sector;
+            let player_height = if player_sector == Some(sector_id) && !self.noclip {
+                PLAYER_HEIGHT
+            } else {
+                0.0
+            };
+            let headroom = self
+                .monsters
+                .heights_in(&self.level, sector_id)
+                .fold(player_height, f32::max);
+
+            let was_moving = mover.is_moving();
+            let damage = mover.update(delta, &mut self.level, headroom);
+            floors_moved |= was_moving && !mover.is_moving() && mover.def().kind.moves_floor();
+
+            let Some(damage) = damage else {
+                continue;
+            };
+            let clearance = self.level.sector(sector_id).height();
+            if player_height > clearance && !self.god {
+                self.player.take_damage(damage);
+            }
+            self.monsters
+                .crush(&self.level, sector_id, clearance, damage);
+        }
+
+        if floors_moved {
+            self.nav = NavGraph::new(&self.level, MAX_STEP);
+        }
+    }
+
+    /// Switches weapons when asked and fires the one in hand while the fire
+    /// button is held.
+    fn update_weapons(&mut self, delta: f32) {
+        match self.input.take_weapon_switch() {
+            Some(WeaponSwitch::Slot(slot)) => {
+                self.weapons.select(slot);
+            }
+            Some(WeaponSwitch::Next) => self.weapons.next(),
+            None => {}
+        }
+        self.weapons.update(delta);
+
+        if !self.input.firing() || self.player.is_dead() {
+            return;
+        }
+        let Some(fire) = self.weapons.fire(&mut self.player.ammo) else {
+            return;
+        };
+        let from = self.pos.extend(self.eye_height() - SHOT_DROP);
+        let facing = self.rot.xy().normalize_or_zero();
+
+        match fire {
+            Fire::Hitscan {
+                damage,
+                range,
+                spread,
+                pellets,
+                holes,
+            } => {
+                let mut rng = rand::thread_rng();
+                let mut shot_lines = vec![];
+                for _ in 0..pellets {
+                    let angle = (rng.gen::<f32>() - 0.5) * spread.to_radians();
+                    let to = from + Vec2::from_angle(angle).rotate(facing).extend(0.0) * range;
+                    let wall = self.level.trace_hit(from, to);
+                    let hit = self
+                        .monsters
+                        .first_hit(&self.level, from, to, 0.0)
+                        .filter(|(_, fraction)| *fraction < wall.map_or(1.0, |wall| wall.fraction));
+                    match hit {
+                        Some((index, _)) => {
+                            if let Some(monster) = self.monsters.get_mut(index) {
+                                monster.hurt(damage);
+                            }
+                        }
+                        None => {
+                            let Some(TraceHit {
+                                pos,
+                                line: Some((sector_id, line_index)),
+                                ..
+                            }) = wall
+                            else {
+                                continue;
+                            };
+                            if holes {
+                                self.add_bullet_hole(sector_id, line_index, pos);
+                            }
+                            shot_lines.push((sector_id, line_index));
+                        }
+                    }
+                }
+                for (sector_id, line_index) in shot_lines {
+                    self.activate_line(Activation::Shoot, sector_id, line_index);
+                }
+            }
+            Fire::Projectile {
+                damage,
+                speed,
+                radius,
+                color,
+            } => self.projectiles.push(Projectile {
+                pos: from,
+                velocity: facing * speed,
+                damage,
+                radius,
+                color,
+                age: 0.0,
+            }),
+        }
+    }
+
+    /// Marks where a shot hit a line.
+    fn add_bullet_hole(&mut self, sector_id: SectorId, line_index: usize, pos: Vec3) {
+        let sector = self.level.sector(sector_id);
+        let (start, _) = sector.line_points(line_index);
+        let decal = Decal {
+            along: start.distance(pos.xy()),
+            height: pos.z - sector.base_height(),
+            size: Vec2::splat(BULLET_HOLE_SIZE),
+            texture: Some("bullet_hole".to_string()),
+            color: Vec3::ONE,
+        };
+        self.level.add_decal(sector_id, line_index, decal);
+    }
+
+    /// Moves every projectile, hurting the first monster it reaches and
+    /// dropping it when it hits something or burns out. Lines it hits are
+    /// shot.
+    fn update_projectiles(&mut self, delta: f32) {
+        let mut shot_lines = vec![];
+        for mut projectile in std::mem::take(&mut self.projectiles) {
+            let mut next = projectile.pos + (projectile.velocity * delta).extend(0.0);
+            let wall = self.level.trace_hit(projectile.pos, next);
+            let hit = self
+                .monsters
+                .first_hit(&self.level, projectile.pos, next, projectile.radius)
+                .filter(|(_, fraction)| wall.is_none_or(|wall| *fraction < wall.fraction));
+
+            if let Some((index, _)) = hit {
+                if let Some(monster) = self.monsters.get_mut(index) {
+                    monster.hurt(projectile.damage);
+                }
+                continue;
+            }
+            if wall.is_none() {
+                if let Some(crossing) = self.level.crossed_warp(projectile.pos.xy(), next.xy()) {
+                    next = crossing.warp.apply_3d(next);
+                    projectile.velocity = crossing.warp.rotate(projectile.velocity);
+                }
+            }
+            projectile.pos = next;
+            projectile.age += delta;
+            match wall {
+                Some(wall) => shot_lines.extend(wall.line),
+                None if !projectile.is_spent() => self.projectiles.push(projectile),
+                None => {}
+            }
+        }
+
+        for (sector_id, line_index) in shot_lines {
+            self.activate_line(Activation::Shoot, sector_id, line_index);
+        }
+    }
+
+    /// Heads for the next waypoint of a walk, facing it. A walk that gets
+    /// stuck, say on a door that closed, is given up.
+    fn follow_walk(&mut self, delta: f32) {
+        let Some(waypoint) = self.walk.first().copied() else {
+            return;
+        };
+        let to = waypoint - self.pos;
+        let distance = delta * self.move_speed;
+        if to.length() <= distance {
+            self.walk.remove(0);
+            self.move_to(waypoint);
+            return;
+        }
+
+        let direction = to.normalize();
+        self.rot = direction.extend(self.rot.z);
+        let before = self.pos;
+        self.slide(direction * distance);
+        if self.pos == before {
+            self.walk.clear();
+        }
+    }
+
+    /// Moves by `step` if nothing is in the way, otherwise along whichever
+    /// axis is free so the player slides along walls.
+    fn slide(&mut self, step: Vec2) {
+        for step in [step, Vec2::new(step.x, 0.0), Vec2::new(0.0, step.y)] {
+            if self.can_move_to(self.pos + step) {
+                self.move_to(self.pos + step);
+                return;
+            }
+        }
+    }
+
+    /// A move is open when it doesn't cross a wall or blocking mask of the
+    /// current sector and ends inside a sector whose floor is no more than
+    /// `MAX_STEP` above the current one and whose ceiling leaves room for
+    /// the player. Anywhere is reachable once outside the level, so a bad
+    /// teleport can't leave the player stuck. Moves through warp portals
+    /// are checked where they come out.
+    fn can_move_to(&self, pos: Vec2) -> bool {
+        let Some(current) = self.find_current_sector() else {
+            return true;
+        };
+        if self.level.blocked(self.pos, pos) {
+            return false;
+        }
+        let (pos, height) = match self.level.crossed_warp(self.pos, pos) {
+            Some(crossing) => (crossing.warp.apply(pos), crossing.warp.height),
+            None => (pos, 0.0),
+        };
+        let Some(target_id) = self.level.find_current_sector(pos) else {
+            return false;
+        };
+
+        let target = self.level.sector(target_id);
+        let step = target.base_height() - height - self.level.sector(current).base_height();
+        let fits = target_id == current || target.height() >= PLAYER_HEIGHT;
+        fits && (self.fly || step <= MAX_STEP)
+    }
+
+    fn floor_height(&self, pos: Vec2) -> f32 {
+        self.level
+            .find_current_sector(pos)
+            .map_or(0.0, |sector_id| self.level.sector(sector_id).base_height())
+    }
+}
+
+impl Cvars for GameState {
+    fn cvars(&self) -> Vec<Cvar> {
+        vec![
+            Cvar {
+                name: "noclip",
+                description: "walk through walls",
+                value: CvarValue::Bool(self.noclip),
+            },
+            Cvar {
+                name: "god",
+                description: "take no damage",
+                value: CvarValue::Bool(self.god),
+            },
+            Cvar {
+                name: "fly",
+                description: "move up and down with space and control",
+                value: CvarValue::Bool(self.fly),
+            },
+            Cvar {
+                name: "ai",
+                description: "let monsters think",
+                value: CvarValue::Bool(self.ai),
+            },
+            Cvar {
+                name: "move_speed",
+                description: "units per second",
+                value: CvarValue::Float(self.move_speed),
+            },
+            Cvar {
+                name: "turn_speed",
+                description: "radians per second",
+                value: CvarValue::Float(self.turn_speed),
+            },
+        ]
+    }
+
+    fn set_cvar(&mut self, name: &str, value: CvarValue) -> bool {
+        match (name, value) {
+            ("noclip", CvarValue::Bool(value)) => self.noclip = value,
+            ("god", CvarValue::Bool(value)) => self.god = value,
+            ("fly", CvarValue::Bool(value)) => self.set_fly(value),
+            ("ai", CvarValue::Bool(value)) => self.ai = value,
+            ("move_speed", CvarValue::Float(value)) => self.move_speed = value,
+            ("turn_speed", CvarValue::Float(value)) => self.turn_speed = value,
+            _ => return false,
+        }
+        true
     }
 }
 
 fn scale(vec: Vec2, scale: f32) -> Vec2 {
     Vec2::new(vec.x * scale, vec.y * scale)
 }
+
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::level::{LevelState, Line, Sector, SectorId, Wall};
+
+/// Heights are picked in steps of this size so levels read cleanly.
+const HEIGHT_STEP: f32 = 0.25;
+
+/// Left, up, right and down on the room grid.
+const DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+/// What the generator is allowed to build.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Number of rooms, the level ends up with a corridor fewer than this
+    /// plus any extra corridors that close loops.
+    pub rooms: usize,
+    pub min_room_size: f32,
+    pub max_room_size: f32,
+    pub corridor_width: f32,
+    /// Shortest corridor between two neighbouring rooms.
+    pub corridor_length: f32,
+    /// Largest change in floor height across a portal. Keep this at or under
+    /// `game::MAX_STEP` or the player may not be able to reach every room.
+    pub max_step: f32,
+    /// Chance of joining two neighbouring rooms that are already connected
+    /// another way, from 0 to 1.
+    pub loop_chance: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            rooms: 12,
+            min_room_size: 3.0,
+            max_room_size: 6.0,
+            corridor_width: 1.0,
+            corridor_length: 1.0,
+            max_step: 0.5,
+            loop_chance: 0.2,
+        }
+    }
+}
+
+struct Room {
+    min: Vec2,
+    max: Vec2,
+    base_height: f32,
+    openings: Vec<(Vec2, Vec2, SectorId)>,
+}
+
+/// Builds a level of rectangular rooms joined by straight corridors. The same
+/// seed and settings always give the same level.
+///
+/// Rooms sit on a grid of slots, grown one neighbour at a time from a first
+/// room centred on the origin, so the corridors between each room and the
+/// one it grew from make a tree that reaches every room. Each corridor's
+/// floor sits halfway between the rooms it joins, and every room contains
+/// the middle of its slot so corridors along a row or column always line
+/// up with both ends. Rooms come first in the level and are named `room0`,
+/// `room1` and so on, corridors follow.
+pub fn generate(seed: u64, settings: &Settings) -> Result<LevelState, String> {
+    // Keep corridors clear of room corners
+    let margin = settings.corridor_width / 2.0 + HEIGHT_STEP;
+    if settings.rooms == 0 {
+        return Err("at least one room is needed".to_string());
+    }
+    if settings.corridor_width <= 0.0 || settings.corridor_length <= 0.0 {
+        return Err("corridors need a positive width and length".to_string());
+    }
+    if settings.min_room_size < 2.0 * margin || settings.max_room_size < settings.min_room_size {
+        return Err(format!(
+            "room sizes must be at least {} and the largest at least the smallest",
+            2.0 * margin
+        ));
+    }
+    if settings.max_step < 0.0 {
+        return Err("the height step can't be negative".to_string());
+    }
+    if !(0.0..=1.0).contains(&settings.loop_chance) {
+        return Err("the loop chance must be between 0 and 1".to_string());
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let spacing = settings.max_room_size + settings.corridor_length;
+    let steps = (2.0 * settings.max_step / HEIGHT_STEP).floor() as i32;
+
+    // Grow the layout, remembering which room each new one branched from
+    let mut slots = vec![(0, 0)];
+    let mut by_slot = HashMap::from([((0, 0), 0)]);
+    let mut links = vec![];
+    let mut base_heights = vec![0.0];
+    while slots.len() < settings.rooms {
+        let from = rng.gen_range(0..slots.len());
+        let (dx, dy) = DIRECTIONS[rng.gen_range(0..DIRECTIONS.len())];
+        let slot = (slots[from].0 + dx, slots[from].1 + dy);
+        if by_slot.contains_key(&slot) {
+            continue;
+        }
+        by_slot.insert(slot, slots.len());
+        links.push((from, slots.len()));
+        base_heights.push(base_heights[from] + rng.gen_range(-steps..=steps) as f32 * HEIGHT_STEP);
+        slots.push(slot);
+    }
+
+    // Extra corridors between neighbours, as long as the floors are close
+    // enough for a corridor halfway between them to be climbable
+    for (index, slot) in slots.iter().enumerate() {
+        for (dx, dy) in [(1, 0), (0, 1)] {
+            let Some(&other) = by_slot.get(&(slot.0 + dx, slot.1 + dy)) else {
+                continue;
+            };
+            let linked = links.contains(&(index, other)) || links.contains(&(other, index));
+            if !linked
+                && (base_heights[index] - base_heights[other]).abs() <= 2.0 * settings.max_step
+                && rng.gen_bool(settings.loop_chance)
+            {
+                links.push((index, other));
+            }
+        }
+    }
+
+    let mut rooms: Vec<Room> = slots
+        .iter()
+        .zip(&base_heights)
+        .map(|(slot, base_height)| {
+            let centre = Vec2::new(slot.0 as f32, slot.1 as f32) * spacing;
+            let size = Vec2::new(
+                rng.gen_range(settings.min_room_size..=settings.max_room_size),
+                rng.gen_range(settings.min_room_size..=settings.max_room_size),
+            );
+            // Anywhere in the slot, as long as the centre stays well inside
+            let lowest = (centre - size + margin).max(centre - settings.max_room_size / 2.0);
+            let highest = (centre - margin).min(centre + settings.max_room_size / 2.0 - size);
+            let min = Vec2::new(
+                rng.gen_range(lowest.x..=highest.x),
+                rng.gen_range(lowest.y..=highest.y),
+            );
+            Room {
+                min,
+                max: min + size,
+                base_height: *base_height,
+                openings: vec![],
+            }
+        })
+        .collect();
+
+    let half_width = settings.corridor_width / 2.0;
+    let mut corridors = vec![];
+    for (corridor_index, (first, second)) in links.iter().enumerate() {
+        let corridor_id = slots.len() + corridor_index;
+        // Always run left to right or bottom to top
+        let (first, second) = if slots[*first] < slots[*second] {
+            (*first, *second)
+        } else {
+            (*second, *first)
+        };
+        let centre = Vec2::new(slots[first].0 as f32, slots[first].1 as f32) * spacing;
+        let (min, max, first_edge, second_edge) = if slots[first].1 == slots[second].1 {
+            let min = Vec2::new(rooms[first].max.x, centre.y - half_width);
+            let max = Vec2::new(rooms[second].min.x, centre.y + half_width);
+            (
+                min,
+                max,
+                (min, Vec2::new(min.x, max.y)),
+                (Vec2::new(max.x, min.y), max),
+            )
+        } else {
+            let min = Vec2::new(centre.x - half_width, rooms[first].max.y);
+            let max = Vec2::new(centre.x + half_width, rooms[second].min.y);
+            (
+                min,
+                max,
+                (min, Vec2::new(max.x, min.y)),
+                (Vec2::new(min.x, max.y), max),
+            )
+        };
+        rooms[first]
+            .openings
+            .push((first_edge.0, first_edge.1, corridor_id));
+        rooms[second]
+            .openings
+            .push((second_edge.0, second_edge.1, corridor_id));
+        corridors.push(Room {
+            min,
+            max,
+            base_height: (rooms[first].base_height + rooms[second].base_height) / 2.0,
+            openings: vec![
+                (first_edge.0, first_edge.1, first),
+                (second_edge.0, second_edge.1, second),
+            ],
+        });
+    }
+
+    let mut sectors: Vec<Sector> = rooms
+        .iter()
+        .enumerate()
+        .map(|(index, room)| {
+            let color = Vec3::new(
+                rng.gen_range(0.3..1.0),
+                rng.gen_range(0.3..1.0),
+                rng.gen_range(0.3..1.0),
+            );
+            let height = 2.5 + rng.gen_range(0..=6) as f32 * HEIGHT_STEP;
+            rectangle(room, color, height).with_name(&format!("room{index}"))
+        })
+        .collect();
+    sectors.extend(
+        corridors
+            .iter()
+            .map(|corridor| rectangle(corridor, Vec3::splat(0.5), 2.5)),
+    );
+
+    Ok(LevelState::new(sectors))
+}
+
+/// A rectangular sector with a point at each end of every opening, so each
+/// opening gets a portal line of its own.
+fn rectangle(room: &Room, color: Vec3, height: f32) -> Sector {
+    let (min, max) = (room.min, room.max);
+    let size = max - min;
+    // Distance clockwise around the edge from the bottom left corner
+    let around = |point: Vec2| {
+        if point.x == min.x {
+            point.y - min.y
+        } else if point.y == max.y {
+            size.y + point.x - min.x
+        } else if point.x == max.x {
+            size.y + size.x + max.y - point.y
+        } else {
+            2.0 * size.y + size.x + max.x - point.x
+        }
+    };
+
+    let mut points = vec![min, Vec2::new(min.x, max.y), max, Vec2::new(max.x, min.y)];
+    for (start, end, _) in &room.openings {
+        points.push(*start);
+        points.push(*end);
+    }
+    points.sort_by(|a, b| around(*a).total_cmp(&around(*b)));
+    points.dedup();
+
+    let len = points.len();
+    let lines = (0..len)
+        .map(|index| {
+            let (a, b) = (points[index], points[(index + 1) % len]);
+            let wall = room
+                .openings
+                .iter()
+                .find(|(start, end, _)| (*start == a && *end == b) || (*start == b && *end == a))
+                .map_or(Wall::Solid(color), |(_, _, target)| Wall::Portal(*target));
+            Line::new(wall, index, (index + 1) % len)
+        })
+        .collect();
+
+    Sector::new(points, lines, room.base_height, height)
+}
@@ -0,0 +1,40 @@
+use std::{f32::consts::PI, ops::Neg};
+
+use glam::{Mat3, Vec2, Vec3};
+
+/// Transform that moves world space points into camera space, where the
+/// player is at the origin looking down the negative Y axis.
+pub fn create_transform(pos: Vec2, rot: Vec3) -> Mat3 {
+    let mut angle = rot.angle_between(glam::Vec3::NEG_Y);
+    let angle_from_x = rot.angle_between(glam::Vec3::X);
+
+    if angle_from_x < PI / 2.0 {
+        angle = angle.neg()
+    }
+
+    let rot_transform = glam::Mat3::from_axis_angle(Vec3::Z, angle);
+    let trans_transform = glam::Mat3::from_translation(pos.neg());
+    rot_transform * trans_transform
+}
+
+/// Distance along the ray from `origin` in `direction` to the segment
+/// `start`..`end`, if the ray hits it.
+pub fn intersection_distance(origin: Vec2, direction: Vec2, start: Vec2, end: Vec2) -> Option<f32> {
+    let v1 = (origin - start).extend(0.0);
+    let v2 = (end - start).extend(0.0);
+    let v3 = Vec3::new(-direction.y, direction.x, 0.0);
+
+    let dot = v2.dot(v3);
+    if dot.abs() < 0.000001 {
+        return None;
+    }
+
+    let t1 = v2.cross(v1).z / dot;
+    let t2 = v1.dot(v3) / dot;
+
+    if t1 >= 0.0 && (0.0..=1.0).contains(&t2) {
+        return Some(t1);
+    }
+
+    None
+}
@@ -0,0 +1,323 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    canvas::Canvas,
+    font::Font,
+    game::GameState,
+    primitives::{LineDescriptor, RectDescriptor, TextDescriptor},
+};
+
+/// Health at or below this is drawn in red.
+const LOW_HEALTH: i32 = 25;
+
+const COMPASS_POINTS: [(f32, &str); 8] = [
+    (0.0, "N"),
+    (45.0, "NE"),
+    (90.0, "E"),
+    (135.0, "SE"),
+    (180.0, "S"),
+    (225.0, "SW"),
+    (270.0, "W"),
+    (315.0, "NW"),
+];
+
+/// The widgets drawn on top of the 3D view, normally read from a JSON file
+/// so the layout can be changed without a rebuild.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hud {
+    widgets: Vec<Widget>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Widget {
+    pub kind: WidgetKind,
+    pub anchor: Anchor,
+    /// Pixels from the anchored position, positive is right and down.
+    #[serde(default)]
+    pub offset: Vec2,
+    #[serde(default = "default_scale")]
+    pub scale: u32,
+    #[serde(default = "default_color")]
+    pub color: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum WidgetKind {
+    Health,
+    Armor,
+    Ammo,
+    /// Name of the weapon in hand.
+    Weapon,
+    Crosshair {
+        size: f32,
+    },
+    /// Name of the sector the player is standing in, if it has one.
+    SectorName,
+    /// Position, facing and sector of the player, see `GameState::where_am_i`.
+    Position,
+    /// The last message a level script showed, while it lasts.
+    Message,
+    /// Strip of compass points centred on the player's heading.
+    Compass {
+        width: f32,
+    },
+    /// Solid bar across the whole screen, usually behind the counters.
+    StatusBar {
+        height: f32,
+    },
+}
+
+/// Which point of the screen a widget is attached to. The same point of the
+/// widget is lined up with it, so `BottomRight` widgets grow up and left.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Centre,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    fn place(self, screen: Vec2, size: Vec2, offset: Vec2) -> Vec2 {
+        let factor = match self {
+            Anchor::TopLeft => Vec2::new(0.0, 0.0),
+            Anchor::Top => Vec2::new(0.5, 0.0),
+            Anchor::TopRight => Vec2::new(1.0, 0.0),
+            Anchor::Left => Vec2::new(0.0, 0.5),
+            Anchor::Centre => Vec2::new(0.5, 0.5),
+            Anchor::Right => Vec2::new(1.0, 0.5),
+            Anchor::BottomLeft => Vec2::new(0.0, 1.0),
+            Anchor::Bottom => Vec2::new(0.5, 1.0),
+            Anchor::BottomRight => Vec2::new(1.0, 1.0),
+        };
+        ((screen - size) * factor + offset).round()
+    }
+}
+
+fn default_scale() -> u32 {
+    2
+}
+
+fn default_color() -> Vec3 {
+    Vec3::splat(1.0)
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        let widget = |kind, anchor, offset: Vec2| Widget {
+            kind,
+            anchor,
+            offset,
+            scale: default_scale(),
+            color: default_color(),
+        };
+
+        Self {
+            widgets: vec![
+                Widget {
+                    color: Vec3::splat(0.15),
+                    ..widget(
+                        WidgetKind::StatusBar { height: 32.0 },
+                        Anchor::Bottom,
+                        Vec2::ZERO,
+                    )
+                },
+                widget(WidgetKind::Health, Anchor::BottomLeft, Vec2::new(8.0, -8.0)),
+                widget(WidgetKind::Armor, Anchor::Bottom, Vec2::new(0.0, -8.0)),
+                widget(WidgetKind::Ammo, Anchor::BottomRight, Vec2::new(-8.0, -8.0)),
+                Widget {
+                    scale: 1,
+                    ..widget(
+                        WidgetKind::Weapon,
+                        Anchor::BottomRight,
+                        Vec2::new(-8.0, -40.0),
+                    )
+                },
+                widget(
+                    WidgetKind::Crosshair { size: 12.0 },
+                    Anchor::Centre,
+                    Vec2::ZERO,
+                ),
+                widget(
+                    WidgetKind::Compass { width: 240.0 },
+                    Anchor::Top,
+                    Vec2::new(0.0, 8.0),
+                ),
+                Widget {
+                    scale: 1,
+                    ..widget(WidgetKind::SectorName, Anchor::TopLeft, Vec2::new(8.0, 8.0))
+                },
+                widget(WidgetKind::Message, Anchor::Top, Vec2::new(0.0, 40.0)),
+            ],
+        }
+    }
+}
+
+impl Hud {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let hud = serde_json::from_reader(reader)?;
+
+        Ok(hud)
+    }
+
+    pub fn widgets(&self) -> &[Widget] {
+        &self.widgets
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, font: &Font, game_state: &GameState) {
+        for widget in &self.widgets {
+            widget.draw(canvas, font, game_state);
+        }
+    }
+}
+
+impl Widget {
+    fn draw(&self, canvas: &mut Canvas, font: &Font, game_state: &GameState) {
+        let screen = Vec2::new(canvas.width() as f32, canvas.height() as f32);
+        let player = game_state.player();
+
+        match self.kind {
+            WidgetKind::Health => {
+                let color = if player.health <= LOW_HEALTH {
+                    Vec3::new(1.0, 0.2, 0.2)
+                } else {
+                    self.color
+                };
+                self.draw_text(canvas, font, &format!("HEALTH {:3}", player.health), color);
+            }
+            WidgetKind::Armor => {
+                self.draw_text(
+                    canvas,
+                    font,
+                    &format!("ARMOR {:3}", player.armor),
+                    self.color,
+                );
+            }
+            WidgetKind::Ammo => {
+                self.draw_text(canvas, font, &format!("AMMO {:3}", player.ammo), self.color);
+            }
+            WidgetKind::Weapon => {
+                let name = game_state.weapons().selected().name.to_uppercase();
+                self.draw_text(canvas, font, &name, self.color);
+            }
+            WidgetKind::SectorName => {
+                let name = game_state
+                    .find_current_sector()
+                    .and_then(|sector_id| game_state.level().sector(sector_id).name());
+                if let Some(name) = name {
+                    self.draw_text(canvas, font, name, self.color);
+                }
+            }
+            WidgetKind::Message => {
+                if let Some(message) = game_state.message() {
+                    self.draw_text(canvas, font, message, self.color);
+                }
+            }
+            WidgetKind::Position => {
+                self.draw_text(canvas, font, &game_state.where_am_i(), self.color);
+            }
+            WidgetKind::Crosshair { size } => {
+                let centre = self.anchor.place(screen, Vec2::splat(size), self.offset)
+                    + Vec2::splat(size / 2.0);
+                let stroke = self.scale as f32 / 2.0;
+                for axis in [Vec2::X, Vec2::Y] {
+                    canvas.draw_line(&LineDescriptor {
+                        start: centre - axis * size / 2.0,
+                        end: centre + axis * size / 2.0,
+                        color: self.color,
+                        stroke,
+                    });
+                }
+            }
+            WidgetKind::StatusBar { height } => {
+                let size = Vec2::new(screen.x, height);
+                canvas.draw_rect(&RectDescriptor {
+                    top_left: self.anchor.place(screen, size, self.offset),
+                    size,
+                    color: self.color,
+                });
+            }
+            WidgetKind::Compass { width } => {
+                self.draw_compass(canvas, font, width, heading(game_state.rot()));
+            }
+        }
+    }
+
+    fn draw_text(&self, canvas: &mut Canvas, font: &Font, text: &str, color: Vec3) {
+        let screen = Vec2::new(canvas.width() as f32, canvas.height() as f32);
+        let size = font.measure(text, self.scale);
+        canvas.draw_text(
+            font,
+            &TextDescriptor {
+                text,
+                top_left: self.anchor.place(screen, size, self.offset),
+                color,
+                scale: self.scale,
+                alpha: 1.0,
+            },
+        );
+    }
+
+    /// The compass shows 90 degrees either side of `heading`, with the
+    /// heading itself printed underneath the centre mark.
+    fn draw_compass(&self, canvas: &mut Canvas, font: &Font, width: f32, heading: f32) {
+        let screen = Vec2::new(canvas.width() as f32, canvas.height() as f32);
+        let line_height = (font.height() * self.scale) as f32;
+        let size = Vec2::new(width, line_height * 2.0);
+        let top_left = self.anchor.place(screen, size, self.offset);
+        let centre = top_left.x + width / 2.0;
+
+        for (bearing, label) in COMPASS_POINTS {
+            let delta = (bearing - heading + 180.0).rem_euclid(360.0) - 180.0;
+            let label_size = font.measure(label, self.scale);
+            let x = centre + delta / 90.0 * (width - label_size.x) / 2.0;
+            if delta.abs() <= 90.0 {
+                canvas.draw_text(
+                    font,
+                    &TextDescriptor {
+                        text: label,
+                        top_left: Vec2::new((x - label_size.x / 2.0).round(), top_left.y),
+                        color: self.color,
+                        scale: self.scale,
+                        alpha: 1.0 - delta.abs() / 90.0 * 0.75,
+                    },
+                );
+            }
+        }
+
+        let text = format!("{:03.0}", heading.round() % 360.0);
+        let text_size = font.measure(&text, self.scale);
+        canvas.draw_text(
+            font,
+            &TextDescriptor {
+                text: &text,
+                top_left: Vec2::new(
+                    (centre - text_size.x / 2.0).round(),
+                    top_left.y + line_height,
+                ),
+                color: self.color,
+                scale: self.scale,
+                alpha: 1.0,
+            },
+        );
+    }
+}
+
+/// Degrees clockwise from north (negative Y) the player is facing.
+fn heading(rot: Vec3) -> f32 {
+    rot.x.atan2(-rot.y).to_degrees().rem_euclid(360.0)
+}
@@ -8,6 +8,13 @@ pub struct InputState {
     right: bool,
     rot_left: bool,
     rot_right: bool,
+    up: bool,
+    down: bool,
+    fire: bool,
+    /// The use key was pressed since the game last looked.
+    use_pressed: bool,
+    /// A weapon change asked for since the game last looked.
+    weapon_switch: Option<WeaponSwitch>,
 }
 
 impl InputState {
@@ -35,6 +42,50 @@ impl InputState {
         self.rot_right = movement;
     }
 
+    pub fn up(&mut self, movement: bool) {
+        self.up = movement;
+    }
+
+    pub fn down(&mut self, movement: bool) {
+        self.down = movement;
+    }
+
+    pub fn fire(&mut self, firing: bool) {
+        self.fire = firing;
+    }
+
+    pub fn firing(&self) -> bool {
+        self.fire
+    }
+
+    pub fn press_use(&mut self) {
+        self.use_pressed = true;
+    }
+
+    /// Whether use was pressed, which is then forgotten.
+    pub fn take_use(&mut self) -> bool {
+        std::mem::take(&mut self.use_pressed)
+    }
+
+    /// Asks for the weapon in `slot`, counting from zero.
+    pub fn select_weapon(&mut self, slot: usize) {
+        self.weapon_switch = Some(WeaponSwitch::Slot(slot));
+    }
+
+    pub fn next_weapon(&mut self) {
+        self.weapon_switch = Some(WeaponSwitch::Next);
+    }
+
+    /// The weapon change asked for, if any, which is then forgotten.
+    pub fn take_weapon_switch(&mut self) -> Option<WeaponSwitch> {
+        self.weapon_switch.take()
+    }
+
+    /// One for up, minus one for down.
+    pub fn vertical(&self) -> f32 {
+        (self.up as i32 - self.down as i32) as f32
+    }
+
     pub fn move_vec(&self) -> Vec2 {
         let mut vec = Vec2::ZERO;
         vec.x += if self.forward { 1.0 } else { 0.0 };
@@ -62,3 +113,9 @@ pub enum RotationDirection {
     Left,
     Right,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponSwitch {
+    Slot(usize),
+    Next,
+}
@@ -1,95 +1,265 @@
 use serde::{Deserialize, Serialize};
-use std::{
-    fs::File,
-    io::{self, BufReader, BufWriter},
-    path::Path,
-};
+use std::{fs, io, path::Path};
+
+use glam::{Mat3, Vec2, Vec3, Vec3Swizzles};
 
-use glam::{Vec2, Vec3};
+use crate::{
+    format::{self, Encoding},
+    geometry::intersection_distance,
+    mover::MoverDef,
+    spatial::{Bounds, SectorIndex},
+};
 
 pub type SectorId = usize;
 
-#[derive(Default, Serialize, Deserialize)]
+/// Line ends closer than this are the same point.
+const SAME_POINT: f32 = 1e-4;
+
+/// Most decals a line keeps, the oldest go first.
+const MAX_DECALS: usize = 16;
+
+/// Where `LevelState::trace_hit` was stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceHit {
+    pub fraction: f32,
+    /// Where the trace stopped, on the far side of any warps it went
+    /// through.
+    pub pos: Vec3,
+    /// Sector and index of the wall or portal step in the way, `None` when
+    /// the trace started outside the level.
+    pub line: Option<(SectorId, usize)>,
+}
+
+/// A move through a warp portal, see `LevelState::crossed_warp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarpCrossing {
+    /// Sector and index of the portal line.
+    pub line: (SectorId, usize),
+    /// Sector on the other side.
+    pub target: SectorId,
+    pub warp: Warp,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct LevelState {
     sectors: Vec<Sector>,
+    #[serde(default)]
+    movers: Vec<MoverDef>,
+    /// Path of the level's script, relative to the level file.
+    #[serde(default)]
+    script: Option<String>,
+    #[serde(skip)]
+    index: SectorIndex,
 }
 
 impl LevelState {
-    pub fn demo() -> Self {
+    pub fn new(sectors: Vec<Sector>) -> Self {
+        let index = SectorIndex::new(&sectors);
         Self {
-            sectors: vec![
-                Sector {
-                    points: vec![
-                        Vec2::new(-1.0, -1.0),
-                        Vec2::new(-1.0, 0.5),
-                        Vec2::new(-0.5, 1.0),
-                        Vec2::new(1.0, 1.0),
-                        Vec2::new(1.0, -1.0),
-                    ],
-                    lines: vec![
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(1.0, 0.0, 0.0)),
-                            point_1_id: 0,
-                            point_2_id: 1,
-                        },
-                        Line {
-                            wall_type: Wall::Portal(1),
-                            point_1_id: 1,
-                            point_2_id: 2,
-                        },
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(0.0, 1.0, 1.0)),
-                            point_1_id: 2,
-                            point_2_id: 3,
-                        },
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(1.0, 0.0, 1.0)),
-                            point_1_id: 3,
-                            point_2_id: 4,
-                        },
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(0.0, 0.0, 1.0)),
-                            point_1_id: 4,
-                            point_2_id: 0,
-                        },
-                    ],
-                    base_height: 0.0,
-                    height: 2.5,
-                },
-                Sector {
-                    points: vec![
-                        Vec2::new(-1.0, 0.5),
-                        Vec2::new(-1.5, 1.5),
-                        Vec2::new(-0.5, 2.0),
-                        Vec2::new(-0.5, 1.0),
-                    ],
-                    lines: vec![
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(1.0, 0.0, 0.0)),
-                            point_1_id: 0,
-                            point_2_id: 1,
-                        },
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(1.0, 1.0, 0.0)),
-                            point_1_id: 1,
-                            point_2_id: 2,
-                        },
-                        Line {
-                            wall_type: Wall::Solid(Vec3::new(0.0, 1.0, 1.0)),
-                            point_1_id: 2,
-                            point_2_id: 3,
-                        },
-                        Line {
-                            wall_type: Wall::Portal(0),
-                            point_1_id: 3,
-                            point_2_id: 0,
-                        },
+            sectors,
+            movers: vec![],
+            script: None,
+            index,
+        }
+    }
+
+    pub fn with_movers(mut self, movers: Vec<MoverDef>) -> Self {
+        self.movers = movers;
+        self
+    }
+
+    pub fn with_script(mut self, script: Option<String>) -> Self {
+        self.script = script;
+        self
+    }
+
+    pub fn demo() -> Self {
+        Self::new(vec![
+            Sector {
+                points: vec![
+                    Vec2::new(-1.0, -1.0),
+                    Vec2::new(-1.0, 0.5),
+                    Vec2::new(-0.5, 1.0),
+                    Vec2::new(1.0, 1.0),
+                    Vec2::new(1.0, -1.0),
+                ],
+                lines: vec![
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(1.0, 0.0, 0.0)),
+                        point_1_id: 0,
+                        point_2_id: 1,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Portal(1),
+                        point_1_id: 1,
+                        point_2_id: 2,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(0.0, 1.0, 1.0)),
+                        point_1_id: 2,
+                        point_2_id: 3,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(1.0, 0.0, 1.0)),
+                        point_1_id: 3,
+                        point_2_id: 4,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(0.0, 0.0, 1.0)),
+                        point_1_id: 4,
+                        point_2_id: 0,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                ],
+                base_height: 0.0,
+                height: 2.5,
+                name: Some("Hall".to_string()),
+                tag: None,
+                sky: false,
+            },
+            Sector {
+                points: vec![
+                    Vec2::new(-1.0, 0.5),
+                    Vec2::new(-1.5, 1.5),
+                    Vec2::new(-0.5, 2.0),
+                    Vec2::new(-0.5, 1.0),
+                ],
+                lines: vec![
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(1.0, 0.0, 0.0)),
+                        point_1_id: 0,
+                        point_2_id: 1,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(1.0, 1.0, 0.0)),
+                        point_1_id: 1,
+                        point_2_id: 2,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Solid(Vec3::new(0.0, 1.0, 1.0)),
+                        point_1_id: 2,
+                        point_2_id: 3,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                    Line {
+                        wall_type: Wall::Portal(0),
+                        point_1_id: 3,
+                        point_2_id: 0,
+                        special: None,
+                        warp: None,
+                        mask: None,
+                        texture: None,
+                        decals: vec![],
+                    },
+                ],
+                base_height: 1.0,
+                height: 2.5,
+                name: Some("Alcove".to_string()),
+                tag: None,
+                sky: false,
+            },
+        ])
+    }
+
+    /// A `columns` by `rows` grid of square sectors, one unit across, joined by
+    /// portals with the odd wall in between. Used to stress the renderer and
+    /// level queries with levels much bigger than the demo.
+    pub fn grid(columns: usize, rows: usize) -> Self {
+        let sector_id = |column: usize, row: usize| row * columns + column;
+        // Deterministic scatter of walls between neighbouring sectors
+        let blocked = |column: usize, row: usize, vertical: bool| {
+            (column * 7 + row * 13 + vertical as usize * 5).is_multiple_of(9)
+        };
+        let color = |column: usize, row: usize| {
+            Vec3::new(
+                (column % 4) as f32 / 3.0,
+                (row % 4) as f32 / 3.0,
+                ((column + row) % 2) as f32,
+            )
+        };
+
+        let mut sectors = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = (column as f32, row as f32);
+
+                // left, top, right and bottom neighbours
+                let neighbours = [
+                    (column > 0 && !blocked(column - 1, row, true))
+                        .then(|| sector_id(column - 1, row)),
+                    (row + 1 < rows && !blocked(column, row, false))
+                        .then(|| sector_id(column, row + 1)),
+                    (column + 1 < columns && !blocked(column, row, true))
+                        .then(|| sector_id(column + 1, row)),
+                    (row > 0 && !blocked(column, row - 1, false))
+                        .then(|| sector_id(column, row - 1)),
+                ];
+
+                let lines = neighbours
+                    .iter()
+                    .enumerate()
+                    .map(|(index, neighbour)| {
+                        let wall_type = match neighbour {
+                            Some(sector_id) => Wall::Portal(*sector_id),
+                            None => Wall::Solid(color(column, row)),
+                        };
+                        Line::new(wall_type, index, (index + 1) % 4)
+                    })
+                    .collect();
+
+                sectors.push(Sector::new(
+                    vec![
+                        Vec2::new(x, y),
+                        Vec2::new(x, y + 1.0),
+                        Vec2::new(x + 1.0, y + 1.0),
+                        Vec2::new(x + 1.0, y),
                     ],
-                    base_height: 1.0,
-                    height: 2.5,
-                },
-            ],
+                    lines,
+                    ((column + row) % 3) as f32 * 0.25,
+                    2.5,
+                ));
+            }
         }
+
+        Self::new(sectors)
     }
 
     pub fn sectors(&self) -> &Vec<Sector> {
@@ -100,40 +270,285 @@ impl LevelState {
         &self.sectors[index]
     }
 
-    pub fn _load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let level = serde_json::from_reader(reader)?;
+    /// Doors, lifts and crushers, see `mover`.
+    pub fn movers(&self) -> &[MoverDef] {
+        &self.movers
+    }
+
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+
+    /// Moves a sector's floor and ceiling. Only heights can change in place,
+    /// the sector index only cares about points.
+    pub fn set_heights(&mut self, sector_id: SectorId, base_height: f32, height: f32) {
+        let sector = &mut self.sectors[sector_id];
+        sector.base_height = base_height;
+        sector.height = height;
+    }
+
+    /// Sticks a decal on a line, making room by dropping the line's oldest
+    /// once it has plenty.
+    pub fn add_decal(&mut self, sector_id: SectorId, line_index: usize, decal: Decal) {
+        let decals = &mut self.sectors[sector_id].lines[line_index].decals;
+        if decals.len() >= MAX_DECALS {
+            decals.remove(0);
+        }
+        decals.push(decal);
+    }
 
-        Ok(level)
+    /// Reads a JSON or binary level of any version, see `format`.
+    pub fn _load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        format::decode(&fs::read(path)?)
     }
 
+    /// Writes the level as binary when `path` ends in `.bin`, otherwise as
+    /// JSON.
     pub fn _save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, self)?;
-        Ok(())
+        let path = path.as_ref();
+        fs::write(path, format::encode(self, Encoding::for_path(path))?)
     }
 
     pub fn find_current_sector(&self, pos: Vec2) -> Option<SectorId> {
-        self.sectors().iter().position(|sector| sector.contains(pos))
+        self.index
+            .candidates(pos)
+            .find(|sector_id| self.sectors[*sector_id].contains(pos))
+    }
+
+    /// Sectors tagged `tag`, for line specials to act on.
+    pub fn tagged(&self, tag: u32) -> impl Iterator<Item = SectorId> + '_ {
+        self.sectors
+            .iter()
+            .enumerate()
+            .filter(move |(_, sector)| sector.tag == Some(tag))
+            .map(|(sector_id, _)| sector_id)
+    }
+
+    /// The line on the other side of a portal, going back along the same
+    /// edge, or where a warp puts it.
+    pub fn opposite_line(
+        &self,
+        sector_id: SectorId,
+        line_index: usize,
+    ) -> Option<(SectorId, usize)> {
+        // Levels being validated may have lines with points that don't exist
+        let points = |sector: &Sector, line: &Line| {
+            Some((
+                *sector.points.get(line.point_1_id)?,
+                *sector.points.get(line.point_2_id)?,
+            ))
+        };
+        let sector = &self.sectors[sector_id];
+        let line = &sector.lines[line_index];
+        let Wall::Portal(target) = line.wall_type else {
+            return None;
+        };
+        let (start, end) = points(sector, line)?;
+        let (start, end) = match line.warp {
+            Some(warp) => (warp.apply(start), warp.apply(end)),
+            None => (start, end),
+        };
+        let target_sector = self.sectors.get(target)?;
+        let line_index = target_sector.lines.iter().position(|other| {
+            other.wall_type == Wall::Portal(sector_id)
+                && points(target_sector, other).is_some_and(|(other_start, other_end)| {
+                    other_start.distance(end) < SAME_POINT && other_end.distance(start) < SAME_POINT
+                })
+        })?;
+        Some((target, line_index))
+    }
+
+    /// First sector called `name`.
+    pub fn find_sector(&self, name: &str) -> Option<SectorId> {
+        self.sectors
+            .iter()
+            .position(|sector| sector.name() == Some(name))
+    }
+
+    /// Follows the straight line from `from` to `to` (z is height) through
+    /// the sectors it crosses, returning how far along it, from 0 to 1, a
+    /// wall or the floor or ceiling of a portal blocks it. `None` means
+    /// nothing is in the way.
+    pub fn trace(&self, from: Vec3, to: Vec3) -> Option<f32> {
+        self.trace_hit(from, to).map(|hit| hit.fraction)
+    }
+
+    /// Like `trace`, also saying which line stopped the line of sight.
+    /// Traces go on through warps from where they put the line.
+    pub fn trace_hit(&self, mut from: Vec3, mut to: Vec3) -> Option<TraceHit> {
+        let mut sector_id = self.find_current_sector(from.xy())?;
+
+        for _ in 0..self.sectors.len() {
+            let sector = &self.sectors[sector_id];
+            if sector.contains(to.xy()) {
+                return None;
+            }
+
+            // The line starts outside every sector but the first, so the
+            // furthest crossing is where it leaves
+            let exit = (0..sector.lines.len())
+                .filter_map(|line_index| {
+                    let (start, end) = sector.line_points(line_index);
+                    intersection_distance(from.xy(), (to - from).xy(), start, end)
+                        .map(|distance| (distance, line_index))
+                })
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            let Some((distance, line_index)) = exit else {
+                return Some(TraceHit {
+                    fraction: 0.0,
+                    pos: from,
+                    line: None,
+                });
+            };
+            if distance > 1.0 {
+                return None;
+            }
+
+            let hit = TraceHit {
+                fraction: distance,
+                pos: from.lerp(to, distance),
+                line: Some((sector_id, line_index)),
+            };
+            let line = &sector.lines[line_index];
+            let Wall::Portal(next) = line.wall_type else {
+                return Some(hit);
+            };
+            let Some(next_sector) = self.sectors.get(next) else {
+                return Some(hit);
+            };
+            let warp = line.warp.unwrap_or_default();
+            let height = from.z + (to.z - from.z) * distance;
+            let floor = sector
+                .base_height
+                .max(next_sector.base_height - warp.height);
+            let ceiling = (sector.base_height + sector.height)
+                .min(next_sector.base_height + next_sector.height - warp.height);
+            if height < floor || height > ceiling {
+                return Some(hit);
+            }
+            if line.warp.is_some() {
+                from = warp.apply_3d(from);
+                to = warp.apply_3d(to);
+            }
+            sector_id = next;
+        }
+
+        Some(TraceHit {
+            fraction: 1.0,
+            pos: to,
+            line: None,
+        })
+    }
+
+    /// The warp portal a move straight from `from` to `to` leaves `from`'s
+    /// sector through, if there is one.
+    pub fn crossed_warp(&self, from: Vec2, to: Vec2) -> Option<WarpCrossing> {
+        let sector_id = self.find_current_sector(from)?;
+        let sector = &self.sectors[sector_id];
+        (0..sector.lines.len()).find_map(|line_index| {
+            let line = &sector.lines[line_index];
+            let (Wall::Portal(target), Some(warp)) = (line.wall_type, line.warp) else {
+                return None;
+            };
+            let (start, end) = sector.line_points(line_index);
+            intersection_distance(from, to - from, start, end)
+                .filter(|distance| *distance <= 1.0)?;
+            Some(WarpCrossing {
+                line: (sector_id, line_index),
+                target,
+                warp,
+            })
+        })
+    }
+
+    /// Whether a move straight from `from` to `to` goes through a line of
+    /// `from`'s sector that isn't a portal, or a portal with a blocking mask.
+    pub fn blocked(&self, from: Vec2, to: Vec2) -> bool {
+        let Some(sector_id) = self.find_current_sector(from) else {
+            return false;
+        };
+        let sector = &self.sectors[sector_id];
+        (0..sector.lines.len())
+            .filter(|line_index| {
+                let line = &sector.lines[*line_index];
+                !matches!(line.wall_type, Wall::Portal(_))
+                    || line.mask.is_some_and(|mask| mask.blocking)
+            })
+            .any(|line_index| {
+                let (start, end) = sector.line_points(line_index);
+                intersection_distance(from, to - from, start, end)
+                    .is_some_and(|distance| distance <= 1.0)
+            })
+    }
+
+    pub fn sector_bounds(&self, sector_id: SectorId) -> &Bounds {
+        self.index.bounds(sector_id)
     }
 }
 
 /// A collection of lines that create a convex shape, some lines will be
 /// treated as walls, others as portals. The portals will point to the next
 /// sector which should be rendered in the window created by the portal. It is
-/// assumed that the next sector is in the correct location, there will be no
-/// checks that portals have matching dimensions.
-#[derive(Debug, Serialize, Deserialize)]
+/// assumed that the next sector is in the correct location, or where the
+/// portal's warp says it is, there will be no checks that portals have
+/// matching dimensions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sector {
     points: Vec<Vec2>,
     lines: Vec<Line>,
     base_height: f32,
     height: f32,
+    #[serde(default)]
+    name: Option<String>,
+    /// Picks the sector out for line specials.
+    #[serde(default)]
+    tag: Option<u32>,
+    /// Open to the sky rather than roofed, the ceiling still stops things
+    /// going higher.
+    #[serde(default)]
+    sky: bool,
 }
 
 impl Sector {
+    pub fn new(points: Vec<Vec2>, lines: Vec<Line>, base_height: f32, height: f32) -> Self {
+        Self {
+            points,
+            lines,
+            base_height,
+            height,
+            name: None,
+            tag: None,
+            sky: false,
+        }
+    }
+
+    /// A sector with a line along every edge of the polygon, all of them
+    /// `wall`. The points are put in the order `contains` expects.
+    pub fn from_points(mut points: Vec<Vec2>, wall: Wall, base_height: f32, height: f32) -> Self {
+        if signed_area(&points) > 0.0 {
+            points.reverse();
+        }
+        let lines = (0..points.len())
+            .map(|index| Line::new(wall, index, (index + 1) % points.len()))
+            .collect();
+        Self::new(points, lines, base_height, height)
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    pub fn with_sky(mut self) -> Self {
+        self.sky = true;
+        self
+    }
+
     pub fn walls(&self) -> Vec<(Vec2, Vec2, Vec3)> {
         self.lines
             .iter()
@@ -152,7 +567,7 @@ impl Sector {
             .collect()
     }
 
-    pub fn portals(&self) -> Vec<(Vec2, Vec2, SectorId)> {
+    pub fn portals(&self) -> Vec<(Vec2, Vec2, SectorId, Option<Warp>)> {
         self.lines
             .iter()
             .filter(|line| matches!(line.wall_type, Wall::Portal(_)))
@@ -162,6 +577,7 @@ impl Sector {
                         self.points[line.point_1_id],
                         self.points[line.point_2_id],
                         sector_id,
+                        line.warp,
                     )
                 } else {
                     panic!() // todo: yeah...
@@ -170,15 +586,125 @@ impl Sector {
             .collect()
     }
 
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Both ends of a line.
+    pub fn line_points(&self, line_index: usize) -> (Vec2, Vec2) {
+        let line = &self.lines[line_index];
+        (self.points[line.point_1_id], self.points[line.point_2_id])
+    }
+
+    pub fn set_point(&mut self, point_index: usize, pos: Vec2) {
+        self.points[point_index] = pos;
+    }
+
+    pub fn set_wall(&mut self, line_index: usize, wall: Wall) {
+        self.lines[line_index].wall_type = wall;
+    }
+
+    pub fn set_texture(&mut self, line_index: usize, texture: Option<&str>) {
+        self.lines[line_index].texture = texture.map(str::to_string);
+    }
+
+    /// Splits a line between neighbouring points in two at `pos`, adding a
+    /// point between them. Both halves keep everything else about the line.
+    /// Returns the new point, or `None` if the line doesn't follow an edge of
+    /// the polygon.
+    pub fn split_line(&mut self, line_index: usize, pos: Vec2) -> Option<usize> {
+        let len = self.points.len();
+        let line = self.lines[line_index].clone();
+        let first = if (line.point_1_id + 1) % len == line.point_2_id {
+            line.point_1_id
+        } else if (line.point_2_id + 1) % len == line.point_1_id {
+            line.point_2_id
+        } else {
+            return None;
+        };
+
+        let new_point = first + 1;
+        self.points.insert(new_point, pos);
+        for line in &mut self.lines {
+            for point_id in [&mut line.point_1_id, &mut line.point_2_id] {
+                if *point_id >= new_point {
+                    *point_id += 1;
+                }
+            }
+        }
+
+        let line = &mut self.lines[line_index];
+        let end = line.point_2_id;
+        line.point_2_id = new_point;
+        let half = Line {
+            point_1_id: new_point,
+            point_2_id: end,
+            ..line.clone()
+        };
+        self.lines.insert(line_index + 1, half);
+        Some(new_point)
+    }
+
     pub fn height(&self) -> f32 {
         self.height
     }
 
+    pub fn set_height(&mut self, height: f32) {
+        self.height = height;
+    }
+
+    pub fn set_base_height(&mut self, base_height: f32) {
+        self.base_height = base_height;
+    }
+
     pub fn base_height(&self) -> f32 {
         self.base_height
     }
 
-    fn contains(&self, pos: Vec2) -> bool {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn tag(&self) -> Option<u32> {
+        self.tag
+    }
+
+    pub fn sky(&self) -> bool {
+        self.sky
+    }
+
+    pub fn set_sky(&mut self, sky: bool) {
+        self.sky = sky;
+    }
+
+    /// Centre of mass of the sector's area, which for a convex sector is
+    /// always inside it.
+    pub fn centroid(&self) -> Vec2 {
+        let mut area = 0.0;
+        let mut centroid = Vec2::ZERO;
+        for p_index in 0..self.points.len() {
+            let p1 = self.points[p_index];
+            let p2 = self.points[(p_index + 1) % self.points.len()];
+            let cross = p1.perp_dot(p2);
+            area += cross;
+            centroid += (p1 + p2) * cross;
+        }
+
+        if area.abs() <= f32::EPSILON {
+            return self.points.iter().sum::<Vec2>() / self.points.len().max(1) as f32;
+        }
+        centroid / (3.0 * area)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(&self.points)
+    }
+
+    pub fn contains(&self, pos: Vec2) -> bool {
         for p_index in 0..self.points.len() {
             let p1 = self.points[p_index];
             let p2 = self.points[(p_index + 1) % self.points.len()];
@@ -194,15 +720,226 @@ impl Sector {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Line {
     wall_type: Wall,
     point_1_id: usize,
     point_2_id: usize,
+    #[serde(default)]
+    special: Option<Special>,
+    /// Where the far side of a portal line is, when it isn't right here.
+    #[serde(default)]
+    warp: Option<Warp>,
+    /// Grate, fence or window hung in a portal line.
+    #[serde(default)]
+    mask: Option<Mask>,
+    /// Name of the texture drawn on the wall, and on the steps of a portal,
+    /// in place of the colour.
+    #[serde(default)]
+    texture: Option<String>,
+    /// Bullet holes and the like added during play, never saved.
+    #[serde(skip)]
+    decals: Vec<Decal>,
+}
+
+impl Line {
+    pub fn new(wall_type: Wall, point_1_id: usize, point_2_id: usize) -> Self {
+        Self {
+            wall_type,
+            point_1_id,
+            point_2_id,
+            special: None,
+            warp: None,
+            mask: None,
+            texture: None,
+            decals: vec![],
+        }
+    }
+
+    pub fn with_special(mut self, special: Special) -> Self {
+        self.special = Some(special);
+        self
+    }
+
+    pub fn with_warp(mut self, warp: Warp) -> Self {
+        self.warp = Some(warp);
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn with_texture(mut self, texture: &str) -> Self {
+        self.texture = Some(texture.to_string());
+        self
+    }
+
+    pub fn wall_type(&self) -> Wall {
+        self.wall_type
+    }
+
+    pub fn special(&self) -> Option<&Special> {
+        self.special.as_ref()
+    }
+
+    pub fn warp(&self) -> Option<Warp> {
+        self.warp
+    }
+
+    pub fn mask(&self) -> Option<Mask> {
+        self.mask
+    }
+
+    pub fn texture(&self) -> Option<&str> {
+        self.texture.as_deref()
+    }
+
+    pub fn decals(&self) -> &[Decal] {
+        &self.decals
+    }
+
+    pub fn point_ids(&self) -> (usize, usize) {
+        (self.point_1_id, self.point_2_id)
+    }
+}
+
+/// Something stuck on a wall during play, like a bullet hole or a sign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decal {
+    /// Distance from the line's first point to the middle of the decal.
+    pub along: f32,
+    /// Height of the middle above the sector's floor.
+    pub height: f32,
+    pub size: Vec2,
+    /// Drawn tinted by `color`, or just `color` when there's no texture of
+    /// this name.
+    pub texture: Option<String>,
+    pub color: Vec3,
+}
+
+/// Twice the area of the polygon, negative when the points go clockwise
+/// with y pointing up, the order sectors use.
+pub fn signed_area(points: &[Vec2]) -> f32 {
+    (0..points.len())
+        .map(|index| points[index].perp_dot(points[(index + 1) % points.len()]))
+        .sum()
+}
+
+/// Something a line does when the player uses it, walks over it or shoots
+/// it, run by `GameState`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Special {
+    pub activation: Activation,
+    pub action: Action,
+    /// Sectors, and monsters spawned with it, that the action works on.
+    #[serde(default)]
+    pub tag: Option<u32>,
+    /// Works every time rather than only the first.
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Activation {
+    /// Pressing use at the line, a switch.
+    Use,
+    /// Walking over the line from either side.
+    Cross,
+    /// Shooting the line.
+    Shoot,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    /// Sets off the movers of the tagged sectors.
+    Movers,
+    /// Moves the player to the middle of the first tagged sector.
+    Teleport,
+    /// Ends the level, loading the file `next` when there is one.
+    Exit { next: Option<String> },
+    /// Counts a secret as found.
+    Secret,
+    /// Tagged monsters come for the player.
+    Alert,
+}
+
+/// Where the far side of a portal really is, for portals between sectors
+/// that aren't next to each other: teleporters that can be seen through, or
+/// rooms bigger on the inside. A point on this side is rotated by `angle`
+/// and moved by `offset` to get the same point on the other side. Monsters
+/// don't go through warps, to them a warp is a wall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Warp {
+    pub offset: Vec2,
+    /// Radians anticlockwise.
+    #[serde(default)]
+    pub angle: f32,
+    /// Added to heights on the way through, for portals between floors.
+    #[serde(default)]
+    pub height: f32,
+}
+
+impl Warp {
+    pub fn apply(&self, pos: Vec2) -> Vec2 {
+        self.rotate(pos) + self.offset
+    }
+
+    /// `apply` for a point with a height in z.
+    pub fn apply_3d(&self, pos: Vec3) -> Vec3 {
+        self.apply(pos.xy()).extend(pos.z + self.height)
+    }
+
+    /// Turns a direction the way the warp does.
+    pub fn rotate(&self, direction: Vec2) -> Vec2 {
+        Vec2::from_angle(self.angle).rotate(direction)
+    }
+
+    /// The warp of the portal back.
+    pub fn inverse(&self) -> Self {
+        Self {
+            offset: -Vec2::from_angle(-self.angle).rotate(self.offset),
+            angle: -self.angle,
+            height: -self.height,
+        }
+    }
+
+    /// `apply` as a matrix, to put in front of camera transforms.
+    pub fn matrix(&self) -> Mat3 {
+        Mat3::from_translation(self.offset) * Mat3::from_angle(self.angle)
+    }
+}
+
+/// Something hung in a portal line that the far side shows through, drawn
+/// over it in the gap between the two sectors' floors and ceilings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mask {
+    pub color: Vec3,
+    /// How much the solid parts hide what's behind them, from 0 to 1.
+    pub opacity: f32,
+    pub pattern: MaskPattern,
+    /// Stops the player and monsters going through, like a fence or window
+    /// would. Shots and sight still pass.
+    #[serde(default)]
+    pub blocking: bool,
+}
+
+/// Which parts of a mask are solid, sizes are in world units.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MaskPattern {
+    /// Solid all over, a window.
+    Pane,
+    /// Upright bars `width` wide every `spacing`, a fence.
+    Bars { spacing: f32, width: f32 },
+    /// Bars both ways, a grate.
+    Grid { spacing: f32, width: f32 },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Wall {
     Solid(Vec3),
     Portal(SectorId),
+    /// Shows the sector reflected about the line, otherwise a wall.
+    Mirror,
 }
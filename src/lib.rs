@@ -1,8 +1,33 @@
 mod renderer;
-mod game;
-mod input;
-mod primitives;
-mod level;
+pub mod canvas;
+pub mod console;
+pub mod decompose;
+pub mod editor;
+pub mod font;
+pub mod format;
+pub mod scene;
+pub mod game;
+pub mod generate;
+pub mod geometry;
+pub mod hud;
+pub mod input;
+pub mod primitives;
+pub mod reload;
+pub mod script;
+pub mod sky;
+pub mod level;
+pub mod monster;
+pub mod mover;
+pub mod nav;
+pub mod player;
+pub mod spatial;
+pub mod stats;
+pub mod texture;
+pub mod validate;
+pub mod wad;
+pub mod weapon;
+
+use std::{path::Path, time::Instant};
 
 use winit::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
@@ -10,6 +35,15 @@ use winit::{
     window::WindowBuilder,
 };
 
+/// Console script run at startup when it exists.
+const AUTOEXEC: &str = "autoexec.cfg";
+
+/// Panorama drawn above sky sectors, the built-in one is used without it.
+const SKY: &str = "assets/sky.png";
+
+/// Textures beyond the built-in ones, with their images next to it.
+const TEXTURES: &str = "assets/textures.json";
+
 pub async fn run() {
     env_logger::init();
 
@@ -17,48 +51,140 @@ pub async fn run() {
     let window = WindowBuilder::new().build(&event_loop).unwrap();
 
     let mut render_state = renderer::State::new(&window).await;
+    match hud::Hud::load("assets/hud.json") {
+        Ok(hud) => render_state.set_hud(hud),
+        Err(e) => log::warn!("could not load assets/hud.json, using the default HUD: {e}"),
+    }
+    if Path::new(SKY).exists() {
+        match sky::Sky::load(SKY) {
+            Ok(sky) => render_state.set_sky(sky),
+            Err(e) => log::warn!("could not load {SKY}, using the built-in sky: {e}"),
+        }
+    }
+    if Path::new(TEXTURES).exists() {
+        match texture::Textures::load(TEXTURES) {
+            Ok(textures) => render_state.set_textures(textures),
+            Err(e) => log::warn!("could not load {TEXTURES}, using the built-in textures: {e}"),
+        }
+    }
     let mut game_state = game::GameState::default();
+    match monster::MonsterDefs::load("assets/monsters.json") {
+        Ok(defs) => game_state.monsters_mut().set_defs(defs),
+        Err(e) => log::warn!("could not load assets/monsters.json, using the built-in monsters: {e}"),
+    }
+    match weapon::Weapons::load("assets/weapons.json") {
+        Ok(weapons) => game_state.set_weapons(weapons),
+        Err(e) => log::warn!("could not load assets/weapons.json, using the built-in weapons: {e}"),
+    }
+    let mut frame_stats = stats::FrameStats::default();
+    let mut console = console::Console::default();
+    let mut editor = editor::Editor::new(window.inner_size());
+    let mut watcher = reload::LevelWatcher::default();
+
+    if Path::new(AUTOEXEC).exists() {
+        if let Err(e) = console.exec(AUTOEXEC, &mut game_state, &mut [&mut render_state, &mut editor, &mut watcher]) {
+            log::error!("could not run {AUTOEXEC}: {e}");
+        }
+    }
 
-    let mut last_input = std::time::Instant::now();
+    let mut last_input = Instant::now();
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == window.id() => {
-            if !game_state.input(event) && !render_state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => {
-                        render_state.resize(*physical_size);
-                    }
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        // new_inner_size is &&mut so we have to dereference it twice
-                        render_state.resize(**new_inner_size);
-                    }
-                    _ => {}
+        } if window_id == window.id()
+            && !console.input(event)
+            && !editor.input(event, &mut game_state, render_state.showing_map())
+            && !frame_stats.input(event)
+            && !game_state.input(event)
+            && !render_state.input(event) =>
+        {
+            match event {
+                WindowEvent::CloseRequested
+                | WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(physical_size) => {
+                    render_state.resize(*physical_size);
+                    editor.resize(*physical_size);
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    // new_inner_size is &&mut so we have to dereference it twice
+                    render_state.resize(**new_inner_size);
+                    editor.resize(**new_inner_size);
                 }
+                _ => {}
             }
         }
         Event::RedrawRequested(window_id) if window_id == window.id() => {
-            let start = std::time::Instant::now();
-            
+            let mut sample = stats::FrameSample::default();
+            let start = Instant::now();
+
+            if console.is_open() || editor.is_active() {
+                game_state.release_input();
+            }
+            console.run_pending(&mut game_state, &mut [&mut render_state, &mut editor, &mut watcher]);
+
+            // The editor's changes would be lost, leave the file alone until
+            // it's closed
+            if !editor.is_active() {
+                match watcher.poll(&mut game_state) {
+                    Some(reload::Reload::Loaded(report)) => console.print(&report),
+                    Some(reload::Reload::Failed(report)) => {
+                        console.print(&report);
+                        console.open();
+                    }
+                    None => {}
+                }
+            }
+
             let delta = last_input.elapsed().as_secs_f32();
-            last_input = std::time::Instant::now();
+            last_input = Instant::now();
             game_state.update(delta);
-            
-            render_state.update(&game_state);
+            for event in game_state.take_events() {
+                match event {
+                    game::GameEvent::Exit(Some(next)) => {
+                        let command = format!("map {next}");
+                        match console.execute(&command, &mut game_state, &mut [&mut render_state, &mut editor, &mut watcher]) {
+                            Ok(report) => console.print(&report),
+                            Err(e) => {
+                                console.print(&format!("error: {e}"));
+                                console.open();
+                            }
+                        }
+                    }
+                    game::GameEvent::Exit(None) => console.print("level complete"),
+                    game::GameEvent::Secret => console.print(&format!(
+                        "found a secret, {} so far",
+                        game_state.secrets_found()
+                    )),
+                    game::GameEvent::ScriptError(e) => {
+                        console.print(&format!("script error: {e}"));
+                        console.open();
+                    }
+                }
+            }
+            sample.sim = elapsed_ms(start);
+
+            let start = Instant::now();
+            let scene_stats = render_state.update(&game_state, &frame_stats, &console, &editor);
+            sample.render = elapsed_ms(start);
+            sample.sectors_visited = scene_stats.sectors_visited;
+            sample.columns_drawn = scene_stats.columns_drawn;
+
             match render_state.render() {
-                Ok(_) => {}
+                Ok((upload, present)) => {
+                    sample.upload = upload;
+                    sample.present = present;
+                    frame_stats.push(sample);
+                }
                 // Reconfigure the surface if lost
                 Err(wgpu::SurfaceError::Lost) => render_state.resize(window.inner_size()),
                 // The system is out of memory, we should probably quit
@@ -66,10 +192,6 @@ pub async fn run() {
                 // All other errors (Outdated, Timeout) should be resolved by the next frame
                 Err(e) => eprintln!("{:?}", e),
             }
-            
-            
-            let elapsed_ms = start.elapsed().as_millis();
-            println!("{elapsed_ms}ms");
         }
         Event::MainEventsCleared => {
             // RedrawRequested will only trigger once, unless we manually
@@ -79,3 +201,7 @@ pub async fn run() {
         _ => {}
     });
 }
+
+fn elapsed_ms(start: Instant) -> f32 {
+    start.elapsed().as_secs_f32() * 1000.0
+}
@@ -1,6 +1,84 @@
+use std::{env, process};
+
 use pollster::FutureExt;
-use raycaster::run;
+use raycaster::{
+    generate::{self, Settings},
+    level::LevelState,
+    run,
+    validate::{validate, Severity},
+};
+
+const USAGE: &str = "\
+usage: raycaster
+       raycaster generate <seed> <file> [rooms]
+       raycaster convert <from> <to>";
 
 fn main() {
-    run().block_on();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => run().block_on(),
+        Some("generate") => {
+            if let Err(e) = generate_level(&args[1..]) {
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Some("convert") => {
+            if let Err(e) = convert_level(&args[1..]) {
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Some(_) => {
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    }
+}
+
+/// Writes a generated level to a file that `map` in the console loads.
+fn generate_level(args: &[String]) -> Result<(), String> {
+    let (seed, path, rooms) = match args {
+        [seed, path] => (seed, path, None),
+        [seed, path, rooms] => (seed, path, Some(rooms)),
+        _ => return Err(USAGE.to_string()),
+    };
+    let seed = seed
+        .parse()
+        .map_err(|_| format!("expected a seed, got \"{seed}\""))?;
+    let mut settings = Settings::default();
+    if let Some(rooms) = rooms {
+        settings.rooms = rooms
+            .parse()
+            .map_err(|_| format!("expected a number of rooms, got \"{rooms}\""))?;
+    }
+
+    let level = generate::generate(seed, &settings)?;
+    let issues = validate(&level);
+    for issue in &issues {
+        eprintln!("{issue}");
+    }
+    if issues.iter().any(|issue| issue.severity == Severity::Error) {
+        return Err("the generated level is invalid".to_string());
+    }
+
+    level
+        ._save(path)
+        .map_err(|e| format!("could not write {path}: {e}"))?;
+    println!("wrote {path}, {} sectors", level.sectors().len());
+    Ok(())
+}
+
+/// Rewrites a level in the encoding its new extension asks for, upgrading it
+/// to the current version on the way.
+fn convert_level(args: &[String]) -> Result<(), String> {
+    let [from, to] = args else {
+        return Err(USAGE.to_string());
+    };
+    let level = LevelState::_load(from).map_err(|e| format!("could not load {from}: {e}"))?;
+    level
+        ._save(to)
+        .map_err(|e| format!("could not write {to}: {e}"))?;
+    println!("wrote {to}, {} sectors", level.sectors().len());
+    Ok(())
 }
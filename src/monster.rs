@@ -0,0 +1,510 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::MAX_STEP,
+    level::{LevelState, SectorId},
+    nav::NavGraph,
+    scene::Sprite,
+};
+
+/// Definitions used when `assets/monsters.json` can't be loaded.
+const BUILT_IN: &str = include_str!("../assets/monsters.json");
+
+/// Seconds between path searches while chasing.
+const REPATH_INTERVAL: f32 = 0.5;
+
+/// Height of a dead monster's sprite compared to a living one.
+const CORPSE_HEIGHT: f32 = 0.15;
+
+/// How far apart the player's and a chasing monster's edges stay.
+const PERSONAL_SPACE: f32 = 0.3;
+
+/// Every kind of monster by name, normally read from a JSON file so
+/// designers can change behaviour without a rebuild.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MonsterDefs {
+    kinds: HashMap<String, MonsterDef>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MonsterDef {
+    pub health: i32,
+    /// Units per second.
+    pub speed: f32,
+    pub radius: f32,
+    pub height: f32,
+    pub color: Vec3,
+    /// Furthest the monster can see the player from.
+    pub sight_range: f32,
+    /// Degrees either side of straight ahead added together.
+    pub field_of_view: f32,
+    /// State a new monster starts in.
+    pub start: String,
+    pub states: HashMap<String, StateDef>,
+}
+
+/// What a monster does in a state and when it leaves it. Transitions are
+/// checked in order every tick and the first whose conditions all hold is
+/// taken.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateDef {
+    pub action: Action,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Transition {
+    pub when: Vec<Condition>,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Condition {
+    /// The player is in range, in front and not behind a wall.
+    SeesPlayer,
+    /// The player hasn't been seen for this many seconds.
+    LostPlayer(f32),
+    PlayerWithin(f32),
+    PlayerBeyond(f32),
+    /// Seconds since the state was entered.
+    After(f32),
+    /// Took damage since the state was entered.
+    Hurt,
+    /// A line special called them since the state was entered.
+    Alerted,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Action {
+    /// Stand still.
+    Idle,
+    /// Follow the sector graph to where the player was last seen.
+    Chase,
+    /// Face the player and hit them every `cooldown` seconds while they're
+    /// in sight and within `range`, a short range makes it a melee attack.
+    Attack {
+        damage: i32,
+        range: f32,
+        cooldown: f32,
+    },
+}
+
+impl Default for MonsterDefs {
+    fn default() -> Self {
+        Self::parse(BUILT_IN).expect("the built-in monsters are valid")
+    }
+}
+
+impl MonsterDefs {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Reads definitions and checks every state they name exists.
+    pub fn parse(json: &str) -> io::Result<Self> {
+        let defs: Self = serde_json::from_str(json)?;
+        for (name, def) in &defs.kinds {
+            let missing = |state: &String| !def.states.contains_key(state);
+            if missing(&def.start) {
+                return Err(invalid(&format!(
+                    "{name} starts in state \"{}\", which doesn't exist",
+                    def.start
+                )));
+            }
+            for (state_name, state) in &def.states {
+                if let Some(transition) = state.transitions.iter().find(|t| missing(&t.to)) {
+                    return Err(invalid(&format!(
+                        "{name} goes from \"{state_name}\" to \"{}\", which doesn't exist",
+                        transition.to
+                    )));
+                }
+            }
+        }
+        Ok(defs)
+    }
+
+    pub fn get(&self, kind: &str) -> Option<&MonsterDef> {
+        self.kinds.get(kind)
+    }
+
+    /// Names of every kind, sorted.
+    pub fn kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<&str> = self.kinds.keys().map(String::as_str).collect();
+        kinds.sort();
+        kinds
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Monster {
+    kind: String,
+    pos: Vec2,
+    facing: Vec2,
+    health: i32,
+    state: String,
+    state_time: f32,
+    unseen_time: f32,
+    hurt: bool,
+    alerted: bool,
+    /// Line specials with this tag act on the monster.
+    tag: Option<u32>,
+    /// Seconds until the next attack can land.
+    cooldown: f32,
+    /// Where the player was last seen.
+    target: Option<Vec2>,
+    path: Vec<Vec2>,
+    repath: f32,
+}
+
+impl Monster {
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn pos(&self) -> Vec2 {
+        self.pos
+    }
+
+    pub fn health(&self) -> i32 {
+        self.health
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+
+    pub fn hurt(&mut self, amount: i32) {
+        self.health -= amount;
+        self.hurt = true;
+    }
+
+    pub fn tag(&self) -> Option<u32> {
+        self.tag
+    }
+
+    pub fn set_tag(&mut self, tag: Option<u32>) {
+        self.tag = tag;
+    }
+
+    fn enter(&mut self, state: &str) {
+        self.state = state.to_string();
+        self.state_time = 0.0;
+        self.hurt = false;
+        self.alerted = false;
+    }
+}
+
+/// The monster definitions and every monster in the level.
+#[derive(Default)]
+pub struct Monsters {
+    defs: MonsterDefs,
+    monsters: Vec<Monster>,
+}
+
+impl Monsters {
+    pub fn defs(&self) -> &MonsterDefs {
+        &self.defs
+    }
+
+    /// Swaps in new definitions, monsters whose kind is gone stop moving.
+    pub fn set_defs(&mut self, defs: MonsterDefs) {
+        self.defs = defs;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Monster> {
+        self.monsters.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Monster> {
+        self.monsters.iter_mut()
+    }
+
+    pub fn clear(&mut self) {
+        self.monsters.clear();
+    }
+
+    pub fn spawn(&mut self, kind: &str, pos: Vec2, facing: Vec2) -> Result<&mut Monster, String> {
+        let def = self
+            .defs
+            .get(kind)
+            .ok_or_else(|| format!("no monster called \"{kind}\""))?;
+        self.monsters.push(Monster {
+            kind: kind.to_string(),
+            pos,
+            facing: facing.normalize_or_zero(),
+            health: def.health,
+            state: def.start.clone(),
+            state_time: 0.0,
+            unseen_time: f32::INFINITY,
+            hurt: false,
+            alerted: false,
+            tag: None,
+            cooldown: 0.0,
+            target: None,
+            path: vec![],
+            repath: 0.0,
+        });
+        Ok(self.monsters.last_mut().expect("a monster was just added"))
+    }
+
+    /// Sends the living monsters tagged `tag` after the player, returning
+    /// how many there were.
+    pub fn alert(&mut self, tag: u32) -> usize {
+        let mut alerted = 0;
+        for monster in &mut self.monsters {
+            if !monster.is_dead() && monster.tag == Some(tag) {
+                monster.alerted = true;
+                alerted += 1;
+            }
+        }
+        alerted
+    }
+
+    /// Runs every living monster's state machine for `delta` seconds against
+    /// a player at `player` (z is eye height), returning the damage they
+    /// dealt.
+    pub fn update(&mut self, delta: f32, level: &LevelState, nav: &NavGraph, player: Vec3) -> i32 {
+        let mut damage = 0;
+
+        for monster in &mut self.monsters {
+            let Some(def) = self.defs.get(&monster.kind) else {
+                continue;
+            };
+            if monster.is_dead() || !def.states.contains_key(&monster.state) {
+                continue;
+            }
+
+            let to_player = player.truncate() - monster.pos;
+            let distance = to_player.length();
+            let eye = monster
+                .pos
+                .extend(floor_height(level, monster.pos) + def.height * 0.8);
+            let in_view = monster.facing == Vec2::ZERO
+                || monster.facing.angle_between(to_player).abs()
+                    <= def.field_of_view.to_radians() / 2.0;
+            let sees = distance <= def.sight_range && in_view && level.trace(eye, player).is_none();
+
+            // Getting hurt or called gives the player away
+            if sees || monster.hurt || monster.alerted {
+                monster.target = Some(player.truncate());
+                monster.unseen_time = 0.0;
+            } else {
+                monster.unseen_time += delta;
+            }
+            monster.state_time += delta;
+            monster.cooldown -= delta;
+
+            let holds = |condition: &Condition| match *condition {
+                Condition::SeesPlayer => sees,
+                Condition::LostPlayer(seconds) => monster.unseen_time >= seconds,
+                Condition::PlayerWithin(range) => distance <= range,
+                Condition::PlayerBeyond(range) => distance > range,
+                Condition::After(seconds) => monster.state_time >= seconds,
+                Condition::Hurt => monster.hurt,
+                Condition::Alerted => monster.alerted,
+            };
+            let next = def.states[&monster.state]
+                .transitions
+                .iter()
+                .find(|transition| transition.when.iter().all(holds))
+                .map(|transition| transition.to.clone());
+            if let Some(next) = next {
+                monster.enter(&next);
+                monster.path.clear();
+            }
+
+            match def.states[&monster.state].action {
+                Action::Idle => {}
+                Action::Chase => {
+                    if distance > def.radius + PERSONAL_SPACE {
+                        chase(monster, def, delta, level, nav);
+                    }
+                }
+                Action::Attack {
+                    damage: amount,
+                    range,
+                    cooldown,
+                } => {
+                    if sees {
+                        monster.facing = to_player.normalize_or_zero();
+                        if monster.cooldown <= 0.0 && distance <= range {
+                            damage += amount;
+                            monster.cooldown = cooldown;
+                        }
+                    }
+                }
+            }
+        }
+
+        damage
+    }
+
+    /// Billboards for every monster, the dead ones lying flat.
+    pub fn sprites(&self, level: &LevelState) -> Vec<Sprite> {
+        self.monsters
+            .iter()
+            .filter_map(|monster| {
+                let def = self.defs.get(&monster.kind)?;
+                let (height, color) = if monster.is_dead() {
+                    (def.height * CORPSE_HEIGHT, def.color * 0.5)
+                } else {
+                    (def.height, def.color)
+                };
+                Some(Sprite {
+                    pos: monster.pos,
+                    base_height: floor_height(level, monster.pos),
+                    width: def.radius * 2.0,
+                    height,
+                    color,
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Monster> {
+        self.monsters.get_mut(index)
+    }
+
+    /// Heights of the living monsters standing in `sector_id`.
+    pub fn heights_in<'a>(
+        &'a self,
+        level: &'a LevelState,
+        sector_id: SectorId,
+    ) -> impl Iterator<Item = f32> + 'a {
+        self.monsters
+            .iter()
+            .filter(move |monster| {
+                !monster.is_dead() && level.find_current_sector(monster.pos) == Some(sector_id)
+            })
+            .filter_map(|monster| self.defs.get(&monster.kind).map(|def| def.height))
+    }
+
+    /// Takes `damage` off every living monster in `sector_id` taller than
+    /// `clearance`. Being squashed doesn't tell them where the player is.
+    pub fn crush(&mut self, level: &LevelState, sector_id: SectorId, clearance: f32, damage: i32) {
+        for monster in &mut self.monsters {
+            let Some(def) = self.defs.get(&monster.kind) else {
+                continue;
+            };
+            if !monster.is_dead()
+                && def.height > clearance
+                && level.find_current_sector(monster.pos) == Some(sector_id)
+            {
+                monster.health -= damage;
+            }
+        }
+    }
+
+    /// The first living monster the line from `from` to `to` passes through,
+    /// with how far along the line, from 0 to 1, it's hit. `padding` widens
+    /// every monster, for shots that aren't points.
+    pub fn first_hit(
+        &self,
+        level: &LevelState,
+        from: Vec3,
+        to: Vec3,
+        padding: f32,
+    ) -> Option<(usize, f32)> {
+        let direction = (to - from).truncate();
+        self.monsters
+            .iter()
+            .enumerate()
+            .filter(|(_, monster)| !monster.is_dead())
+            .filter_map(|(index, monster)| {
+                let def = self.defs.get(&monster.kind)?;
+                let radius = def.radius + padding;
+                // Where the line enters the monster's circle
+                let offset = from.truncate() - monster.pos;
+                let a = direction.length_squared();
+                let b = 2.0 * offset.dot(direction);
+                let c = offset.length_squared() - radius * radius;
+                let fraction = if c <= 0.0 {
+                    0.0
+                } else {
+                    let discriminant = b * b - 4.0 * a * c;
+                    if a == 0.0 || discriminant < 0.0 {
+                        return None;
+                    }
+                    (-b - discriminant.sqrt()) / (2.0 * a)
+                };
+                if !(0.0..=1.0).contains(&fraction) {
+                    return None;
+                }
+
+                let height = from.z + (to.z - from.z) * fraction;
+                let base = floor_height(level, monster.pos);
+                (base - padding..=base + def.height + padding)
+                    .contains(&height)
+                    .then_some((index, fraction))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Walks towards where the player was last seen, finding a new path every
+/// `REPATH_INTERVAL`. Walls and blocking masks stop a monster the way they
+/// stop the player, and so do warps, which the navigation graph has no way
+/// across.
+fn chase(monster: &mut Monster, def: &MonsterDef, delta: f32, level: &LevelState, nav: &NavGraph) {
+    let Some(target) = monster.target else {
+        return;
+    };
+    monster.repath -= delta;
+    if monster.repath <= 0.0 || monster.path.is_empty() {
+        monster.path = nav
+            .find_path(level, monster.pos, target)
+            .unwrap_or_default();
+        monster.repath = REPATH_INTERVAL;
+    }
+
+    let Some(waypoint) = monster.path.first().copied() else {
+        return;
+    };
+    let to = waypoint - monster.pos;
+    let distance = delta * def.speed;
+    let next = if to.length() <= distance {
+        monster.path.remove(0);
+        waypoint
+    } else {
+        monster.pos + to.normalize() * distance
+    };
+    monster.facing = to.normalize_or_zero();
+
+    let step = floor_height(level, next) - floor_height(level, monster.pos);
+    // Monsters already under a low ceiling can still crawl out
+    let fits = level.find_current_sector(next).is_some_and(|sector_id| {
+        level.find_current_sector(monster.pos) == Some(sector_id)
+            || level.sector(sector_id).height() >= def.height
+    });
+    let crosses =
+        level.blocked(monster.pos, next) || level.crossed_warp(monster.pos, next).is_some();
+    if fits && !crosses && step <= MAX_STEP {
+        monster.pos = next;
+    } else {
+        monster.path.clear();
+    }
+}
+
+fn floor_height(level: &LevelState, pos: Vec2) -> f32 {
+    level
+        .find_current_sector(pos)
+        .map_or(0.0, |sector_id| level.sector(sector_id).base_height())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+
+use crate::level::{LevelState, SectorId};
+
+/// Seconds between the hits a crusher deals to whatever it's squashing.
+const CRUSH_INTERVAL: f32 = 0.5;
+
+/// A sector whose floor or ceiling moves when something sets it off, saved
+/// with the level.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoverDef {
+    /// Sector whose floor or ceiling moves.
+    pub sector: SectorId,
+    pub kind: MoverKind,
+    pub trigger: Trigger,
+    /// Units per second.
+    pub speed: f32,
+    /// Seconds spent at the far end before going back, `None` stays there
+    /// until triggered again. Crushers turn straight round.
+    pub wait: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MoverKind {
+    /// The ceiling starts on the floor and rises to `open` above it, backing
+    /// off again if it would close on anyone.
+    Door { open: f32 },
+    /// The floor goes from `top` down to `bottom`, the ceiling stays put.
+    Lift { top: f32, bottom: f32 },
+    /// The ceiling goes between `top` and `bottom` above the floor for as
+    /// long as it's running, hurting anything it closes on.
+    Crusher { top: f32, bottom: f32, damage: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// Pressing use at a line of the sector, or at a portal into it.
+    Use,
+    /// Walking into the given sector.
+    Enter(SectorId),
+}
+
+/// Where a mover is in its cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    Resting,
+    Leaving,
+    /// At the far end, with the seconds left before going back.
+    Waiting(f32),
+    Returning,
+}
+
+/// A mover in play, `position` goes from 0 where the level starts it to 1 at
+/// the far end.
+#[derive(Debug, Clone)]
+pub struct Mover {
+    def: MoverDef,
+    position: f32,
+    motion: Motion,
+    /// Crushers only move while this is on, triggering them flips it.
+    running: bool,
+    since_crush: f32,
+}
+
+impl Mover {
+    /// A mover at rest, putting its sector where it starts.
+    pub fn new(def: MoverDef, level: &mut LevelState) -> Self {
+        let mover = Self {
+            def,
+            position: 0.0,
+            motion: Motion::Resting,
+            running: false,
+            since_crush: CRUSH_INTERVAL,
+        };
+        mover.apply(level);
+        mover
+    }
+
+    pub fn def(&self) -> &MoverDef {
+        &self.def
+    }
+
+    pub fn motion(&self) -> Motion {
+        self.motion
+    }
+
+    pub fn is_moving(&self) -> bool {
+        matches!(self.motion, Motion::Leaving | Motion::Returning)
+    }
+
+    /// Starts the mover off. A door that's waiting or closing opens again,
+    /// one that stays open closes, and a crusher starts or stops.
+    pub fn activate(&mut self) {
+        if let MoverKind::Crusher { .. } = self.def.kind {
+            self.running = !self.running;
+            if self.running && self.motion == Motion::Resting {
+                self.motion = Motion::Leaving;
+            }
+            return;
+        }
+
+        self.motion = match self.motion {
+            Motion::Resting | Motion::Returning => Motion::Leaving,
+            Motion::Waiting(_) if self.def.wait.is_none() => Motion::Returning,
+            motion => motion,
+        };
+    }
+
+    /// Moves the sector on by `delta` seconds. `headroom` is the height of
+    /// the tallest thing in the sector, which doors and lifts won't close
+    /// on. Returns the damage a crusher deals to everything taller than the
+    /// gap it leaves.
+    pub fn update(&mut self, delta: f32, level: &mut LevelState, headroom: f32) -> Option<i32> {
+        self.since_crush += delta;
+        let (start, end) = self.def.kind.range();
+        let step = self.def.speed * delta / (end - start).abs().max(f32::EPSILON);
+        let (position, motion) = (self.position, self.motion);
+
+        match self.motion {
+            Motion::Resting => return None,
+            Motion::Leaving => {
+                self.position = (self.position + step).min(1.0);
+                if self.position == 1.0 {
+                    self.motion = match self.def.kind {
+                        MoverKind::Crusher { .. } => Motion::Returning,
+                        _ => Motion::Waiting(self.def.wait.unwrap_or(f32::INFINITY)),
+                    };
+                }
+            }
+            Motion::Waiting(left) => {
+                self.motion = if left > delta {
+                    Motion::Waiting(left - delta)
+                } else {
+                    Motion::Returning
+                };
+                return None;
+            }
+            Motion::Returning => {
+                self.position = (self.position - step).max(0.0);
+                if self.position == 0.0 {
+                    self.motion = if self.running {
+                        Motion::Leaving
+                    } else {
+                        Motion::Resting
+                    };
+                }
+            }
+        }
+
+        let before = self.clearance(level);
+        self.apply(level);
+        let clearance = self.clearance(level);
+        if clearance >= headroom || clearance >= before {
+            return None;
+        }
+
+        match self.def.kind {
+            MoverKind::Crusher { damage, .. } => {
+                if self.since_crush < CRUSH_INTERVAL {
+                    return None;
+                }
+                self.since_crush = 0.0;
+                Some(damage)
+            }
+            // Back off the way it came, as if it had hit whoever's in the way
+            _ => {
+                self.position = position;
+                self.motion = match motion {
+                    Motion::Returning => Motion::Leaving,
+                    _ => Motion::Returning,
+                };
+                self.apply(level);
+                None
+            }
+        }
+    }
+
+    /// Space between the sector's floor and ceiling, zero when it's gone.
+    fn clearance(&self, level: &LevelState) -> f32 {
+        level
+            .sectors()
+            .get(self.def.sector)
+            .map_or(0.0, |sector| sector.height())
+    }
+
+    fn apply(&self, level: &mut LevelState) {
+        let Some(sector) = level.sectors().get(self.def.sector) else {
+            return;
+        };
+        let (start, end) = self.def.kind.range();
+        let value = start + (end - start) * self.position;
+        let (base_height, height) = match self.def.kind {
+            MoverKind::Door { .. } | MoverKind::Crusher { .. } => (sector.base_height(), value),
+            MoverKind::Lift { .. } => {
+                let ceiling = sector.base_height() + sector.height();
+                (value, ceiling - value)
+            }
+        };
+        level.set_heights(self.def.sector, base_height, height);
+    }
+}
+
+impl MoverKind {
+    /// The height that moves, at rest and at the far end. Ceilings are
+    /// measured from the floor, floors from zero.
+    fn range(&self) -> (f32, f32) {
+        match *self {
+            MoverKind::Door { open } => (0.0, open),
+            MoverKind::Lift { top, bottom } => (top, bottom),
+            MoverKind::Crusher { top, bottom, .. } => (top, bottom),
+        }
+    }
+
+    /// Lifts change which steps can be climbed, so paths need rebuilding
+    /// once they stop.
+    pub fn moves_floor(&self) -> bool {
+        matches!(self, MoverKind::Lift { .. })
+    }
+}
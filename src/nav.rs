@@ -0,0 +1,215 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use glam::Vec2;
+
+use crate::level::{LevelState, SectorId, Wall};
+
+/// Paths keep at least this far from the ends of a portal, or a quarter of
+/// its width when it is narrow, so they don't graze wall corners.
+const PORTAL_MARGIN: f32 = 0.25;
+
+/// A way from one sector into another through a portal.
+#[derive(Debug, Clone, Copy)]
+pub struct NavEdge {
+    pub target: SectorId,
+    /// Ends of the portal as seen walking through it, narrowed by
+    /// `PORTAL_MARGIN`.
+    pub left: Vec2,
+    pub right: Vec2,
+}
+
+impl NavEdge {
+    pub fn midpoint(&self) -> Vec2 {
+        (self.left + self.right) / 2.0
+    }
+}
+
+/// The sectors of a level and the portals between them that can be walked
+/// through, skipping portals up to floors higher than the step limit.
+#[derive(Debug, Clone, Default)]
+pub struct NavGraph {
+    edges: Vec<Vec<NavEdge>>,
+}
+
+impl NavGraph {
+    pub fn new(level: &LevelState, max_step: f32) -> Self {
+        let sectors = level.sectors();
+        let edges = sectors
+            .iter()
+            .map(|sector| {
+                let centroid = sector.centroid();
+                (0..sector.lines().len())
+                    .filter_map(|line_index| {
+                        let line = &sector.lines()[line_index];
+                        let Wall::Portal(target) = line.wall_type() else {
+                            return None;
+                        };
+                        // Paths are straight lines, which don't go through
+                        // warps
+                        if line.warp().is_some() || line.mask().is_some_and(|mask| mask.blocking) {
+                            return None;
+                        }
+                        let target_sector = sectors.get(target)?;
+                        if target_sector.base_height() - sector.base_height() > max_step {
+                            return None;
+                        }
+
+                        let (start, end) = sector.line_points(line_index);
+                        let margin = PORTAL_MARGIN.min(start.distance(end) / 4.0);
+                        let along = (end - start).normalize_or_zero() * margin;
+                        let (start, end) = (start + along, end - along);
+                        let (left, right) =
+                            if (start + end - 2.0 * centroid).perp_dot(start - centroid) > 0.0 {
+                                (start, end)
+                            } else {
+                                (end, start)
+                            };
+                        Some(NavEdge {
+                            target,
+                            left,
+                            right,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { edges }
+    }
+
+    /// The ways out of `sector_id`.
+    pub fn neighbours(&self, sector_id: SectorId) -> &[NavEdge] {
+        self.edges.get(sector_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Sectors and portals from `from`'s sector to `to`'s, found with A*
+    /// using each portal's midpoint as the place the path crosses it.
+    pub fn find_route(&self, level: &LevelState, from: Vec2, to: Vec2) -> Option<Vec<NavEdge>> {
+        let start = level.find_current_sector(from)?;
+        let goal = level.find_current_sector(to)?;
+
+        let mut cost = vec![f32::INFINITY; self.edges.len()];
+        let mut entry = vec![from; self.edges.len()];
+        let mut came_from: Vec<Option<(SectorId, NavEdge)>> = vec![None; self.edges.len()];
+        let mut open = BinaryHeap::from([Open {
+            estimate: from.distance(to),
+            sector_id: start,
+        }]);
+        cost[start] = 0.0;
+
+        while let Some(Open { sector_id, .. }) = open.pop() {
+            if sector_id == goal {
+                let mut route = vec![];
+                let mut current = goal;
+                while let Some((previous, edge)) = came_from[current] {
+                    route.push(edge);
+                    current = previous;
+                }
+                route.reverse();
+                return Some(route);
+            }
+
+            for edge in self.neighbours(sector_id) {
+                let midpoint = edge.midpoint();
+                let new_cost = cost[sector_id] + entry[sector_id].distance(midpoint);
+                if new_cost < cost[edge.target] {
+                    cost[edge.target] = new_cost;
+                    entry[edge.target] = midpoint;
+                    came_from[edge.target] = Some((sector_id, *edge));
+                    open.push(Open {
+                        estimate: new_cost + midpoint.distance(to),
+                        sector_id: edge.target,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Waypoints from `from` to `to`, ending at `to`. Corners are only taken
+    /// where a portal edge is in the way, found by pulling the route tight
+    /// through its portals (the simple stupid funnel algorithm).
+    pub fn find_path(&self, level: &LevelState, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let route = self.find_route(level, from, to)?;
+        Some(string_pull(from, to, &route))
+    }
+}
+
+/// An entry in the A* open set, ordered so the heap pops the lowest
+/// estimate first.
+struct Open {
+    estimate: f32,
+    sector_id: SectorId,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+fn string_pull(from: Vec2, to: Vec2, route: &[NavEdge]) -> Vec<Vec2> {
+    let mut portals = vec![(from, from)];
+    portals.extend(route.iter().map(|edge| (edge.left, edge.right)));
+    portals.push((to, to));
+
+    let mut path = vec![];
+    let (mut apex, mut left, mut right) = (from, from, from);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut index = 1;
+    while index < portals.len() {
+        let (new_left, new_right) = portals[index];
+
+        // Narrow the funnel from the right, unless that crosses the left
+        // side, whose end becomes a corner of the path
+        if (right - apex).perp_dot(new_right - apex) >= 0.0 {
+            if apex == right || (left - apex).perp_dot(new_right - apex) < 0.0 {
+                right = new_right;
+                right_index = index;
+            } else {
+                path.push(left);
+                apex = left;
+                right = apex;
+                right_index = left_index;
+                index = left_index + 1;
+                continue;
+            }
+        }
+
+        if (left - apex).perp_dot(new_left - apex) <= 0.0 {
+            if apex == left || (right - apex).perp_dot(new_left - apex) > 0.0 {
+                left = new_left;
+                left_index = index;
+            } else {
+                path.push(right);
+                apex = right;
+                left = apex;
+                left_index = right_index;
+                index = right_index + 1;
+                continue;
+            }
+        }
+
+        index += 1;
+    }
+
+    if path.last() != Some(&to) {
+        path.push(to);
+    }
+    path
+}
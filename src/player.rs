@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Everything about the player that isn't where they are.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub health: i32,
+    pub max_health: i32,
+    pub armor: i32,
+    pub max_armor: i32,
+    pub ammo: i32,
+    pub max_ammo: i32,
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        Self {
+            health: 100,
+            max_health: 100,
+            armor: 0,
+            max_armor: 100,
+            ammo: 50,
+            max_ammo: 200,
+        }
+    }
+}
+
+impl PlayerStats {
+    /// Armor soaks up half of the damage while it lasts.
+    pub fn take_damage(&mut self, amount: i32) {
+        let absorbed = (amount / 2).min(self.armor);
+        self.armor -= absorbed;
+        self.health = (self.health - (amount - absorbed)).max(0);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+}
@@ -18,4 +18,19 @@ pub struct VerticalLineDescriptor {
     pub top_x: u32,
     pub bottom_x: u32,
     pub color: Vec4,
-}
\ No newline at end of file
+}
+
+pub struct RectDescriptor {
+    pub top_left: Vec2,
+    pub size: Vec2,
+    pub color: Vec3,
+}
+
+pub struct TextDescriptor<'a> {
+    pub text: &'a str,
+    pub top_left: Vec2,
+    pub color: Vec3,
+    /// Whole number of screen pixels per font pixel.
+    pub scale: u32,
+    pub alpha: f32,
+}
@@ -0,0 +1,149 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    console::{Cvar, CvarValue, Cvars},
+    game::GameState,
+    level::LevelState,
+    script::Scripts,
+    validate::{validate, Severity},
+};
+
+/// Longest `hot_reload_interval` in seconds, anything above is cut down to
+/// this.
+const MAX_INTERVAL: f32 = 3600.0;
+
+/// What happened when the watched level changed.
+pub enum Reload {
+    /// The new level is in, with any warnings about it.
+    Loaded(String),
+    /// The file couldn't be read or has errors, the old level is kept.
+    Failed(String),
+}
+
+/// Polls the file the current level was loaded from and swaps in the new
+/// version whenever it changes on disk.
+pub struct LevelWatcher {
+    enabled: bool,
+    interval: Duration,
+    last_poll: Instant,
+    /// The file being watched and when it was last changed, a new path is
+    /// only compared against from its second poll on.
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+impl Default for LevelWatcher {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_millis(500),
+            last_poll: Instant::now(),
+            path: None,
+            modified: None,
+        }
+    }
+}
+
+impl LevelWatcher {
+    /// Reloads the level if its file changed since the last poll. A level
+    /// with errors, or one that doesn't parse because it's half written, is
+    /// reported and skipped until the file changes again.
+    pub fn poll(&mut self, game_state: &mut GameState) -> Option<Reload> {
+        if !self.enabled || self.last_poll.elapsed() < self.interval {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let Some(path) = game_state.level_path() else {
+            self.path = None;
+            return None;
+        };
+        let modified = modified(path);
+        if self.path.as_deref() != Some(path) {
+            self.path = Some(path.to_path_buf());
+            self.modified = modified;
+            return None;
+        }
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        let name = path.display().to_string();
+        let level = match LevelState::_load(path) {
+            Ok(level) => level,
+            Err(e) => return Some(Reload::Failed(format!("could not reload {name}: {e}"))),
+        };
+
+        let issues = validate(&level);
+        let report = issues
+            .iter()
+            .map(|issue| format!("\n  {issue}"))
+            .collect::<String>();
+        if issues.iter().any(|issue| issue.severity == Severity::Error) {
+            return Some(Reload::Failed(format!(
+                "{name} has errors, keeping the old level:{report}"
+            )));
+        }
+
+        // A broken script shouldn't hold back the rest of the level
+        let scripts = Scripts::for_level(&level, Some(path));
+        let moved = match game_state.reload_level(level) {
+            Some(pos) => format!(", moved to {:.2} {:.2}", pos.x, pos.y),
+            None => String::new(),
+        };
+        match scripts {
+            Ok(scripts) => {
+                game_state.set_scripts(scripts);
+                Some(Reload::Loaded(format!("reloaded {name}{moved}{report}")))
+            }
+            Err(e) => {
+                game_state.set_scripts(None);
+                Some(Reload::Failed(format!(
+                    "reloaded {name}{moved} without its script, {e}{report}"
+                )))
+            }
+        }
+    }
+}
+
+impl Cvars for LevelWatcher {
+    fn cvars(&self) -> Vec<Cvar> {
+        vec![
+            Cvar {
+                name: "hot_reload",
+                description: "reload the level when its file changes",
+                value: CvarValue::Bool(self.enabled),
+            },
+            Cvar {
+                name: "hot_reload_interval",
+                description: "seconds between checks of the level file",
+                value: CvarValue::Float(self.interval.as_secs_f32()),
+            },
+        ]
+    }
+
+    fn set_cvar(&mut self, name: &str, value: CvarValue) -> bool {
+        match (name, value) {
+            ("hot_reload", CvarValue::Bool(value)) => self.enabled = value,
+            ("hot_reload_interval", CvarValue::Float(value)) => {
+                // NaN is left as it was
+                if let Ok(interval) = Duration::try_from_secs_f32(value.clamp(0.0, MAX_INTERVAL)) {
+                    self.interval = interval;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
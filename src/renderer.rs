@@ -1,15 +1,19 @@
 use crate::{
-    primitives::{CricleDescriptor, LineDescriptor, VerticalLineDescriptor}, level::{SectorId, Sector},
+    canvas::Canvas,
+    console::{Console, Cvar, CvarValue, Cvars},
+    editor::Editor,
+    font::Font,
+    hud::Hud,
+    primitives::{CricleDescriptor, LineDescriptor},
+    scene::{self, SceneSettings, SceneStats},
+    sky::Sky,
+    texture::Textures,
+    stats::FrameStats,
 };
 
-use std::{
-    cmp::{max, min},
-    f32::consts::PI,
-    num::NonZeroU32,
-    ops::{Neg},
-};
+use std::{num::NonZeroU32, time::Instant};
 
-use glam::{Mat3, Vec2, Vec3, Vec3Swizzles};
+use glam::{Vec3, Vec3Swizzles};
 use winit::{
     dpi::PhysicalSize,
     event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
@@ -24,8 +28,16 @@ pub struct State {
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: PhysicalSize<u32>,
-    canvas: Vec<u8>,
+    canvas: Canvas,
+    canvas_format: wgpu::TextureFormat,
+    canvas_layout: wgpu::BindGroupLayout,
+    canvas_texture: wgpu::Texture,
+    canvas_bind_group: wgpu::BindGroup,
+    canvas_pipeline: wgpu::RenderPipeline,
+    font: Font,
+    hud: Hud,
     render_map: bool,
+    scene_settings: SceneSettings,
 }
 
 impl State {
@@ -68,7 +80,7 @@ impl State {
             .unwrap_or(surface_caps.formats[0]);
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -79,32 +91,75 @@ impl State {
 
         surface.configure(&device, &config);
 
+        // The canvas is written as BGRA, keep the surface's colour space so
+        // the values end up on screen unchanged
+        let canvas_format = if surface_format.describe().srgb {
+            wgpu::TextureFormat::Bgra8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Bgra8Unorm
+        };
+
+        let canvas_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Canvas Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let canvas = Canvas::new(size.width, size.height);
+        let (canvas_texture, canvas_bind_group) =
+            create_canvas_texture(&device, &canvas_layout, canvas_format, &canvas);
+        let canvas_pipeline = create_canvas_pipeline(&device, &canvas_layout, config.format);
+
         Self {
             surface,
             device,
             queue,
             config,
             size,
-            canvas: vec![0; (size.width * size.height * 4) as usize],
+            canvas,
+            canvas_format,
+            canvas_layout,
+            canvas_texture,
+            canvas_bind_group,
+            canvas_pipeline,
+            font: Font::builtin(),
+            hud: Hud::default(),
             render_map: true,
+            scene_settings: SceneSettings::default(),
         }
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+    /// Returns the milliseconds spent uploading and drawing the canvas, and
+    /// those spent waiting for the surface and presenting it.
+    pub fn render(&mut self) -> Result<(f32, f32), wgpu::SurfaceError> {
+        // Acquiring the surface can wait for vsync, so it counts as presenting
+        let start = Instant::now();
         let output = self.surface.get_current_texture()?;
+        let acquire = start.elapsed().as_secs_f32() * 1000.0;
+
+        let start = Instant::now();
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        // Each column of the canvas becomes a row of the texture
         self.queue.write_texture(
-            output.texture.as_image_copy(),
-            &self.canvas,
+            self.canvas_texture.as_image_copy(),
+            self.canvas.pixels(),
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: NonZeroU32::new(4 * self.size.width),
-                rows_per_image: NonZeroU32::new(self.size.height),
+                bytes_per_row: NonZeroU32::new(4 * self.canvas.height()),
+                rows_per_image: NonZeroU32::new(self.canvas.width()),
             },
-            output.texture.size(),
+            self.canvas_texture.size(),
         );
 
         let mut encoder = self
@@ -114,66 +169,86 @@ impl State {
             });
 
         {
-            let mut _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: false,
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
                     },
                 })],
                 depth_stencil_attachment: None,
             });
+
+            render_pass.set_pipeline(&self.canvas_pipeline);
+            render_pass.set_bind_group(0, &self.canvas_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
         }
 
+        let upload = start.elapsed().as_secs_f32() * 1000.0;
+        let start = Instant::now();
+
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
 
-        Ok(())
+        Ok((upload, acquire + start.elapsed().as_secs_f32() * 1000.0))
     }
 
-    pub fn update(&mut self, game_state: &GameState) {
-        let size = self.size;
-        let pixels = (size.height * size.width) as usize;
-        let mut pixel_offset = 0;
+    pub fn update(
+        &mut self,
+        game_state: &GameState,
+        frame_stats: &FrameStats,
+        console: &Console,
+        editor: &Editor,
+    ) -> SceneStats {
+        self.canvas.clear();
+
+        let scene_stats = if editor.is_active() {
+            editor.draw(&mut self.canvas, &self.font, game_state);
+            SceneStats::default()
+        } else if !self.render_map {
+            self.update_map(game_state);
+            SceneStats::default()
+        } else {
+            let scene_stats =
+                scene::render_scene(&mut self.canvas, game_state, &self.scene_settings);
+            game_state.weapons().draw(&mut self.canvas);
+            self.hud.draw(&mut self.canvas, &self.font, game_state);
+            scene_stats
+        };
 
-        // Clear Screen
-        loop {
-            // let x = (pixel_offset % size.width as usize) as f32 / size.width as f32;
-            // let y = (size.height as usize - (pixel_offset / size.width as usize)) as f32
-            //     / size.height as f32;
+        if frame_stats.show_overlay() {
+            frame_stats.draw_overlay(&mut self.canvas, &self.font);
+        }
+        console.draw(&mut self.canvas, &self.font);
 
-            let colour: u32 = 0; // per_pixel(x, y);
+        scene_stats
+    }
 
-            // let pixel_offset = ((y * self.size.width + x) * 4) as usize;
+    pub fn showing_map(&self) -> bool {
+        !self.render_map
+    }
 
-            // todo: check the color format first
-            let rgba_offset = pixel_offset * 4;
-            self.canvas[rgba_offset] = (colour >> 24) as u8;
-            self.canvas[rgba_offset + 1] = (colour >> 16) as u8;
-            self.canvas[rgba_offset + 2] = (colour >> 8) as u8;
-            self.canvas[rgba_offset + 3] = colour as u8;
+    pub fn set_hud(&mut self, hud: Hud) {
+        self.hud = hud;
+    }
 
-            pixel_offset += 1;
-            if pixel_offset == pixels {
-                break;
-            }
-        }
-        if !self.render_map {
-            self.update_map(game_state);
-        } else {
-            self.update_scene(game_state);
-        }
+    pub fn set_sky(&mut self, sky: Sky) {
+        self.scene_settings.sky = sky;
+    }
+
+    pub fn set_textures(&mut self, textures: Textures) {
+        self.scene_settings.textures = textures;
     }
 
     fn update_map(&mut self, game_state: &GameState) {
         // Draw Level
         for sector in game_state.level().sectors() {
             for wall in sector.walls() {
-                self.draw_line(&LineDescriptor {
+                self.canvas.draw_line(&LineDescriptor {
                     start: wall.0,
                     end: wall.1,
                     color: Vec3::splat(1.0),
@@ -184,117 +259,32 @@ impl State {
 
         // Draw Player
         let look_at = game_state.pos() + (game_state.rot().xy() * 100.0);
-        self.draw_line(&LineDescriptor {
+        self.canvas.draw_line(&LineDescriptor {
             start: game_state.pos(),
             end: look_at,
             color: Vec3::new(1.0, 0.0, 0.0),
             stroke: 1.0,
         });
-        self.draw_circle(&CricleDescriptor {
+        self.canvas.draw_circle(&CricleDescriptor {
             centre: game_state.pos(),
             radius: 100.0,
             color: Vec3::splat(1.0),
         });
     }
 
-    fn update_scene(&mut self, game_state: &GameState) {
-        // we need to iterate through the walls in the scene, each one needs to be transformed into user-space
-        //  First create the quaternion that will transform the wall points
-
-        let transform = create_transform(game_state.pos(), game_state.rot());
-
-        // for wall in game_state.level().walls() {
-        //     self.draw_line(&LineDescriptor {
-        //         start: transform.transform_point2(wall[0]),
-        //         end: transform.transform_point2(wall[1]),
-        //         color: Vec3::splat(1.0),
-        //         stroke: 1.0,
-        //     });
-        // }
-
-        let fov_y = PI / 2.0;
-        let render_distance = 2.0;
-        let mut player_height = 1.5;
-
-        let mut additional_sectors = Vec::<SectorId>::new();
-        let mut sector_index = 0;
-
-        if let Some(current_sector) = game_state.find_current_sector() {
-            player_height += game_state.level().sector(current_sector).base_height();
-            additional_sectors.push(current_sector);
-        }
-        loop {
-            if additional_sectors.len() == sector_index {
-                break;
-            }
-            let sector = game_state.level().sector(additional_sectors[sector_index]);
-
-            let portal_sectors = self.draw_sector(sector, transform, fov_y, render_distance, player_height);
-
-            for sector_id in portal_sectors {
-                if !additional_sectors.contains(&sector_id) {
-                    additional_sectors.push(sector_id);
-                }
-            }
-            sector_index += 1;
-        }
-    }
-
-    fn draw_sector(&mut self, sector: &Sector, transform: Mat3, fov_y: f32, render_distance: f32, player_height: f32) -> Vec<SectorId> {
-        let mut additional_sectors = Vec::<SectorId>::new();
-        let half_canvas_height = self.size.height as f32 / 2.0;
-
-        for y in 0..(self.size.width) {
-            let ray_angle = -((y as f32 / self.size.width as f32) * 2.0 - 1.0) * fov_y / 2.0;
-
-            let ray = Mat3::from_axis_angle(Vec3::Z, ray_angle).transform_vector2(Vec2::Y);
-
-            for portal in sector.portals() {
-                let start = transform.transform_point2(portal.0);
-                let end = transform.transform_point2(portal.1);
-                if intersection_distance(Vec2::ZERO, ray, start, end).is_some() {
-                    if !additional_sectors.contains(&portal.2) {
-                        additional_sectors.push(portal.2);
-                    }
-                    break;
-                }                    
-            }
-
-            for wall in sector.walls() {
-                let start = transform.transform_point2(wall.0);
-                let end = transform.transform_point2(wall.1);
-                if let Some(distance) = intersection_distance(Vec2::ZERO, ray, start, end) {
-                    let corrected_distance = (distance * ray_angle.cos()).max(0.0);
-                    if corrected_distance > render_distance {
-                        continue;
-                    }
-                    let perceived_height = (sector.height()) / corrected_distance * 200.0;
-                    let perceived_base_height =
-                        (sector.base_height() - player_height) / corrected_distance * 200.0;
-
-                    let color = wall.2 * (1.0 - (corrected_distance / render_distance)).max(0.0);
-
-                    self.draw_vertical_line(&VerticalLineDescriptor {
-                        y,
-                        top_x: (half_canvas_height - perceived_height - perceived_base_height)
-                            as u32,
-                        bottom_x: (half_canvas_height - perceived_base_height).min(self.size.height as f32) as u32,
-                        color: color.extend(1000.0 / corrected_distance),
-                    })
-                }
-            }
-        }   
-
-        additional_sectors
-    }
-
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-            self.canvas = vec![0; (new_size.width * new_size.height * 4) as usize];
+            self.canvas = Canvas::new(new_size.width, new_size.height);
+            (self.canvas_texture, self.canvas_bind_group) = create_canvas_texture(
+                &self.device,
+                &self.canvas_layout,
+                self.canvas_format,
+                &self.canvas,
+            );
         }
     }
 
@@ -319,113 +309,124 @@ impl State {
     fn toggle_renderer(&mut self) {
         self.render_map = !self.render_map;
     }
+}
 
-    fn draw_vertical_line(&mut self, line: &VerticalLineDescriptor) {
-        let mut pixel_offset = line.top_x * self.size.width + line.y;
-        for _ in line.top_x..line.bottom_x {
-            // loop with if would be faster
-            let rgba_offset = (pixel_offset * 4) as usize;
-
-            let red_channel = (255.0 * line.color[0]) as u8;
-            let green_channel = (255.0 * line.color[1]) as u8;
-            let blue_channel = (255.0 * line.color[2]) as u8;
-            let alpha_channel = (255.0 * line.color[3]) as u8;
-
-            if self.canvas[rgba_offset + 3] < alpha_channel {
-                self.canvas[rgba_offset] = blue_channel;
-                self.canvas[rgba_offset + 1] = green_channel;
-                self.canvas[rgba_offset + 2] = red_channel;
-                self.canvas[rgba_offset + 3] = alpha_channel;
-                pixel_offset += self.size.width;
-            }
-        }
+impl Cvars for State {
+    fn cvars(&self) -> Vec<Cvar> {
+        vec![
+            Cvar {
+                name: "fov",
+                description: "field of view in degrees",
+                value: CvarValue::Float(self.scene_settings.fov_y.to_degrees()),
+            },
+            Cvar {
+                name: "render_distance",
+                description: "how far sectors are drawn",
+                value: CvarValue::Float(self.scene_settings.render_distance),
+            },
+            Cvar {
+                name: "threads",
+                description: "threads the scene is drawn with",
+                value: CvarValue::Int(self.scene_settings.threads as i64),
+            },
+            Cvar {
+                name: "show_map",
+                description: "show the map instead of the 3D view",
+                value: CvarValue::Bool(!self.render_map),
+            },
+        ]
     }
 
-    fn draw_circle(&mut self, circle: &CricleDescriptor) {
-        let min_y = max((circle.centre.y - circle.radius) as i32, 0) as u32;
-        let max_y = min((circle.centre.y + circle.radius) as u32, self.size.height);
-
-        let min_x = max((circle.centre.x - circle.radius) as i32, 0) as u32;
-        let max_x = min((circle.centre.x + circle.radius) as u32, self.size.width);
-
-        for y in min_y..max_y {
-            for x in min_x..max_x {
-                let distance = circle.centre.distance(Vec2::new(x as f32, y as f32));
-                let _intensity = (circle.radius - distance) / circle.radius;
-                if distance < circle.radius {
-                    self.plot_with_opacity(x, y, 128, 128, 128, 0.5);
-                }
+    fn set_cvar(&mut self, name: &str, value: CvarValue) -> bool {
+        match (name, value) {
+            ("fov", CvarValue::Float(value)) => {
+                self.scene_settings.fov_y = value.clamp(1.0, 179.0).to_radians()
             }
-        }
-    }
-
-    fn draw_line(&mut self, line: &LineDescriptor) {
-        let start = (line.start.x as isize, line.start.y as isize);
-        let end = (line.end.x as isize, line.end.y as isize);
-
-        for (x, y) in bresenham::Bresenham::new(start, end) {
-            if 0 < x && x < self.size.width as isize && 0 < y && y < self.size.height as isize {
-                self.plot(x as u32, y as u32, line.color);
+            ("render_distance", CvarValue::Float(value)) => {
+                self.scene_settings.render_distance = value.max(0.0)
+            }
+            ("threads", CvarValue::Int(value)) => {
+                self.scene_settings.threads = value.max(1) as usize
             }
+            ("show_map", CvarValue::Bool(value)) => self.render_map = !value,
+            _ => return false,
         }
+        true
     }
-
-    fn plot(&mut self, x: u32, y: u32, color: Vec3) {
-        let pixel_offset = (x + y * self.size.width) as usize;
-        let rgba_offset = pixel_offset * 4;
-        self.canvas[rgba_offset] = (color[2] * 255.0) as u8;
-        self.canvas[rgba_offset + 1] = (color[1] * 255.0) as u8;
-        self.canvas[rgba_offset + 2] = (color[0] * 255.0) as u8;
-    }
-
-    fn plot_with_opacity(&mut self, x: u32, y: u32, r: u8, g: u8, b: u8, opacity: f32) {
-        let pixel_offset = (x + y * self.size.width) as usize;
-        let rgba_offset = pixel_offset * 4;
-
-        self.canvas[rgba_offset] = blend(self.canvas[rgba_offset], b, opacity);
-        self.canvas[rgba_offset + 1] = blend(self.canvas[rgba_offset + 1], g, opacity);
-        self.canvas[rgba_offset + 2] = blend(self.canvas[rgba_offset + 2], r, opacity);
-    }
-}
-
-fn blend(current: u8, new: u8, opacity: f32) -> u8 {
-    let current = current as f32;
-    let new = new as f32 * opacity;
-
-    let hdr_color = current + new;
-
-    255.0_f32.min(hdr_color) as u8
 }
 
-fn create_transform(pos: Vec2, rot: Vec3) -> Mat3 {
-    let mut angle = rot.angle_between(glam::Vec3::NEG_Y);
-    let angle_from_x = rot.angle_between(glam::Vec3::X);
-
-    if angle_from_x < PI / 2.0 {
-        angle = angle.neg()
-    }
-
-    let rot_transform = glam::Mat3::from_axis_angle(Vec3::Z, angle);
-    let trans_transform = glam::Mat3::from_translation(pos.neg());
-    rot_transform * trans_transform
+/// Creates the texture the canvas is uploaded into. It is the canvas turned on
+/// its side, one texture row per canvas column.
+fn create_canvas_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+    canvas: &Canvas,
+) -> (wgpu::Texture, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Canvas Texture"),
+        size: wgpu::Extent3d {
+            width: canvas.height(),
+            height: canvas.width(),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Canvas Bind Group"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&view),
+        }],
+    });
+
+    (texture, bind_group)
 }
 
-fn intersection_distance(origin: Vec2, direction: Vec2, start: Vec2, end: Vec2) -> Option<f32> {
-    let v1 = (origin - start).extend(0.0);
-    let v2 = (end - start).extend(0.0);
-    let v3 = Vec3::new(direction.y, direction.x, 0.0);
-
-    let dot = v2.dot(v3);
-    if dot.abs() < 0.000001 {
-        return None;
-    }
-
-    let t1 = v2.cross(v1).z / dot;
-    let t2 = v1.dot(v3) / dot;
-
-    if t1 >= 0.0 && (0.0..=1.0).contains(&t2) {
-        return Some(t1);
-    }
-
-    None
+fn create_canvas_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Canvas Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("canvas.wgsl").into()),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Canvas Pipeline Layout"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Canvas Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
 }
@@ -0,0 +1,789 @@
+use std::{f32::consts::PI, thread};
+
+use glam::{Mat3, Vec2, Vec3, Vec4};
+
+use crate::{
+    canvas::{Canvas, CanvasBand},
+    game::GameState,
+    geometry::{create_transform, intersection_distance},
+    level::{LevelState, Line, Mask, MaskPattern, SectorId, Wall},
+    primitives::VerticalLineDescriptor,
+    sky::Sky,
+    texture::Textures,
+};
+
+pub struct SceneSettings {
+    pub fov_y: f32,
+    pub render_distance: f32,
+    /// Number of column bands rendered in parallel, one keeps everything on
+    /// the calling thread.
+    pub threads: usize,
+    /// Seen above sectors open to the sky.
+    pub sky: Sky,
+    /// What line textures and decals name.
+    pub textures: Textures,
+}
+
+impl Default for SceneSettings {
+    fn default() -> Self {
+        Self {
+            fov_y: PI / 2.0,
+            render_distance: 2.0,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            sky: Sky::default(),
+            textures: Textures::default(),
+        }
+    }
+}
+
+/// Something drawn as an upright rectangle that always faces the camera,
+/// standing on `base_height`.
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite {
+    pub pos: Vec2,
+    pub base_height: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: Vec3,
+}
+
+/// Colour of the upper and lower walls where a portal's sectors have
+/// different floors or ceilings.
+const STEP_COLOR: Vec3 = Vec3::new(0.5, 0.5, 0.5);
+
+/// Most sectors drawn in one frame, in case warps lead back round to the
+/// same sectors at ever different angles.
+const MAX_VISIBLE_SECTORS: usize = 1024;
+
+/// Counters describing how much work a call to `render_scene` did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SceneStats {
+    pub sectors_visited: usize,
+    /// Wall spans drawn across all columns.
+    pub columns_drawn: usize,
+}
+
+/// Draws the 3D view of the level from the player's point of view.
+///
+/// Visible sectors are found first by following portals out of the player's
+/// sector, then the screen is split into bands of columns which are drawn
+/// independently, so the output does not depend on `settings.threads`.
+pub fn render_scene(
+    canvas: &mut Canvas,
+    game_state: &GameState,
+    settings: &SceneSettings,
+) -> SceneStats {
+    let view = View::new(game_state, settings, canvas);
+    let sectors = view.visible_sectors(game_state);
+
+    let threads = settings.threads.clamp(1, canvas.width().max(1) as usize) as u32;
+    let band_width = canvas.width().div_ceil(threads);
+
+    let columns_drawn = if threads == 1 {
+        canvas
+            .bands_mut(band_width)
+            .next()
+            .map_or(0, |mut band| view.draw_columns(&mut band, &sectors))
+    } else {
+        thread::scope(|scope| {
+            let handles: Vec<_> = canvas
+                .bands_mut(band_width)
+                .map(|mut band| {
+                    let (view, sectors) = (&view, &sectors);
+                    scope.spawn(move || view.draw_columns(&mut band, sectors))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .sum()
+        })
+    };
+
+    let mut overlays = view.mask_overlays(&sectors);
+    overlays.extend(view.sprite_overlays(game_state.level(), &game_state.sprites(), &sectors));
+    if !overlays.is_empty() {
+        let depths = view.wall_depths(&sectors);
+        view.draw_overlays(canvas, overlays, &depths);
+    }
+
+    SceneStats {
+        sectors_visited: sectors.len(),
+        columns_drawn,
+    }
+}
+
+struct View<'a> {
+    transform: Mat3,
+    pos: Vec2,
+    player_height: f32,
+    render_distance: f32,
+    fov_y: f32,
+    canvas_height: u32,
+    /// Angle and camera space direction of the ray cast for each column.
+    rays: Vec<(f32, Vec2)>,
+    sky: &'a Sky,
+    textures: &'a Textures,
+    /// Seconds of play, for animated and scrolling textures.
+    time: f32,
+}
+
+impl<'a> View<'a> {
+    fn new(game_state: &GameState, settings: &'a SceneSettings, canvas: &Canvas) -> Self {
+        // we need to iterate through the walls in the scene, each one needs to be transformed into user-space
+        //  First create the quaternion that will transform the wall points
+        let transform = create_transform(game_state.pos(), game_state.rot());
+
+        let player_height = game_state.eye_height();
+
+        let width = canvas.width();
+        let rays = (0..width)
+            .map(|y| {
+                let ray_angle = -((y as f32 / width as f32) * 2.0 - 1.0) * settings.fov_y / 2.0;
+                let ray = Mat3::from_axis_angle(Vec3::Z, -ray_angle).transform_vector2(Vec2::NEG_Y);
+                (ray_angle, ray)
+            })
+            .collect();
+
+        Self {
+            transform,
+            pos: game_state.pos(),
+            player_height,
+            render_distance: settings.render_distance,
+            fov_y: settings.fov_y,
+            canvas_height: canvas.height(),
+            rays,
+            sky: &settings.sky,
+            textures: &settings.textures,
+            time: game_state.time(),
+        }
+    }
+
+    /// Walks the portal graph from the player's sector, returning every
+    /// sector that can be seen in the order they should be drawn. A sector
+    /// seen through warps or mirrors at different angles is drawn once for
+    /// each.
+    fn visible_sectors<'g>(&self, game_state: &'g GameState) -> Vec<TransformedSector<'g>> {
+        let mut visible = Vec::<TransformedSector>::new();
+        let mut placements = Vec::<Placement>::new();
+        let mut sector_index = 0;
+
+        if let Some(current_sector) = game_state.find_current_sector() {
+            placements.push(Placement {
+                sector_id: current_sector,
+                transform: self.transform,
+                height_offset: 0.0,
+                window: None,
+            });
+        }
+        while sector_index < placements.len().min(MAX_VISIBLE_SECTORS) {
+            let placement = placements[sector_index];
+            sector_index += 1;
+
+            let bounds = game_state.level().sector_bounds(placement.sector_id);
+            let pos = if placement.window.is_some() {
+                placement.transform.inverse().transform_point2(Vec2::ZERO)
+            } else {
+                self.pos
+            };
+            if bounds.culled(placement.transform, pos, self.render_distance) {
+                continue;
+            }
+
+            let sector = TransformedSector::new(game_state.level(), placement);
+
+            let mut seen = vec![false; sector.portals.len()];
+            for (_, ray) in self.rays.iter().filter(|(_, ray)| sector.shows(*ray)) {
+                // Rays cross the portal the sector is seen through too, the
+                // way on is the furthest one
+                let hit = sector
+                    .portals
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, portal)| {
+                        intersection_distance(Vec2::ZERO, *ray, portal.start, portal.end)
+                            .map(|distance| (index, distance))
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(index, _)| index);
+
+                if let Some(portal_index) = hit {
+                    seen[portal_index] = true;
+                }
+            }
+            for (portal, _) in sector.portals.iter().zip(seen).filter(|(_, seen)| *seen) {
+                let next = portal.placement(&placement);
+                if !placements.iter().any(|other| other.same_as(&next)) {
+                    placements.push(next);
+                }
+            }
+
+            visible.push(sector);
+        }
+
+        visible
+    }
+
+    /// Draws the band's columns of every visible sector, returning the number
+    /// of wall spans drawn.
+    fn draw_columns(&self, band: &mut CanvasBand, sectors: &[TransformedSector]) -> usize {
+        let columns = band.first_column()..band.first_column() + band.width();
+        let mut columns_drawn = 0;
+        for sector in sectors {
+            for y in columns.clone() {
+                columns_drawn += self.draw_column(band, y, self.rays[y as usize], sector);
+            }
+        }
+        columns_drawn
+    }
+
+    /// Distance along the view direction to the nearest wall or shut portal
+    /// in each column, infinite where none is drawn.
+    fn wall_depths(&self, sectors: &[TransformedSector]) -> Vec<f32> {
+        self.rays
+            .iter()
+            .map(|(ray_angle, ray)| {
+                sectors
+                    .iter()
+                    .filter(|sector| sector.shows(*ray))
+                    .flat_map(|sector| {
+                        let shut = sector
+                            .portals
+                            .iter()
+                            .filter(|portal| portal.is_shut(sector))
+                            .map(|portal| (portal.start, portal.end));
+                        sector
+                            .walls
+                            .iter()
+                            .map(|wall| (wall.start, wall.end))
+                            .chain(shut)
+                    })
+                    .filter_map(|(start, end)| intersection_distance(Vec2::ZERO, *ray, start, end))
+                    .map(|distance| distance * ray_angle.cos())
+                    .filter(|distance| *distance <= self.render_distance)
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .collect()
+    }
+
+    /// Sprites as overlays, moved into camera space along with the sector
+    /// they stand in once for every view of it through warps and mirrors.
+    /// Sprites outside the level, or in sectors that weren't drawn, go where
+    /// they are.
+    fn sprite_overlays(
+        &self,
+        level: &LevelState,
+        sprites: &[Sprite],
+        sectors: &[TransformedSector],
+    ) -> Vec<Overlay> {
+        let mut overlays = vec![];
+        for sprite in sprites {
+            let sector_id = level.find_current_sector(sprite.pos);
+            let mut views: Vec<_> = sectors
+                .iter()
+                .filter(|sector| Some(sector.placement.sector_id) == sector_id)
+                .map(|sector| {
+                    (
+                        sector.placement.transform.transform_point2(sprite.pos),
+                        sprite.base_height + sector.placement.height_offset,
+                        Some(sector),
+                    )
+                })
+                .collect();
+            if views.is_empty() {
+                let pos = self.transform.transform_point2(sprite.pos);
+                views.push((pos, sprite.base_height, None));
+            }
+
+            for (pos, base_height, sector) in views {
+                let depth = -pos.y;
+                if depth <= 0.05 || depth > self.render_distance {
+                    continue;
+                }
+                let half_width = sprite.width / 2.0;
+                let left = self.column(pos.x - half_width, depth).max(0.0) as u32;
+                let right = (self.column(pos.x + half_width, depth).max(0.0) as u32)
+                    .min(self.rays.len() as u32);
+                let color = sprite.color * (1.0 - depth / self.render_distance).max(0.0);
+                overlays.extend(
+                    (left..right)
+                        .filter(|x| {
+                            sector.is_none_or(|sector| sector.shows(self.rays[*x as usize].1))
+                        })
+                        .map(|x| Overlay {
+                            x,
+                            depth,
+                            bottom: base_height,
+                            top: base_height + sprite.height,
+                            color,
+                            opacity: 1.0,
+                            rows: None,
+                        }),
+                );
+            }
+        }
+        overlays
+    }
+
+    /// Masks hung in the portals of visible sectors, a column at a time so
+    /// bars can leave gaps between them.
+    fn mask_overlays(&self, sectors: &[TransformedSector]) -> Vec<Overlay> {
+        let mut overlays = vec![];
+        for sector in sectors {
+            for portal in &sector.portals {
+                let Some(mask) = portal.mask else {
+                    continue;
+                };
+                let bottom = portal.floor.max(sector.base_height);
+                let top = portal.ceiling.min(sector.base_height + sector.height);
+                if top <= bottom {
+                    continue;
+                }
+
+                for (x, (ray_angle, ray)) in self.rays.iter().enumerate() {
+                    if !sector.shows(*ray) {
+                        continue;
+                    }
+                    let Some(distance) =
+                        intersection_distance(Vec2::ZERO, *ray, portal.start, portal.end)
+                    else {
+                        continue;
+                    };
+                    let depth = distance * ray_angle.cos();
+                    if depth <= 0.05 || depth > self.render_distance {
+                        continue;
+                    }
+                    let along = (*ray * distance - portal.start).length();
+                    let rows = match mask.pattern {
+                        MaskPattern::Pane => None,
+                        MaskPattern::Bars { spacing, width }
+                            if along.rem_euclid(spacing) < width =>
+                        {
+                            None
+                        }
+                        MaskPattern::Bars { .. } => continue,
+                        MaskPattern::Grid { spacing, width }
+                            if along.rem_euclid(spacing) < width =>
+                        {
+                            None
+                        }
+                        MaskPattern::Grid { spacing, width } => Some((spacing, width)),
+                    };
+                    overlays.push(Overlay {
+                        x: x as u32,
+                        depth,
+                        bottom,
+                        top,
+                        color: mask.color * (1.0 - depth / self.render_distance).max(0.0),
+                        opacity: mask.opacity,
+                        rows,
+                    });
+                }
+            }
+        }
+        overlays
+    }
+
+    /// Draws overlays from the furthest to the nearest, each only where it is
+    /// in front of the wall in its column.
+    fn draw_overlays(&self, canvas: &mut Canvas, mut overlays: Vec<Overlay>, depths: &[f32]) {
+        let half_canvas_height = self.canvas_height as f32 / 2.0;
+        overlays.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+
+        for overlay in overlays {
+            if overlay.depth >= depths[overlay.x as usize] {
+                continue;
+            }
+            let row = |height: f32| {
+                half_canvas_height - (height - self.player_height) / overlay.depth * 200.0
+            };
+            let top = row(overlay.top).max(0.0) as u32;
+            let bottom = (row(overlay.bottom).max(0.0) as u32).min(self.canvas_height);
+            for y in top..bottom {
+                if let Some((spacing, width)) = overlay.rows {
+                    let height = self.player_height
+                        + (half_canvas_height - y as f32 - 0.5) * overlay.depth / 200.0;
+                    if (height - overlay.bottom).rem_euclid(spacing) >= width {
+                        continue;
+                    }
+                }
+                canvas.plot_blended(overlay.x, y, overlay.color, overlay.opacity);
+            }
+        }
+    }
+
+    /// Column a camera space point `x` across and `depth` ahead lands in, the
+    /// inverse of the ray angles picked in `new`.
+    fn column(&self, x: f32, depth: f32) -> f32 {
+        let width = self.rays.len() as f32;
+        width * (1.0 - (-x).atan2(depth) / (self.fov_y / 2.0)) / 2.0
+    }
+
+    fn draw_column(
+        &self,
+        band: &mut CanvasBand,
+        y: u32,
+        (ray_angle, ray): (f32, Vec2),
+        sector: &TransformedSector,
+    ) -> usize {
+        let half_canvas_height = self.canvas_height as f32 / 2.0;
+        let mut columns_drawn = 0;
+        if !sector.shows(ray) {
+            return columns_drawn;
+        }
+
+        for wall in &sector.walls {
+            if let Some(distance) = intersection_distance(Vec2::ZERO, ray, wall.start, wall.end) {
+                let corrected_distance = (distance * ray_angle.cos()).max(0.0);
+                self.draw_sky(band, y, ray, sector, corrected_distance);
+                if corrected_distance > self.render_distance {
+                    continue;
+                }
+                let perceived_height = (sector.height) / corrected_distance * 200.0;
+                let perceived_base_height =
+                    (sector.base_height - self.player_height) / corrected_distance * 200.0;
+
+                let rows = (
+                    (half_canvas_height - perceived_height - perceived_base_height) as u32,
+                    (half_canvas_height - perceived_base_height).min(self.canvas_height as f32)
+                        as u32,
+                );
+                let hit = LineHit {
+                    line: wall.line,
+                    along: (ray * distance - wall.start).length(),
+                    floor: sector.base_height,
+                    depth: corrected_distance,
+                };
+                self.draw_span(band, y, rows, wall.color, &hit);
+                columns_drawn += 1;
+            }
+        }
+
+        // Rays cross the line the sector is seen through too, but only the
+        // furthest line is seen from the sector's own side
+        let walls = sector.walls.iter().map(|wall| (wall.start, wall.end));
+        let portals = sector
+            .portals
+            .iter()
+            .map(|portal| (portal.start, portal.end));
+        let furthest = walls
+            .chain(portals)
+            .filter_map(|(start, end)| intersection_distance(Vec2::ZERO, ray, start, end))
+            .fold(0.0, f32::max);
+
+        // Steps up and down to the far sector, the rest is left to it
+        for portal in &sector.portals {
+            let Some(distance) = intersection_distance(Vec2::ZERO, ray, portal.start, portal.end)
+            else {
+                continue;
+            };
+            let corrected_distance = (distance * ray_angle.cos()).max(0.0);
+            self.draw_sky(band, y, ray, sector, corrected_distance);
+            if corrected_distance > self.render_distance || distance < furthest {
+                continue;
+            }
+            let ceiling = sector.base_height + sector.height;
+            // Between two skies there's nothing above the lower ceiling
+            let upper_ceiling = if sector.sky.is_some() && portal.sky {
+                portal.ceiling.max(sector.base_height)
+            } else {
+                ceiling
+            };
+            let spans = [
+                (sector.base_height, portal.floor.min(ceiling)),
+                (portal.ceiling.max(sector.base_height), upper_ceiling),
+            ];
+            let hit = LineHit {
+                line: portal.line,
+                along: (ray * distance - portal.start).length(),
+                floor: sector.base_height,
+                depth: corrected_distance,
+            };
+            for (bottom, top) in spans {
+                if top <= bottom {
+                    continue;
+                }
+                let rows = (
+                    (half_canvas_height - (top - self.player_height) / corrected_distance * 200.0)
+                        as u32,
+                    (half_canvas_height
+                        - (bottom - self.player_height) / corrected_distance * 200.0)
+                        .min(self.canvas_height as f32) as u32,
+                );
+                self.draw_span(band, y, rows, STEP_COLOR, &hit);
+                columns_drawn += 1;
+            }
+        }
+
+        columns_drawn
+    }
+
+    /// Draws the `rows` of column `y` where it meets a line, in the line's
+    /// texture and with its decals when it has them, otherwise in `color`.
+    fn draw_span(
+        &self,
+        band: &mut CanvasBand,
+        y: u32,
+        (top_x, bottom_x): (u32, u32),
+        color: Vec3,
+        hit: &LineHit,
+    ) {
+        let fade = (1.0 - (hit.depth / self.render_distance)).max(0.0);
+        let depth = 1000.0 / hit.depth;
+        let texture = hit.line.texture().and_then(|name| self.textures.get(name));
+        let decals: Vec<_> = hit
+            .line
+            .decals()
+            .iter()
+            .filter(|decal| (hit.along - decal.along).abs() < decal.size.x / 2.0)
+            .map(|decal| {
+                let texture = decal
+                    .texture
+                    .as_deref()
+                    .and_then(|name| self.textures.get(name));
+                (decal, texture)
+            })
+            .collect();
+
+        if texture.is_none() && decals.is_empty() {
+            band.draw_vertical_line(&VerticalLineDescriptor {
+                y,
+                top_x,
+                bottom_x,
+                color: (color * fade).extend(depth),
+            });
+            return;
+        }
+
+        let half_canvas_height = self.canvas_height as f32 / 2.0;
+        band.draw_vertical_span(y, top_x, bottom_x, depth, |row| {
+            let up = self.player_height
+                + (half_canvas_height - row as f32 - 0.5) * hit.depth / 200.0
+                - hit.floor;
+            let mut color = texture.map_or(color, |texture| {
+                texture.sample(hit.along, up, self.time).truncate()
+            });
+            for (decal, texture) in &decals {
+                let u = (hit.along - decal.along) / decal.size.x + 0.5;
+                let v = 0.5 - (up - decal.height) / decal.size.y;
+                if !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                let texel =
+                    texture.map_or(Vec4::ONE, |texture| texture.sample_decal(u, v, self.time));
+                color = color.lerp(texel.truncate() * decal.color, texel.w);
+            }
+            color * fade
+        });
+    }
+
+    /// Fills column `y` above where the ceiling of a sector open to the sky
+    /// meets a line `corrected_distance` away, behind anything drawn there.
+    fn draw_sky(
+        &self,
+        band: &mut CanvasBand,
+        y: u32,
+        ray: Vec2,
+        sector: &TransformedSector,
+        corrected_distance: f32,
+    ) {
+        let Some(to_world) = sector.sky else {
+            return;
+        };
+        let half_canvas_height = self.canvas_height as f32 / 2.0;
+        let ceiling = sector.base_height + sector.height;
+        let bottom = half_canvas_height
+            - (ceiling - self.player_height) / corrected_distance.max(f32::EPSILON) * 200.0;
+        let direction = to_world.transform_vector2(ray);
+        band.draw_background(y, 0, bottom.max(0.0) as u32, |row| {
+            self.sky.sample(direction, row as f32 / half_canvas_height)
+        });
+    }
+}
+
+/// Where a sector is drawn from, which is only somewhere other than the
+/// player's view when it's seen through a warp or mirror.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    sector_id: SectorId,
+    /// Moves the sector's points into camera space.
+    transform: Mat3,
+    /// Added to the sector's heights to put them level with the player's.
+    height_offset: f32,
+    /// The warp portal or mirror, in camera space, the sector is seen
+    /// through. It's only drawn in the columns behind it.
+    window: Option<(Vec2, Vec2)>,
+}
+
+impl Placement {
+    fn same_as(&self, other: &Placement) -> bool {
+        self.sector_id == other.sector_id
+            && self.transform.abs_diff_eq(other.transform, 1e-3)
+            && (self.height_offset - other.height_offset).abs() < 1e-3
+    }
+}
+
+/// A sector's walls and portals moved into camera space, built once per frame
+/// rather than once per column.
+struct TransformedSector<'l> {
+    placement: Placement,
+    walls: Vec<TransformedWall<'l>>,
+    portals: Vec<TransformedPortal<'l>>,
+    base_height: f32,
+    height: f32,
+    /// Takes camera space directions back to the world, for sectors open to
+    /// the sky.
+    sky: Option<Mat3>,
+}
+
+/// A wall in camera space, with the line it's drawn for.
+struct TransformedWall<'l> {
+    start: Vec2,
+    end: Vec2,
+    color: Vec3,
+    line: &'l Line,
+}
+
+/// A portal or mirror in camera space with the floor and ceiling of the
+/// sector on the other side, which for a mirror is the same one.
+struct TransformedPortal<'l> {
+    start: Vec2,
+    end: Vec2,
+    line: &'l Line,
+    sector_id: SectorId,
+    /// Moves the far side's points to where they're seen from this side,
+    /// and how far its heights go up, for warps and mirrors.
+    through: Option<(Mat3, f32)>,
+    floor: f32,
+    ceiling: f32,
+    mask: Option<Mask>,
+    /// Whether the far sector is open to the sky.
+    sky: bool,
+}
+
+/// Where a column meets a line, `along` it from its first point and `depth`
+/// ahead, for a line of a sector whose floor is at `floor`.
+struct LineHit<'l> {
+    line: &'l Line,
+    along: f32,
+    floor: f32,
+    depth: f32,
+}
+
+/// Part of a column drawn over the walls once every sector is done, like a
+/// sprite or a mask, between heights `bottom` and `top`.
+struct Overlay {
+    x: u32,
+    depth: f32,
+    bottom: f32,
+    top: f32,
+    color: Vec3,
+    opacity: f32,
+    /// Only draws bars `width` tall every `spacing` up from `bottom`.
+    rows: Option<(f32, f32)>,
+}
+
+/// Reflects points about the line through `start` and `end`.
+fn reflection(start: Vec2, end: Vec2) -> Mat3 {
+    let direction = (end - start).normalize_or_zero();
+    let (x, y) = (direction.x, direction.y);
+    let flip = Mat3::from_cols(
+        Vec3::new(2.0 * x * x - 1.0, 2.0 * x * y, 0.0),
+        Vec3::new(2.0 * x * y, 2.0 * y * y - 1.0, 0.0),
+        Vec3::Z,
+    );
+    Mat3::from_translation(start) * flip * Mat3::from_translation(-start)
+}
+
+impl TransformedPortal<'_> {
+    /// Where the sector on the other side is drawn from, for a portal of a
+    /// sector drawn from `near`.
+    fn placement(&self, near: &Placement) -> Placement {
+        match self.through {
+            None => Placement {
+                sector_id: self.sector_id,
+                ..*near
+            },
+            Some((matrix, height)) => Placement {
+                sector_id: self.sector_id,
+                transform: near.transform * matrix,
+                height_offset: near.height_offset + height,
+                window: Some((self.start, self.end)),
+            },
+        }
+    }
+
+    /// Nothing can be seen through a portal whose sectors' floors and
+    /// ceilings leave no gap, like a closed door.
+    fn is_shut(&self, sector: &TransformedSector) -> bool {
+        self.ceiling.min(sector.base_height + sector.height) <= self.floor.max(sector.base_height)
+    }
+}
+
+impl<'l> TransformedSector<'l> {
+    fn new(level: &'l LevelState, placement: Placement) -> Self {
+        let Placement {
+            sector_id,
+            transform,
+            height_offset,
+            ..
+        } = placement;
+        let sector = level.sector(sector_id);
+        let mut walls = vec![];
+        let mut portals = vec![];
+        for (line_index, line) in sector.lines().iter().enumerate() {
+            let (start, end) = sector.line_points(line_index);
+            let (far_id, through, mask) = match line.wall_type() {
+                Wall::Solid(color) => {
+                    walls.push(TransformedWall {
+                        start: transform.transform_point2(start),
+                        end: transform.transform_point2(end),
+                        color,
+                        line,
+                    });
+                    continue;
+                }
+                Wall::Portal(target) => (
+                    target,
+                    line.warp()
+                        .map(|warp| (warp.inverse().matrix(), -warp.height)),
+                    line.mask(),
+                ),
+                Wall::Mirror => (sector_id, Some((reflection(start, end), 0.0)), None),
+            };
+            let Some(far) = level.sectors().get(far_id) else {
+                continue;
+            };
+            let floor =
+                far.base_height() + height_offset + through.map_or(0.0, |(_, height)| height);
+            portals.push(TransformedPortal {
+                start: transform.transform_point2(start),
+                end: transform.transform_point2(end),
+                line,
+                sector_id: far_id,
+                through,
+                floor,
+                ceiling: floor + far.height(),
+                mask,
+                sky: far.sky(),
+            });
+        }
+        Self {
+            placement,
+            walls,
+            portals,
+            base_height: sector.base_height() + height_offset,
+            height: sector.height(),
+            sky: sector.sky().then(|| transform.inverse()),
+        }
+    }
+
+    /// Whether the column with camera space direction `ray` looks through
+    /// the window the sector is seen through, always true without one.
+    fn shows(&self, ray: Vec2) -> bool {
+        self.placement
+            .window
+            .is_none_or(|(start, end)| intersection_distance(Vec2::ZERO, ray, start, end).is_some())
+    }
+}
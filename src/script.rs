@@ -0,0 +1,394 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use glam::{Vec2, Vec3};
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, EvalAltResult, Map,
+    Scope, AST,
+};
+
+use crate::{
+    game::GameState,
+    level::{Decal, LevelState, SectorId},
+};
+
+/// Most steps a script may take in one hook before it's stopped, enough for
+/// a few thousand loop iterations.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Something that happened in the game that scripts can react to, each runs
+/// the function of the same name if the script defines it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    /// The top level of the script, run once when it's loaded.
+    Load,
+    /// `on_tick(dt)` every update, with the seconds since the last one.
+    Tick(f32),
+    /// `on_enter_sector(sector)` when the player walks into a sector.
+    EnterSector(SectorId),
+    /// `on_use_line(sector, line)` when the player presses use at a line.
+    UseLine(SectorId, usize),
+}
+
+impl Hook {
+    fn name(&self) -> &'static str {
+        match self {
+            Hook::Load => "load",
+            Hook::Tick(_) => "on_tick",
+            Hook::EnterSector(_) => "on_enter_sector",
+            Hook::UseLine(..) => "on_use_line",
+        }
+    }
+
+    fn args(&self) -> Vec<Dynamic> {
+        match *self {
+            Hook::Load => vec![],
+            Hook::Tick(delta) => vec![Dynamic::from_float(delta as f64)],
+            Hook::EnterSector(sector_id) => vec![Dynamic::from_int(sector_id as i64)],
+            Hook::UseLine(sector_id, line_index) => vec![
+                Dynamic::from_int(sector_id as i64),
+                Dynamic::from_int(line_index as i64),
+            ],
+        }
+    }
+}
+
+/// Changes a script asked for, made to the game once the hook returns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCommand {
+    /// Puts a sector's floor and ceiling at the given heights.
+    MoveSector {
+        sector: SectorId,
+        floor: f32,
+        ceiling: f32,
+    },
+    /// Sets off the movers of the sectors tagged with this.
+    Activate(u32),
+    Spawn {
+        kind: String,
+        pos: Vec2,
+    },
+    Teleport(Vec2),
+    Message(String),
+    /// Sticks a decal on a line, like a sign or blood.
+    Decal {
+        sector: SectorId,
+        line: usize,
+        decal: Decal,
+    },
+}
+
+/// What scripts can see of the game, copied in before each hook since the
+/// engine can't borrow the game state.
+#[derive(Debug, Default)]
+struct View {
+    player: Vec2,
+    sector: Option<SectorId>,
+    health: i32,
+    sectors: Vec<SectorView>,
+}
+
+#[derive(Debug)]
+struct SectorView {
+    name: Option<String>,
+    floor: f32,
+    ceiling: f32,
+    lines: usize,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    view: View,
+    commands: Vec<ScriptCommand>,
+}
+
+/// A level's script, compiled and sandboxed. Scripts can't reach files,
+/// modules or `eval`, and are stopped when they run too long or build
+/// anything too big.
+pub struct Scripts {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    /// Hooks the script defines, with the right number of parameters.
+    hooks: HashSet<&'static str>,
+    /// Bound as `this` in every hook so scripts can keep state between
+    /// them.
+    state: Dynamic,
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Scripts {
+    /// Compiles a script, `name` is used in error messages.
+    pub fn compile(name: &str, source: &str) -> Result<Self, String> {
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let engine = engine(&shared);
+        let ast = engine.compile(source).map_err(|e| format!("{name}: {e}"))?;
+
+        let hooks = [Hook::Tick(0.0), Hook::EnterSector(0), Hook::UseLine(0, 0)]
+            .into_iter()
+            .filter(|hook| {
+                ast.iter_functions()
+                    .any(|f| f.name == hook.name() && f.params.len() == hook.args().len())
+            })
+            .map(|hook| hook.name())
+            .collect();
+
+        Ok(Self {
+            name: name.to_string(),
+            engine,
+            ast,
+            hooks,
+            state: Dynamic::from_map(Map::new()),
+            shared,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let source = fs::read_to_string(path).map_err(|e| format!("could not read {name}: {e}"))?;
+        Self::compile(&name, &source)
+    }
+
+    /// Loads the script a level names, looked for next to the level's file
+    /// when there is one. `None` when the level has no script.
+    pub fn for_level(
+        level: &LevelState,
+        level_path: Option<&Path>,
+    ) -> Result<Option<Self>, String> {
+        let Some(script) = level.script() else {
+            return Ok(None);
+        };
+        let path = match level_path.and_then(Path::parent) {
+            Some(dir) => dir.join(script),
+            None => PathBuf::from(script),
+        };
+        Self::load(path).map(Some)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runs a hook, returning what the script asked to change. Hooks the
+    /// script doesn't define do nothing. Nothing is changed if it fails.
+    pub fn run(
+        &mut self,
+        hook: Hook,
+        game_state: &GameState,
+    ) -> Result<Vec<ScriptCommand>, String> {
+        if hook != Hook::Load && !self.hooks.contains(hook.name()) {
+            return Ok(vec![]);
+        }
+        self.shared.borrow_mut().view = View::new(game_state);
+
+        let result = match hook {
+            Hook::Load => self.engine.run_ast(&self.ast),
+            _ => {
+                let options = CallFnOptions::new()
+                    .eval_ast(false)
+                    .bind_this_ptr(&mut self.state);
+                self.engine
+                    .call_fn_with_options::<Dynamic>(
+                        options,
+                        &mut Scope::new(),
+                        &self.ast,
+                        hook.name(),
+                        hook.args(),
+                    )
+                    .map(|_| ())
+            }
+        };
+
+        let commands = std::mem::take(&mut self.shared.borrow_mut().commands);
+        result.map_err(|e| format!("{}: {}: {e}", self.name, hook.name()))?;
+        Ok(commands)
+    }
+}
+
+impl View {
+    fn new(game_state: &GameState) -> Self {
+        let sectors = game_state
+            .level()
+            .sectors()
+            .iter()
+            .map(|sector| SectorView {
+                name: sector.name().map(str::to_string),
+                floor: sector.base_height(),
+                ceiling: sector.base_height() + sector.height(),
+                lines: sector.lines().len(),
+            })
+            .collect();
+        Self {
+            player: game_state.pos(),
+            sector: game_state.find_current_sector(),
+            health: game_state.player().health,
+            sectors,
+        }
+    }
+
+    fn sector(&self, sector: i64) -> Result<&SectorView, Box<EvalAltResult>> {
+        usize::try_from(sector)
+            .ok()
+            .and_then(|sector_id| self.sectors.get(sector_id))
+            .ok_or_else(|| format!("there is no sector {sector}").into())
+    }
+}
+
+/// An engine with the sandbox limits and the game's functions registered,
+/// queries read `shared` and everything else queues a command in it.
+fn engine(shared: &Rc<RefCell<Shared>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(4096)
+        .set_max_array_size(4096)
+        .set_max_map_size(4096)
+        .on_print(|text| log::info!("{text}"))
+        .on_debug(|text, _, pos| log::debug!("{pos}: {text}"));
+
+    let view = shared.clone();
+    engine.register_fn("player_x", move || view.borrow().view.player.x as f64);
+    let view = shared.clone();
+    engine.register_fn("player_y", move || view.borrow().view.player.y as f64);
+    let view = shared.clone();
+    engine.register_fn("player_sector", move || {
+        view.borrow()
+            .view
+            .sector
+            .map_or(-1, |sector_id| sector_id as i64)
+    });
+    let view = shared.clone();
+    engine.register_fn("player_health", move || view.borrow().view.health as i64);
+    let view = shared.clone();
+    engine.register_fn(
+        "sector_floor",
+        move |sector: i64| -> Result<f64, Box<EvalAltResult>> {
+            Ok(view.borrow().view.sector(sector)?.floor as f64)
+        },
+    );
+    let view = shared.clone();
+    engine.register_fn(
+        "sector_ceiling",
+        move |sector: i64| -> Result<f64, Box<EvalAltResult>> {
+            Ok(view.borrow().view.sector(sector)?.ceiling as f64)
+        },
+    );
+    let view = shared.clone();
+    engine.register_fn("find_sector", move |name: &str| {
+        view.borrow()
+            .view
+            .sectors
+            .iter()
+            .position(|sector| sector.name.as_deref() == Some(name))
+            .map_or(-1, |sector_id| sector_id as i64)
+    });
+
+    let commands = shared.clone();
+    engine.register_fn(
+        "move_sector",
+        move |sector: i64, floor: Dynamic, ceiling: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let (floor, ceiling) = (number(floor)?, number(ceiling)?);
+            if ceiling < floor {
+                return Err(
+                    format!("sector {sector} would have its ceiling below its floor").into(),
+                );
+            }
+            let mut shared = commands.borrow_mut();
+            shared.view.sector(sector)?;
+            shared.commands.push(ScriptCommand::MoveSector {
+                sector: sector as SectorId,
+                floor,
+                ceiling,
+            });
+            Ok(())
+        },
+    );
+    let commands = shared.clone();
+    engine.register_fn("activate", move |tag: i64| {
+        let tag = u32::try_from(tag).map_err(|_| format!("{tag} is not a tag"))?;
+        commands
+            .borrow_mut()
+            .commands
+            .push(ScriptCommand::Activate(tag));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let commands = shared.clone();
+    engine.register_fn("spawn", move |kind: &str, x: Dynamic, y: Dynamic| {
+        let pos = Vec2::new(number(x)?, number(y)?);
+        commands.borrow_mut().commands.push(ScriptCommand::Spawn {
+            kind: kind.to_string(),
+            pos,
+        });
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let commands = shared.clone();
+    engine.register_fn("teleport", move |x: Dynamic, y: Dynamic| {
+        let pos = Vec2::new(number(x)?, number(y)?);
+        commands
+            .borrow_mut()
+            .commands
+            .push(ScriptCommand::Teleport(pos));
+        Ok::<_, Box<EvalAltResult>>(())
+    });
+    let commands = shared.clone();
+    engine.register_fn("message", move |text: &str| {
+        commands
+            .borrow_mut()
+            .commands
+            .push(ScriptCommand::Message(text.to_string()));
+    });
+    let commands = shared.clone();
+    engine.register_fn(
+        "decal",
+        move |sector: i64,
+              line: i64,
+              along: Dynamic,
+              up: Dynamic,
+              width: Dynamic,
+              height: Dynamic,
+              texture: &str|
+              -> Result<(), Box<EvalAltResult>> {
+            let mut shared = commands.borrow_mut();
+            let lines = shared.view.sector(sector)?.lines;
+            if usize::try_from(line).map_or(true, |line| line >= lines) {
+                return Err(format!("sector {sector} has no line {line}").into());
+            }
+            let decal = Decal {
+                along: number(along)?,
+                height: number(up)?,
+                size: Vec2::new(number(width)?, number(height)?),
+                texture: Some(texture.to_string()),
+                color: Vec3::ONE,
+            };
+            shared.commands.push(ScriptCommand::Decal {
+                sector: sector as SectorId,
+                line: line as usize,
+                decal,
+            });
+            Ok(())
+        },
+    );
+
+    engine
+}
+
+/// Scripts may pass whole numbers where heights and positions are expected.
+fn number(value: Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    match value.as_float() {
+        Ok(value) => Ok(value as f32),
+        Err(type_name) => value
+            .as_int()
+            .map(|value| value as f32)
+            .map_err(|_| format!("expected a number, got {type_name}").into()),
+    }
+}
@@ -0,0 +1,57 @@
+use std::{f32::consts::TAU, io, path::Path};
+
+use glam::{Vec2, Vec3};
+
+use crate::texture::Image;
+
+/// A panorama wrapped once round the horizon, seen above sectors open to the
+/// sky. Columns go clockwise round from the +x axis and rows from straight
+/// up, or the top of the view, down to the horizon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sky {
+    image: Image,
+}
+
+impl Sky {
+    /// `None` unless there are `width` times `height` pixels and at least
+    /// one of them.
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec3>) -> Option<Self> {
+        let pixels = pixels.into_iter().map(|pixel| pixel.extend(1.0)).collect();
+        Image::new(width, height, pixels).map(|image| Self { image })
+    }
+
+    /// Reads a panorama from any image format the `image` crate knows.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Image::load(path).map(|image| Self { image })
+    }
+
+    /// Colour seen looking along `direction`, `height` of the way from the
+    /// top of the panorama to the horizon.
+    pub fn sample(&self, direction: Vec2, height: f32) -> Vec3 {
+        let around = -direction.y.atan2(direction.x) / TAU;
+        // Just short of 1 so the horizon doesn't wrap round to the top
+        let height = height.clamp(0.0, 0.9999);
+        self.image.sample(around, height).truncate()
+    }
+}
+
+impl Default for Sky {
+    /// Blue fading to haze at the horizon over a ring of low hills, so
+    /// turning round can be seen.
+    fn default() -> Self {
+        let (width, height) = (512, 64);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (around, down) = (x as f32 / width as f32 * TAU, y as f32 / height as f32);
+                let hills = 0.15 + 0.06 * (around * 3.0).sin() + 0.04 * (around * 7.0 + 1.3).sin();
+                if down > 1.0 - hills {
+                    Vec3::new(0.2, 0.3, 0.25)
+                } else {
+                    Vec3::new(0.2, 0.4, 0.8).lerp(Vec3::new(0.7, 0.8, 0.9), down)
+                }
+            })
+            .collect();
+        Self::new(width, height, pixels).expect("the built-in sky has every pixel")
+    }
+}
@@ -0,0 +1,160 @@
+use glam::{Mat3, Vec2};
+
+use crate::level::{Sector, SectorId};
+
+/// An axis aligned box around a set of points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Bounds {
+    pub fn from_points(points: &[Vec2]) -> Self {
+        let mut bounds = Self {
+            min: Vec2::splat(f32::MAX),
+            max: Vec2::splat(f32::MIN),
+        };
+        for point in points {
+            bounds.min = bounds.min.min(*point);
+            bounds.max = bounds.max.max(*point);
+        }
+        bounds
+    }
+
+    pub fn contains(&self, pos: Vec2) -> bool {
+        self.min.x <= pos.x && pos.x <= self.max.x && self.min.y <= pos.y && pos.y <= self.max.y
+    }
+
+    pub fn corners(&self) -> [Vec2; 4] {
+        [
+            self.min,
+            Vec2::new(self.min.x, self.max.y),
+            self.max,
+            Vec2::new(self.max.x, self.min.y),
+        ]
+    }
+
+    /// Distance from `pos` to the closest point of the box, zero when inside.
+    pub fn distance(&self, pos: Vec2) -> f32 {
+        pos.clamp(self.min, self.max).distance(pos)
+    }
+
+    /// True when the box cannot show up in front of the camera described by
+    /// `transform` (which looks down negative Y), either because it is
+    /// entirely behind the view plane or because it is further away than
+    /// `render_distance`.
+    pub fn culled(&self, transform: Mat3, pos: Vec2, render_distance: f32) -> bool {
+        if self.distance(pos) > render_distance {
+            return true;
+        }
+
+        self.corners()
+            .iter()
+            .all(|corner| transform.transform_point2(*corner).y >= 0.0)
+    }
+
+    fn union(&self, other: &Bounds) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Uniform grid over the level used to find which sectors might contain a
+/// point without testing every sector. Each cell lists the sectors whose
+/// bounds overlap it.
+#[derive(Debug, Clone)]
+pub struct SectorIndex {
+    bounds: Vec<Bounds>,
+    area: Bounds,
+    cell_size: Vec2,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<SectorId>>,
+}
+
+impl Default for SectorIndex {
+    fn default() -> Self {
+        Self {
+            bounds: vec![],
+            area: Bounds {
+                min: Vec2::ZERO,
+                max: Vec2::ZERO,
+            },
+            cell_size: Vec2::ONE,
+            columns: 0,
+            rows: 0,
+            cells: vec![],
+        }
+    }
+}
+
+impl SectorIndex {
+    pub fn new(sectors: &[Sector]) -> Self {
+        if sectors.is_empty() {
+            return Self::default();
+        }
+
+        let bounds: Vec<Bounds> = sectors.iter().map(Sector::bounds).collect();
+        let area = bounds[1..]
+            .iter()
+            .fold(bounds[0], |area, bounds| area.union(bounds));
+
+        // Aim for roughly one sector per cell
+        let side = (sectors.len() as f32).sqrt().ceil() as usize;
+        let columns = side.max(1);
+        let rows = side.max(1);
+        let cell_size = ((area.max - area.min) / Vec2::new(columns as f32, rows as f32))
+            .max(Vec2::splat(f32::EPSILON));
+
+        let mut index = Self {
+            bounds,
+            area,
+            cell_size,
+            columns,
+            rows,
+            cells: vec![vec![]; columns * rows],
+        };
+
+        for (sector_id, bounds) in index.bounds.iter().enumerate() {
+            let (min_column, min_row) = index.cell(bounds.min);
+            let (max_column, max_row) = index.cell(bounds.max);
+            for row in min_row..=max_row {
+                for column in min_column..=max_column {
+                    index.cells[row * columns + column].push(sector_id);
+                }
+            }
+        }
+
+        index
+    }
+
+    pub fn bounds(&self, sector_id: SectorId) -> &Bounds {
+        &self.bounds[sector_id]
+    }
+
+    /// Sectors whose bounds contain `pos`, the caller still needs to check
+    /// the sector shape itself.
+    pub fn candidates(&self, pos: Vec2) -> impl Iterator<Item = SectorId> + '_ {
+        let cell = if self.area.contains(pos) {
+            let (column, row) = self.cell(pos);
+            &self.cells[row * self.columns + column][..]
+        } else {
+            &[]
+        };
+
+        cell.iter()
+            .copied()
+            .filter(move |sector_id| self.bounds[*sector_id].contains(pos))
+    }
+
+    fn cell(&self, pos: Vec2) -> (usize, usize) {
+        let cell = ((pos - self.area.min) / self.cell_size).max(Vec2::ZERO);
+        (
+            (cell.x as usize).min(self.columns - 1),
+            (cell.y as usize).min(self.rows - 1),
+        )
+    }
+}
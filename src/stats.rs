@@ -0,0 +1,327 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use glam::{Vec2, Vec3};
+use serde::Serialize;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    canvas::Canvas,
+    font::Font,
+    primitives::{LineDescriptor, RectDescriptor, TextDescriptor},
+};
+
+/// Number of frames the rolling statistics are computed over.
+const HISTORY: usize = 240;
+
+/// How often a summary is written to the log.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Timings, in milliseconds, and counters for a single frame.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct FrameSample {
+    pub sim: f32,
+    pub render: f32,
+    /// Writing the canvas to the GPU and drawing it.
+    pub upload: f32,
+    /// Waiting for the surface, often on vsync, and presenting it.
+    pub present: f32,
+    pub sectors_visited: usize,
+    pub columns_drawn: usize,
+}
+
+impl FrameSample {
+    pub fn total(&self) -> f32 {
+        self.sim + self.render + self.upload + self.present
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct PhaseSummary {
+    pub average: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl PhaseSummary {
+    fn new(mut values: Vec<f32>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        values.sort_by(f32::total_cmp);
+        let percentile = |p: f32| values[((values.len() - 1) as f32 * p).round() as usize];
+
+        Self {
+            average: values.iter().sum::<f32>() / values.len() as f32,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct StatsSummary {
+    pub frames: usize,
+    pub sim: PhaseSummary,
+    pub render: PhaseSummary,
+    pub upload: PhaseSummary,
+    pub present: PhaseSummary,
+    pub total: PhaseSummary,
+    pub sectors_visited: f32,
+    pub columns_drawn: f32,
+}
+
+#[derive(Serialize)]
+struct StatsDump<'a> {
+    summary: StatsSummary,
+    samples: &'a VecDeque<FrameSample>,
+}
+
+/// Rolling per-frame timings for the simulation, software render, texture
+/// upload and present phases, with an optional on-screen graph.
+pub struct FrameStats {
+    samples: VecDeque<FrameSample>,
+    show_overlay: bool,
+    last_log: Instant,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(HISTORY),
+            show_overlay: false,
+            last_log: Instant::now(),
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn push(&mut self, sample: FrameSample) {
+        if self.samples.len() == HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+
+        if self.last_log.elapsed() > LOG_INTERVAL {
+            self.last_log = Instant::now();
+            let summary = self.summary();
+            log::info!(
+                "frame {:.2}ms avg, {:.2}ms p99 (sim {:.2}ms, render {:.2}ms, upload {:.2}ms, present {:.2}ms), {:.1} sectors, {:.0} columns",
+                summary.total.average,
+                summary.total.p99,
+                summary.sim.average,
+                summary.render.average,
+                summary.upload.average,
+                summary.present.average,
+                summary.sectors_visited,
+                summary.columns_drawn,
+            );
+        }
+    }
+
+    pub fn samples(&self) -> &VecDeque<FrameSample> {
+        &self.samples
+    }
+
+    pub fn show_overlay(&self) -> bool {
+        self.show_overlay
+    }
+
+    pub fn summary(&self) -> StatsSummary {
+        let phase = |value: fn(&FrameSample) -> f32| {
+            PhaseSummary::new(self.samples.iter().map(value).collect())
+        };
+        let frames = self.samples.len().max(1) as f32;
+
+        StatsSummary {
+            frames: self.samples.len(),
+            sim: phase(|sample| sample.sim),
+            render: phase(|sample| sample.render),
+            upload: phase(|sample| sample.upload),
+            present: phase(|sample| sample.present),
+            total: phase(FrameSample::total),
+            sectors_visited: self
+                .samples
+                .iter()
+                .map(|sample| sample.sectors_visited as f32)
+                .sum::<f32>()
+                / frames,
+            columns_drawn: self
+                .samples
+                .iter()
+                .map(|sample| sample.columns_drawn as f32)
+                .sum::<f32>()
+                / frames,
+        }
+    }
+
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F3),
+                        ..
+                    },
+                ..
+            } => {
+                self.show_overlay = !self.show_overlay;
+                true
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F4),
+                        ..
+                    },
+                ..
+            } => {
+                let saved = self
+                    .save_csv("frame_stats.csv")
+                    .and_then(|_| self.save_json("frame_stats.json"));
+                match saved {
+                    Ok(_) => log::info!("saved frame_stats.csv and frame_stats.json"),
+                    Err(e) => log::error!("could not save frame stats: {e}"),
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Writes every sample in the history, one row per frame.
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        writeln!(
+            writer,
+            "sim,render,upload,present,total,sectors_visited,columns_drawn"
+        )?;
+        for sample in &self.samples {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                sample.sim,
+                sample.render,
+                sample.upload,
+                sample.present,
+                sample.total(),
+                sample.sectors_visited,
+                sample.columns_drawn,
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the summary followed by every sample in the history.
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(
+            writer,
+            &StatsDump {
+                summary: self.summary(),
+                samples: &self.samples,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Summary text in the top left corner and a graph of the frame history in
+    /// the bottom left, one bar per frame with each phase stacked on top of
+    /// the last in the same colour as its label. The line marks 60fps.
+    pub fn draw_overlay(&self, canvas: &mut Canvas, font: &Font) {
+        const BAR_WIDTH: f32 = 2.0;
+        const PIXELS_PER_MS: f32 = 4.0;
+        const TARGET_MS: f32 = 1000.0 / 60.0;
+
+        let bottom = canvas.height() as f32 - 8.0;
+        let colors = [
+            Vec3::new(0.2, 0.8, 0.2),
+            Vec3::new(0.2, 0.4, 1.0),
+            Vec3::new(1.0, 0.8, 0.1),
+            Vec3::new(1.0, 0.2, 0.2),
+        ];
+
+        let summary = self.summary();
+        let fps = 1000.0 / summary.total.average.max(f32::EPSILON);
+        let lines = [
+            (
+                format!(
+                    "{fps:.0} fps, {:.2}ms avg, {:.2}ms p95, {:.2}ms p99",
+                    summary.total.average, summary.total.p95, summary.total.p99
+                ),
+                Vec3::splat(1.0),
+            ),
+            (format!("sim     {:.2}ms", summary.sim.average), colors[0]),
+            (
+                format!("render  {:.2}ms", summary.render.average),
+                colors[1],
+            ),
+            (
+                format!("upload  {:.2}ms", summary.upload.average),
+                colors[2],
+            ),
+            (
+                format!("present {:.2}ms", summary.present.average),
+                colors[3],
+            ),
+            (
+                format!(
+                    "{:.1} sectors, {:.0} columns",
+                    summary.sectors_visited, summary.columns_drawn
+                ),
+                Vec3::splat(1.0),
+            ),
+        ];
+        for (index, (text, color)) in lines.iter().enumerate() {
+            canvas.draw_text(
+                font,
+                &TextDescriptor {
+                    text,
+                    top_left: Vec2::new(8.0, 8.0 + (index as u32 * font.height()) as f32),
+                    color: *color,
+                    scale: 1,
+                    alpha: 1.0,
+                },
+            );
+        }
+
+        for (index, sample) in self.samples.iter().enumerate() {
+            let x = 8.0 + index as f32 * BAR_WIDTH;
+            let mut top = bottom;
+            for (phase, color) in [sample.sim, sample.render, sample.upload, sample.present]
+                .iter()
+                .zip(colors)
+            {
+                let height = phase * PIXELS_PER_MS;
+                top -= height;
+                canvas.draw_rect(&RectDescriptor {
+                    top_left: Vec2::new(x, top),
+                    size: Vec2::new(BAR_WIDTH, height),
+                    color,
+                });
+            }
+        }
+
+        let target = bottom - TARGET_MS * PIXELS_PER_MS;
+        canvas.draw_line(&LineDescriptor {
+            start: Vec2::new(8.0, target),
+            end: Vec2::new(8.0 + HISTORY as f32 * BAR_WIDTH, target),
+            color: Vec3::splat(1.0),
+            stroke: 1.0,
+        });
+    }
+}
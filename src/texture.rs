@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    f32::consts::TAU,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use glam::{Vec2, Vec3, Vec4};
+use serde::Deserialize;
+
+/// Pixels with alpha, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vec4>,
+}
+
+impl Image {
+    /// `None` unless there are `width` times `height` pixels and at least
+    /// one of them.
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec4>) -> Option<Self> {
+        let count = width.checked_mul(height)?;
+        (count > 0 && usize::try_from(count) == Ok(pixels.len())).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// A single colour.
+    pub fn solid(color: Vec4) -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            pixels: vec![color],
+        }
+    }
+
+    /// Reads any image format the `image` crate knows.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| invalid(&format!("could not read {}: {e}", path.display())))?
+            .into_rgba8();
+        let pixels = image
+            .pixels()
+            .map(|pixel| Vec4::from_array(pixel.0.map(|channel| channel as f32 / 255.0)))
+            .collect();
+        Self::new(image.width(), image.height(), pixels)
+            .ok_or_else(|| invalid(&format!("{} is empty", path.display())))
+    }
+
+    /// Builds an image from a function of the position in it, both from 0
+    /// to 1.
+    fn generate(width: u32, height: u32, pixel: impl Fn(f32, f32) -> Vec4) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x as f32 / width as f32, y as f32 / height as f32))
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Pixel at `u` across and `v` down, the image repeating every 1 both
+    /// ways.
+    pub fn sample(&self, u: f32, v: f32) -> Vec4 {
+        let x = ((u.rem_euclid(1.0) * self.width as f32) as u32).min(self.width - 1);
+        let y = ((v.rem_euclid(1.0) * self.height as f32) as u32).min(self.height - 1);
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+/// An image, or frames of one, laid over walls and decals, which can move
+/// along them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    frames: Vec<Image>,
+    /// Seconds each frame shows for.
+    frame_time: f32,
+    /// World units the texture moves along and up the wall every second.
+    scroll: Vec2,
+    /// World units one repeat of the image covers across and up.
+    size: Vec2,
+}
+
+impl Texture {
+    /// `None` without any frames.
+    pub fn new(frames: Vec<Image>, frame_time: f32, scroll: Vec2, size: Vec2) -> Option<Self> {
+        (!frames.is_empty()).then_some(Self {
+            frames,
+            frame_time,
+            scroll,
+            size,
+        })
+    }
+
+    /// Colour at `along` a line from its first point and `height` above
+    /// the floor after `time` seconds of play.
+    pub fn sample(&self, along: f32, height: f32, time: f32) -> Vec4 {
+        let moved = self.scroll * time;
+        self.frame(time).sample(
+            (along - moved.x) / self.size.x,
+            -(height - moved.y) / self.size.y,
+        )
+    }
+
+    /// Colour at `u` across and `v` down a decal, ignoring `size` and
+    /// `scroll` since the decal's size and place are its own.
+    pub fn sample_decal(&self, u: f32, v: f32, time: f32) -> Vec4 {
+        self.frame(time).sample(u, v)
+    }
+
+    fn frame(&self, time: f32) -> &Image {
+        if self.frame_time <= 0.0 {
+            return &self.frames[0];
+        }
+        &self.frames[(time / self.frame_time) as usize % self.frames.len()]
+    }
+}
+
+/// Every texture lines and decals can name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Textures {
+    textures: HashMap<String, Texture>,
+}
+
+/// How `assets/textures.json` describes a texture, frames are image files
+/// next to it.
+#[derive(Deserialize)]
+struct TextureDef {
+    frames: Vec<PathBuf>,
+    #[serde(default)]
+    frame_time: f32,
+    #[serde(default)]
+    scroll: Vec2,
+    #[serde(default = "TextureDef::default_size")]
+    size: Vec2,
+}
+
+impl TextureDef {
+    fn default_size() -> Vec2 {
+        Vec2::ONE
+    }
+}
+
+impl Default for Textures {
+    /// A few drawn in code, so there's something to use without any files:
+    /// `bricks`, `slime`, which flows and shimmers, and `bullet_hole`.
+    fn default() -> Self {
+        let bricks = Image::generate(16, 16, |u, v| {
+            let row = (v * 2.0) as u32;
+            let u = (u + row as f32 * 0.5).rem_euclid(1.0);
+            let mortar = v.rem_euclid(0.5) < 0.07 || u < 0.05;
+            if mortar {
+                Vec4::new(0.6, 0.6, 0.55, 1.0)
+            } else {
+                Vec4::new(0.55, 0.25, 0.18, 1.0)
+            }
+        });
+        let slime = (0..3)
+            .map(|frame| {
+                let phase = frame as f32 / 3.0 * TAU;
+                Image::generate(16, 16, |u, v| {
+                    let wave = ((u * 2.0 + v) * TAU + phase).sin() * 0.5 + 0.5;
+                    Vec3::new(0.1, 0.4, 0.1)
+                        .lerp(Vec3::new(0.4, 0.9, 0.3), wave)
+                        .extend(1.0)
+                })
+            })
+            .collect();
+        let bullet_hole = Image::generate(8, 8, |u, v| {
+            let distance = Vec2::new(u - 0.4375, v - 0.4375).length();
+            let alpha = if distance < 0.3 { 1.0 } else { 0.0 };
+            Vec4::new(0.05, 0.05, 0.05, alpha)
+        });
+
+        let textures = [
+            (
+                "bricks",
+                Texture::new(vec![bricks], 0.0, Vec2::ZERO, Vec2::new(1.0, 0.5)),
+            ),
+            (
+                "slime",
+                Texture::new(slime, 0.25, Vec2::new(0.25, 0.0), Vec2::ONE),
+            ),
+            (
+                "bullet_hole",
+                Texture::new(vec![bullet_hole], 0.0, Vec2::ZERO, Vec2::ONE),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, texture)| Some((name.to_string(), texture?)))
+        .collect();
+        Self { textures }
+    }
+}
+
+impl Textures {
+    /// The built-in textures along with those described in a JSON file,
+    /// which replace built-in ones of the same name.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let defs: HashMap<String, TextureDef> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let mut textures = Self::default();
+        for (name, def) in defs {
+            let frames = def
+                .frames
+                .iter()
+                .map(|frame| Image::load(dir.join(frame)))
+                .collect::<io::Result<_>>()?;
+            let texture = Texture::new(frames, def.frame_time, def.scroll, def.size)
+                .ok_or_else(|| invalid(&format!("{name} has no frames")))?;
+            textures.insert(&name, texture);
+        }
+        Ok(textures)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Texture> {
+        self.textures.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, texture: Texture) {
+        self.textures.insert(name.to_string(), texture);
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+
+use glam::Vec2;
+
+use crate::{
+    level::{signed_area, LevelState, Mask, MaskPattern, SectorId, Wall},
+    mover::{MoverKind, Trigger},
+};
+
+/// Points closer than this are treated as the same point.
+const EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The level works but probably not as intended.
+    Warning,
+    /// The level would draw wrongly or crash.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub sector: SectorId,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.line {
+            Some(line) => write!(
+                f,
+                "{severity}: sector {} line {line}: {}",
+                self.sector, self.message
+            ),
+            None => write!(f, "{severity}: sector {}: {}", self.sector, self.message),
+        }
+    }
+}
+
+/// Checks the assumptions the renderer and the rest of the game make about a
+/// level: sectors are convex, wound the right way and closed, lines point at
+/// real points, every portal leads somewhere with a portal back along the
+/// same edge, and movers move sectors that exist.
+pub fn validate(level: &LevelState) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+    let sectors = level.sectors();
+
+    for (sector_id, sector) in sectors.iter().enumerate() {
+        let mut issue = |severity, line, message: String| {
+            issues.push(ValidationIssue {
+                severity,
+                sector: sector_id,
+                line,
+                message,
+            })
+        };
+
+        let points = sector.points();
+        if points.len() < 3 {
+            issue(
+                Severity::Error,
+                None,
+                format!("has {} points, at least 3 are needed", points.len()),
+            );
+            continue;
+        }
+
+        // Doors start shut
+        let door = level
+            .movers()
+            .iter()
+            .any(|mover| mover.sector == sector_id && matches!(mover.kind, MoverKind::Door { .. }));
+        if sector.height() <= 0.0 && !door {
+            issue(
+                Severity::Warning,
+                None,
+                format!("height {} is not above the floor", sector.height()),
+            );
+        }
+
+        if signed_area(points) >= 0.0 {
+            issue(
+                Severity::Error,
+                None,
+                "points go anticlockwise or have no area".to_string(),
+            );
+        } else if !is_convex(points) {
+            issue(Severity::Error, None, "is not convex".to_string());
+        }
+
+        let mut edges = vec![false; points.len()];
+        for (line_index, line) in sector.lines().iter().enumerate() {
+            let (point_1, point_2) = line.point_ids();
+            if point_1 >= points.len() || point_2 >= points.len() {
+                issue(
+                    Severity::Error,
+                    Some(line_index),
+                    format!("refers to a point that doesn't exist ({point_1}, {point_2})"),
+                );
+                continue;
+            }
+
+            if (point_1 + 1) % points.len() == point_2 {
+                edges[point_1] = true;
+            } else if (point_2 + 1) % points.len() == point_1 {
+                edges[point_2] = true;
+            }
+
+            let (start, end) = (points[point_1], points[point_2]);
+            if start.distance(end) < EPSILON {
+                issue(
+                    Severity::Warning,
+                    Some(line_index),
+                    "has no length".to_string(),
+                );
+            }
+
+            // Where the edge is on the far side
+            let (start, end) = match line.warp() {
+                Some(warp) => (warp.apply(start), warp.apply(end)),
+                None => (start, end),
+            };
+            if let Wall::Portal(target) = line.wall_type() {
+                if target >= sectors.len() {
+                    issue(
+                        Severity::Error,
+                        Some(line_index),
+                        format!("is a portal to sector {target}, which doesn't exist"),
+                    );
+                } else if target == sector_id && line.warp().is_none() {
+                    issue(
+                        Severity::Error,
+                        Some(line_index),
+                        "is a portal to its own sector".to_string(),
+                    );
+                } else if !has_portal(level, target, sector_id, start, end) {
+                    issue(
+                        Severity::Warning,
+                        Some(line_index),
+                        format!("is a portal to sector {target}, which has no portal back along the same edge"),
+                    );
+                } else if let Some(warp) = line.warp() {
+                    let back = level
+                        .opposite_line(sector_id, line_index)
+                        .and_then(|(target, back)| level.sector(target).lines()[back].warp());
+                    if (back.map_or(0.0, |back| back.height) + warp.height).abs() > EPSILON {
+                        issue(
+                            Severity::Warning,
+                            Some(line_index),
+                            "warps heights differently to the portal back".to_string(),
+                        );
+                    }
+                }
+            } else if line.warp().is_some() {
+                issue(
+                    Severity::Warning,
+                    Some(line_index),
+                    "has a warp but is a wall".to_string(),
+                );
+            }
+
+            match line.mask() {
+                Some(_) if !matches!(line.wall_type(), Wall::Portal(_)) => issue(
+                    Severity::Warning,
+                    Some(line_index),
+                    "has a mask but isn't a portal".to_string(),
+                ),
+                Some(Mask {
+                    pattern: MaskPattern::Bars { spacing, .. } | MaskPattern::Grid { spacing, .. },
+                    ..
+                }) if spacing <= 0.0 => issue(
+                    Severity::Warning,
+                    Some(line_index),
+                    "has a mask whose bars are no distance apart".to_string(),
+                ),
+                _ => {}
+            }
+        }
+
+        for (point, covered) in edges.iter().enumerate() {
+            if !covered {
+                issue(
+                    Severity::Warning,
+                    None,
+                    format!(
+                        "edge from point {point} to {} has no wall or portal",
+                        (point + 1) % points.len()
+                    ),
+                );
+            }
+        }
+
+        let centroid = sector.centroid();
+        let overlapping = (0..sectors.len()).find(|other_id| {
+            let other = &sectors[*other_id];
+            *other_id != sector_id && other.points().len() >= 3 && other.contains(centroid)
+        });
+        if let Some(other) = overlapping {
+            issue(Severity::Warning, None, format!("overlaps sector {other}"));
+        }
+    }
+
+    for (index, mover) in level.movers().iter().enumerate() {
+        let mut issue = |message: String| {
+            issues.push(ValidationIssue {
+                severity: Severity::Error,
+                sector: mover.sector,
+                line: None,
+                message,
+            })
+        };
+        if mover.sector >= sectors.len() {
+            issue(format!("is moved by mover {index} but doesn't exist"));
+        }
+        if let Trigger::Enter(trigger) = mover.trigger {
+            if trigger >= sectors.len() {
+                issue(format!(
+                    "is moved by mover {index}, which is set off by sector {trigger} that doesn't exist"
+                ));
+            }
+        }
+        if mover.speed <= 0.0 {
+            issue(format!(
+                "is moved by mover {index}, whose speed isn't above zero"
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Every turn goes the same way as the winding, straight runs are allowed so
+/// split lines don't count against a sector.
+fn is_convex(points: &[Vec2]) -> bool {
+    (0..points.len()).all(|index| {
+        let a = points[index];
+        let b = points[(index + 1) % points.len()];
+        let c = points[(index + 2) % points.len()];
+        (b - a).perp_dot(c - b) <= EPSILON
+    })
+}
+
+fn has_portal(
+    level: &LevelState,
+    sector_id: SectorId,
+    target: SectorId,
+    start: Vec2,
+    end: Vec2,
+) -> bool {
+    let sector = level.sector(sector_id);
+    sector.lines().iter().any(|line| {
+        let (point_1, point_2) = line.point_ids();
+        let (Some(a), Some(b)) = (sector.points().get(point_1), sector.points().get(point_2))
+        else {
+            return false;
+        };
+        line.wall_type() == Wall::Portal(target)
+            && ((a.distance(start) < EPSILON && b.distance(end) < EPSILON)
+                || (a.distance(end) < EPSILON && b.distance(start) < EPSILON))
+    })
+}
@@ -0,0 +1,515 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use glam::{DVec2, Vec2, Vec3};
+
+use crate::level::{LevelState, Line, Sector, Wall};
+
+/// Engine units per Doom map unit. Doom's eye height of 41 comes out a little
+/// under the engine's 1.5.
+pub const DOOM_SCALE: f32 = 1.0 / 32.0;
+
+/// Lumps that can follow a map marker, in any order.
+const MAP_LUMPS: [&str; 10] = [
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP",
+];
+
+/// Thing type of the first player's start.
+const PLAYER_START: u16 = 1;
+
+/// Wall colour used where there is no texture to base one on.
+const DEFAULT_WALL: Vec3 = Vec3::new(0.6, 0.6, 0.6);
+
+/// Map units, points closer than this are the same point.
+const EPSILON: f64 = 1.0 / 64.0;
+
+/// A Doom IWAD or PWAD: the file and its directory of lumps.
+pub struct Wad {
+    bytes: Vec<u8>,
+    lumps: Vec<Lump>,
+}
+
+struct Lump {
+    name: String,
+    offset: usize,
+    size: usize,
+}
+
+/// A map converted from a WAD, with the first player start if it has one.
+pub struct ImportedMap {
+    pub level: LevelState,
+    pub start: Option<(Vec2, Vec3)>,
+}
+
+struct Linedef {
+    right: Option<usize>,
+    left: Option<usize>,
+}
+
+struct Sidedef {
+    upper: String,
+    lower: String,
+    middle: String,
+    sector: usize,
+}
+
+struct DoomSector {
+    floor: i16,
+    ceiling: i16,
+}
+
+struct Seg {
+    v1: usize,
+    v2: usize,
+    linedef: usize,
+    /// Zero when the seg runs the same way as its linedef.
+    direction: i16,
+}
+
+struct Subsector {
+    count: usize,
+    first: usize,
+}
+
+struct Node {
+    origin: DVec2,
+    direction: DVec2,
+    /// Right then left, the top bit marks a subsector.
+    children: [u16; 2],
+}
+
+impl Wad {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        if !bytes.starts_with(b"IWAD") && !bytes.starts_with(b"PWAD") {
+            return Err(invalid("not a WAD file"));
+        }
+        let count = read_usize(&bytes, 4)?;
+        let directory = read_usize(&bytes, 8)?;
+        // Each entry takes 16 bytes, so the file bounds how many there are
+        let room = bytes
+            .len()
+            .checked_sub(directory)
+            .ok_or_else(|| invalid("directory starts past the end of the file"))?
+            / 16;
+        if count > room {
+            return Err(invalid("truncated directory"));
+        }
+
+        let mut lumps = Vec::with_capacity(count);
+        for index in 0..count {
+            let entry = directory + index * 16;
+            let offset = read_usize(&bytes, entry)?;
+            let size = read_usize(&bytes, entry + 4)?;
+            let name = &bytes[entry + 8..entry + 16];
+            if offset.checked_add(size).is_none_or(|end| end > bytes.len()) {
+                return Err(invalid("lump runs past the end of the file"));
+            }
+            lumps.push(Lump {
+                name: lump_name(name),
+                offset,
+                size,
+            });
+        }
+
+        Ok(Self { bytes, lumps })
+    }
+
+    /// Names of the maps in the WAD, like `E1M1` or `MAP01`.
+    pub fn maps(&self) -> Vec<&str> {
+        self.lumps
+            .windows(2)
+            .filter(|pair| pair[1].name == "THINGS")
+            .map(|pair| pair[0].name.as_str())
+            .collect()
+    }
+
+    /// Converts a map to a level. Every subsector becomes a sector: its shape
+    /// is found by clipping the map's bounds with the BSP partition lines on
+    /// the way down to it, then with its own segs. Edges shared by two
+    /// subsectors become portals, the rest are walls.
+    pub fn import_map(&self, name: &str) -> io::Result<ImportedMap> {
+        let marker = self
+            .lumps
+            .iter()
+            .position(|lump| lump.name == name)
+            .ok_or_else(|| invalid(&format!("no map called {name}")))?;
+        let map_lump = |lump_name: &str| {
+            self.lumps[marker + 1..]
+                .iter()
+                .take_while(|lump| MAP_LUMPS.contains(&lump.name.as_str()))
+                .find(|lump| lump.name == lump_name)
+                .map(|lump| &self.bytes[lump.offset..lump.offset + lump.size])
+        };
+        let required = |lump_name: &str| {
+            map_lump(lump_name).ok_or_else(|| invalid(&format!("{name} has no {lump_name}")))
+        };
+
+        let vertices: Vec<DVec2> = records::<4>(required("VERTEXES")?)
+            .map(|record| DVec2::new(i16_at(record, 0) as f64, i16_at(record, 2) as f64))
+            .collect();
+        let linedefs: Vec<Linedef> = records::<14>(required("LINEDEFS")?)
+            .map(|record| Linedef {
+                right: side_index(u16_at(record, 10)),
+                left: side_index(u16_at(record, 12)),
+            })
+            .collect();
+        let sidedefs: Vec<Sidedef> = records::<30>(required("SIDEDEFS")?)
+            .map(|record| Sidedef {
+                upper: lump_name(&record[4..12]),
+                lower: lump_name(&record[12..20]),
+                middle: lump_name(&record[20..28]),
+                sector: u16_at(record, 28) as usize,
+            })
+            .collect();
+        let sectors: Vec<DoomSector> = records::<26>(required("SECTORS")?)
+            .map(|record| DoomSector {
+                floor: i16_at(record, 0),
+                ceiling: i16_at(record, 2),
+            })
+            .collect();
+        let segs: Vec<Seg> = records::<12>(required("SEGS")?)
+            .map(|record| Seg {
+                v1: u16_at(record, 0) as usize,
+                v2: u16_at(record, 2) as usize,
+                linedef: u16_at(record, 6) as usize,
+                direction: i16_at(record, 8),
+            })
+            .collect();
+        let subsectors: Vec<Subsector> = records::<4>(required("SSECTORS")?)
+            .map(|record| Subsector {
+                count: u16_at(record, 0) as usize,
+                first: u16_at(record, 2) as usize,
+            })
+            .collect();
+        let nodes: Vec<Node> = records::<28>(map_lump("NODES").unwrap_or_default())
+            .map(|record| Node {
+                origin: DVec2::new(i16_at(record, 0) as f64, i16_at(record, 2) as f64),
+                direction: DVec2::new(i16_at(record, 4) as f64, i16_at(record, 6) as f64),
+                children: [u16_at(record, 24), u16_at(record, 26)],
+            })
+            .collect();
+
+        let in_range = segs.iter().all(|seg| {
+            seg.v1 < vertices.len() && seg.v2 < vertices.len() && seg.linedef < linedefs.len()
+        }) && subsectors
+            .iter()
+            .all(|subsector| subsector.first + subsector.count <= segs.len())
+            && sidedefs
+                .iter()
+                .all(|sidedef| sidedef.sector < sectors.len())
+            && linedefs
+                .iter()
+                .flat_map(|linedef| [linedef.right, linedef.left])
+                .flatten()
+                .all(|side| side < sidedefs.len());
+        if !in_range || vertices.is_empty() {
+            return Err(invalid(&format!(
+                "{name} refers to things that don't exist"
+            )));
+        }
+
+        // The side of the linedef each seg is on, which is the side facing
+        // into its subsector
+        let seg_side = |seg: &Seg| {
+            let linedef = &linedefs[seg.linedef];
+            if seg.direction == 0 {
+                linedef.right
+            } else {
+                linedef.left
+            }
+        };
+
+        let polygons = subsector_polygons(&vertices, &segs, &subsectors, &nodes)?;
+        let polygons = split_t_junctions(polygons);
+
+        // Subsectors that came out empty are dropped, the rest are numbered in
+        // order
+        let mut sector_ids = vec![None; polygons.len()];
+        let mut next_id = 0;
+        for (subsector, polygon) in polygons.iter().enumerate() {
+            if polygon.len() >= 3 && subsectors[subsector].count > 0 {
+                sector_ids[subsector] = Some(next_id);
+                next_id += 1;
+            }
+        }
+
+        let mut edges = HashMap::new();
+        for (subsector, polygon) in polygons.iter().enumerate() {
+            if sector_ids[subsector].is_some() {
+                for index in 0..polygon.len() {
+                    let next = polygon[(index + 1) % polygon.len()];
+                    edges.insert((key(polygon[index]), key(next)), subsector);
+                }
+            }
+        }
+
+        let mut level_sectors = vec![];
+        for (subsector_index, polygon) in polygons.iter().enumerate() {
+            if sector_ids[subsector_index].is_none() {
+                continue;
+            }
+            let subsector = &subsectors[subsector_index];
+            let subsector_segs = &segs[subsector.first..subsector.first + subsector.count];
+            let doom_sector = subsector_segs
+                .iter()
+                .find_map(seg_side)
+                .map(|side| &sectors[sidedefs[side].sector])
+                .ok_or_else(|| invalid(&format!("subsector {subsector_index} has no sector")))?;
+
+            let lines = (0..polygon.len())
+                .map(|index| {
+                    let (start, end) = (polygon[index], polygon[(index + 1) % polygon.len()]);
+                    let neighbour = edges
+                        .get(&(key(end), key(start)))
+                        .and_then(|neighbour| sector_ids[*neighbour]);
+                    let wall = match neighbour {
+                        Some(sector_id) => Wall::Portal(sector_id),
+                        None => {
+                            // The seg this edge lies along, if any, gives the
+                            // wall its colour
+                            let texture = subsector_segs
+                                .iter()
+                                .find(|seg| {
+                                    let (a, b) = (vertices[seg.v1], vertices[seg.v2]);
+                                    line_distance(start, a, b) < 0.5
+                                        && line_distance(end, a, b) < 0.5
+                                })
+                                .and_then(seg_side)
+                                .map(|side| &sidedefs[side])
+                                .and_then(Sidedef::texture);
+                            Wall::Solid(texture.map_or(DEFAULT_WALL, texture_colour))
+                        }
+                    };
+                    Line::new(wall, index, (index + 1) % polygon.len())
+                })
+                .collect();
+
+            // Flipping y turns Doom's anticlockwise polygons into the
+            // clockwise ones sectors use
+            let points = polygon.iter().map(|point| to_level(*point)).collect();
+            level_sectors.push(Sector::new(
+                points,
+                lines,
+                doom_sector.floor as f32 * DOOM_SCALE,
+                (doom_sector.ceiling as f32 - doom_sector.floor as f32) * DOOM_SCALE,
+            ));
+        }
+
+        let start = map_lump("THINGS").and_then(|things| {
+            records::<10>(things)
+                .find(|record| u16_at(*record, 6) == PLAYER_START)
+                .map(|record| {
+                    let pos = DVec2::new(i16_at(record, 0) as f64, i16_at(record, 2) as f64);
+                    let angle = (i16_at(record, 4) as f32).to_radians();
+                    (to_level(pos), Vec3::new(angle.cos(), -angle.sin(), 0.0))
+                })
+        });
+
+        Ok(ImportedMap {
+            level: LevelState::new(level_sectors),
+            start,
+        })
+    }
+}
+
+impl Sidedef {
+    /// The texture most of the wall will show, `-` means none.
+    fn texture(&self) -> Option<&str> {
+        [&self.middle, &self.upper, &self.lower]
+            .into_iter()
+            .find(|name| name.as_str() != "-")
+            .map(String::as_str)
+    }
+}
+
+/// Walks the BSP tree clipping a box around the whole map, so each subsector
+/// ends up with the convex area the partition lines leave it, then trims it
+/// to the inside of its segs.
+fn subsector_polygons(
+    vertices: &[DVec2],
+    segs: &[Seg],
+    subsectors: &[Subsector],
+    nodes: &[Node],
+) -> io::Result<Vec<Vec<DVec2>>> {
+    let (min, max) = vertices
+        .iter()
+        .fold((vertices[0], vertices[0]), |(min, max), vertex| {
+            (min.min(*vertex), max.max(*vertex))
+        });
+    let (min, max) = (min - 64.0, max + 64.0);
+    let bounds = vec![min, DVec2::new(max.x, min.y), max, DVec2::new(min.x, max.y)];
+
+    let mut polygons = vec![vec![]; subsectors.len()];
+    let root = if nodes.is_empty() {
+        0x8000
+    } else {
+        (nodes.len() - 1) as u16
+    };
+    let mut stack = vec![(root, bounds)];
+    while let Some((child, polygon)) = stack.pop() {
+        if child & 0x8000 != 0 {
+            let index = (child & 0x7fff) as usize;
+            let subsector = subsectors
+                .get(index)
+                .ok_or_else(|| invalid(&format!("node refers to missing subsector {index}")))?;
+            let mut polygon = polygon;
+            for seg in &segs[subsector.first..subsector.first + subsector.count] {
+                let (a, b) = (vertices[seg.v1], vertices[seg.v2]);
+                polygon = clip(&polygon, |point| -(b - a).perp_dot(point - a));
+            }
+            polygons[index] = polygon;
+            continue;
+        }
+
+        let node = nodes
+            .get(child as usize)
+            .ok_or_else(|| invalid(&format!("node refers to missing node {child}")))?;
+        // Node builders number children before their parents, anything else
+        // could lead back round to a node already being walked
+        if let Some(loop_child) = node
+            .children
+            .iter()
+            .find(|next| **next & 0x8000 == 0 && **next >= child)
+        {
+            return Err(invalid(&format!(
+                "node {child} has node {loop_child} as a child, which isn't before it"
+            )));
+        }
+        let side = |point: DVec2| -node.direction.perp_dot(point - node.origin);
+        stack.push((node.children[0], clip(&polygon, side)));
+        stack.push((node.children[1], clip(&polygon, |point| -side(point))));
+    }
+
+    Ok(polygons)
+}
+
+/// The part of a convex polygon where `side` is not negative.
+fn clip(polygon: &[DVec2], side: impl Fn(DVec2) -> f64) -> Vec<DVec2> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for index in 0..polygon.len() {
+        let (a, b) = (polygon[index], polygon[(index + 1) % polygon.len()]);
+        let (side_a, side_b) = (side(a), side(b));
+        if side_a >= -EPSILON {
+            clipped.push(a);
+        }
+        if (side_a > EPSILON && side_b < -EPSILON) || (side_a < -EPSILON && side_b > EPSILON) {
+            clipped.push(a + (b - a) * (side_a / (side_a - side_b)));
+        }
+    }
+
+    clipped.dedup_by(|a, b| a.distance(*b) < EPSILON);
+    while clipped.len() > 1 && clipped[0].distance(clipped[clipped.len() - 1]) < EPSILON {
+        clipped.pop();
+    }
+    if clipped.len() < 3 {
+        clipped.clear();
+    }
+    clipped
+}
+
+/// Adds a point wherever a corner of one polygon touches the middle of
+/// another's edge, so neighbours share whole edges and can be joined with
+/// portals.
+fn split_t_junctions(polygons: Vec<Vec<DVec2>>) -> Vec<Vec<DVec2>> {
+    let corners: Vec<DVec2> = polygons.iter().flatten().copied().collect();
+
+    polygons
+        .iter()
+        .map(|polygon| {
+            let mut split = vec![];
+            for index in 0..polygon.len() {
+                let (a, b) = (polygon[index], polygon[(index + 1) % polygon.len()]);
+                let (min, max) = (a.min(b) - EPSILON, a.max(b) + EPSILON);
+                let length = a.distance(b);
+                let mut on_edge: Vec<(f64, DVec2)> = corners
+                    .iter()
+                    .filter(|corner| corner.cmpge(min).all() && corner.cmple(max).all())
+                    .filter(|corner| line_distance(**corner, a, b) < EPSILON)
+                    .map(|corner| ((*corner - a).dot(b - a) / length, *corner))
+                    .filter(|(along, _)| *along > EPSILON && *along < length - EPSILON)
+                    .collect();
+                on_edge.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+                split.push(a);
+                for (_, corner) in on_edge {
+                    if split
+                        .last()
+                        .is_none_or(|last| last.distance(corner) >= EPSILON)
+                    {
+                        split.push(corner);
+                    }
+                }
+            }
+            split
+        })
+        .collect()
+}
+
+fn line_distance(point: DVec2, a: DVec2, b: DVec2) -> f64 {
+    (b - a).perp_dot(point - a).abs() / a.distance(b).max(f64::EPSILON)
+}
+
+/// Rounds a point so the same corner computed twice compares equal.
+fn key(point: DVec2) -> (i64, i64) {
+    let point = (point * 16.0).round();
+    (point.x as i64, point.y as i64)
+}
+
+fn to_level(point: DVec2) -> Vec2 {
+    Vec2::new(point.x as f32, -point.y as f32) * DOOM_SCALE
+}
+
+/// A stable colour for a texture name, until walls can be textured.
+fn texture_colour(name: &str) -> Vec3 {
+    let hash = name.bytes().fold(0x811c9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    let channel = |shift: u32| 0.35 + ((hash >> shift) & 0xff) as f32 / 255.0 * 0.65;
+    Vec3::new(channel(0), channel(8), channel(16))
+}
+
+fn records<const N: usize>(lump: &[u8]) -> impl Iterator<Item = &[u8; N]> {
+    lump.as_chunks::<N>().0.iter()
+}
+
+fn side_index(side: u16) -> Option<usize> {
+    (side != 0xffff).then_some(side as usize)
+}
+
+fn lump_name(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|byte| **byte != 0)
+        .map(|byte| byte.to_ascii_uppercase() as char)
+        .collect()
+}
+
+fn i16_at(record: &[u8], offset: usize) -> i16 {
+    i16::from_le_bytes([record[offset], record[offset + 1]])
+}
+
+fn u16_at(record: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([record[offset], record[offset + 1]])
+}
+
+/// A count, offset or size, which the format stores signed.
+fn read_usize(bytes: &[u8], offset: usize) -> io::Result<usize> {
+    let field = bytes
+        .get(offset..offset + 4)
+        .map(|field| i32::from_le_bytes(field.try_into().unwrap()))
+        .ok_or_else(|| invalid("truncated header"))?;
+    usize::try_from(field).map_err(|_| invalid("negative count, offset or size"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,191 @@
+use std::{fs, io, path::Path};
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{canvas::Canvas, primitives::RectDescriptor, scene::Sprite};
+
+/// Weapons used when `assets/weapons.json` can't be loaded.
+const BUILT_IN: &str = include_str!("../assets/weapons.json");
+
+/// Seconds the muzzle flash shows for after a shot.
+const FLASH_TIME: f32 = 0.06;
+
+/// Seconds a weapon takes to settle back after its recoil.
+const RECOIL_TIME: f32 = 0.15;
+
+/// Projectiles that haven't hit anything after this many seconds are gone.
+const PROJECTILE_LIFETIME: f32 = 10.0;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeaponDef {
+    pub name: String,
+    /// Seconds between shots.
+    pub cooldown: f32,
+    /// Ammo used by every shot, none for melee weapons.
+    pub ammo: i32,
+    pub fire: Fire,
+    pub color: Vec3,
+    /// Size of the weapon sprite on a 200 pixel tall screen.
+    pub size: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Fire {
+    /// Instant traces, `pellets` of them spread over `spread` degrees.
+    Hitscan {
+        damage: i32,
+        range: f32,
+        spread: f32,
+        pellets: u32,
+        /// Leaves bullet holes in the walls it hits.
+        #[serde(default)]
+        holes: bool,
+    },
+    /// A shot that flies at `speed` units per second until it hits.
+    Projectile {
+        damage: i32,
+        speed: f32,
+        radius: f32,
+        color: Vec3,
+    },
+}
+
+/// The weapons the player carries, selected by slot in the order they're
+/// listed, and the one in hand.
+pub struct Weapons {
+    defs: Vec<WeaponDef>,
+    selected: usize,
+    cooldown: f32,
+    since_fired: f32,
+}
+
+impl Default for Weapons {
+    fn default() -> Self {
+        Self::parse(BUILT_IN).expect("the built-in weapons are valid")
+    }
+}
+
+impl Weapons {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(json: &str) -> io::Result<Self> {
+        let defs: Vec<WeaponDef> = serde_json::from_str(json)?;
+        if defs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "at least one weapon is needed",
+            ));
+        }
+        Ok(Self {
+            defs,
+            selected: 0,
+            cooldown: 0.0,
+            since_fired: f32::INFINITY,
+        })
+    }
+
+    pub fn defs(&self) -> &[WeaponDef] {
+        &self.defs
+    }
+
+    pub fn selected(&self) -> &WeaponDef {
+        &self.defs[self.selected]
+    }
+
+    /// Switches to the weapon in `slot`, counting from zero, if there is one.
+    pub fn select(&mut self, slot: usize) -> bool {
+        if slot >= self.defs.len() {
+            return false;
+        }
+        self.selected = slot;
+        true
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.defs.len();
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.cooldown -= delta;
+        self.since_fired += delta;
+    }
+
+    /// Fires the weapon in hand if it's ready and there's enough `ammo`,
+    /// taking what the shot uses.
+    pub fn fire(&mut self, ammo: &mut i32) -> Option<Fire> {
+        let def = &self.defs[self.selected];
+        if self.cooldown > 0.0 || *ammo < def.ammo {
+            return None;
+        }
+        *ammo -= def.ammo;
+        self.cooldown = def.cooldown;
+        self.since_fired = 0.0;
+        Some(def.fire)
+    }
+
+    /// Draws the weapon in hand at the bottom middle of the screen, kicked
+    /// down after a shot with the flash above it.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let def = self.selected();
+        let scale = canvas.height() as f32 / 200.0;
+        let size = def.size * scale;
+        let recoil = (1.0 - self.since_fired / RECOIL_TIME).max(0.0) * size.y * 0.2;
+        let top_left = Vec2::new(
+            (canvas.width() as f32 - size.x) / 2.0,
+            canvas.height() as f32 - size.y + recoil,
+        );
+
+        if self.since_fired < FLASH_TIME {
+            let flash = Vec2::new(size.x * 0.8, size.x * 0.5);
+            canvas.draw_rect(&RectDescriptor {
+                top_left: Vec2::new(
+                    (canvas.width() as f32 - flash.x) / 2.0,
+                    top_left.y - flash.y,
+                ),
+                size: flash,
+                color: Vec3::new(1.0, 0.9, 0.4),
+            });
+        }
+        canvas.draw_rect(&RectDescriptor {
+            top_left,
+            size,
+            color: def.color,
+        });
+        // Darker strip down the middle so the shape reads as a barrel
+        canvas.draw_rect(&RectDescriptor {
+            top_left: top_left + Vec2::new(size.x * 0.35, 0.0),
+            size: Vec2::new(size.x * 0.3, size.y),
+            color: def.color * 0.6,
+        });
+    }
+}
+
+/// A shot in flight, at the height it was fired from.
+#[derive(Debug, Clone)]
+pub struct Projectile {
+    pub pos: Vec3,
+    pub velocity: Vec2,
+    pub damage: i32,
+    pub radius: f32,
+    pub color: Vec3,
+    pub age: f32,
+}
+
+impl Projectile {
+    pub fn is_spent(&self) -> bool {
+        self.age > PROJECTILE_LIFETIME
+    }
+
+    pub fn sprite(&self) -> Sprite {
+        Sprite {
+            pos: self.pos.truncate(),
+            base_height: self.pos.z - self.radius,
+            width: self.radius * 2.0,
+            height: self.radius * 2.0,
+            color: self.color,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{plain_room, run, GREY};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::GameState,
+    level::{LevelState, Wall},
+};
+
+/// A room south of the origin and one north of it, the line between them
+/// `between` on both sides and the north room's floor at `floor`.
+fn level(between: Wall, back: Wall, floor: f32) -> LevelState {
+    let mut north = plain_room(
+        Vec2::new(-1.0, 0.0),
+        Vec2::new(1.0, 2.0),
+        [GREY, GREY, GREY, back],
+    );
+    north.set_base_height(floor);
+    LevelState::new(vec![
+        plain_room(
+            Vec2::new(-1.0, -2.0),
+            Vec2::new(1.0, 0.0),
+            [GREY, between, GREY, GREY],
+        ),
+        north,
+    ])
+}
+
+fn walk_north(level: LevelState) -> GameState {
+    let mut game_state = GameState::new(level, Vec2::new(0.0, -1.0), Vec3::Y);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 2.0);
+    game_state
+}
+
+#[test]
+fn walls_between_sectors_stop_the_player() {
+    let game_state = walk_north(level(GREY, GREY, 0.0));
+    assert_eq!(game_state.find_current_sector(), Some(0));
+    assert!(game_state.pos().y < 0.0);
+}
+
+#[test]
+fn portals_let_the_player_through_low_steps_only() {
+    let game_state = walk_north(level(Wall::Portal(1), Wall::Portal(0), 0.25));
+    assert_eq!(game_state.find_current_sector(), Some(1));
+
+    let game_state = walk_north(level(Wall::Portal(1), Wall::Portal(0), 1.5));
+    assert_eq!(game_state.find_current_sector(), Some(0));
+}
+
+#[test]
+fn noclip_goes_through_walls() {
+    let mut game_state = GameState::new(level(GREY, GREY, 0.0), Vec2::new(0.0, -1.0), Vec3::Y);
+    game_state.set_noclip(true);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 2.0);
+    assert_eq!(game_state.find_current_sector(), Some(1));
+}
+
+#[test]
+fn grid_walls_are_solid() {
+    // The grid's first sector is walled off from the one above it
+    let level = LevelState::grid(4, 4);
+    assert!(matches!(
+        level.sector(0).lines()[1].wall_type(),
+        Wall::Solid(_)
+    ));
+    let mut game_state = GameState::new(level, Vec2::new(0.5, 0.5), Vec3::Y);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 2.0);
+    assert_eq!(game_state.find_current_sector(), Some(0));
+}
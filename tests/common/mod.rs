@@ -0,0 +1,80 @@
+//! Levels and helpers shared by the integration tests. Each test file uses
+//! only some of them.
+#![allow(dead_code)]
+
+use glam::{Vec2, Vec3};
+use raycaster::{
+    canvas::Canvas,
+    game::GameState,
+    level::{Line, Sector, Wall},
+    scene::{render_scene, SceneSettings},
+};
+
+pub const GREY: Wall = Wall::Solid(Vec3::new(0.5, 0.5, 0.5));
+pub const RED: Wall = Wall::Solid(Vec3::new(1.0, 0.0, 0.0));
+pub const GREEN: Wall = Wall::Solid(Vec3::new(0.0, 1.0, 0.0));
+
+/// A rectangle 2.5 high with its left, top, right and bottom lines in that
+/// order, `with` adds whatever extra a line has.
+pub fn room<T>(
+    min: Vec2,
+    max: Vec2,
+    lines: [(Wall, Option<T>); 4],
+    with: impl Fn(Line, T) -> Line,
+) -> Sector {
+    let points = vec![min, Vec2::new(min.x, max.y), max, Vec2::new(max.x, min.y)];
+    let lines = lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, (wall, extra))| {
+            let line = Line::new(wall, index, (index + 1) % 4);
+            match extra {
+                Some(extra) => with(line, extra),
+                None => line,
+            }
+        })
+        .collect();
+    Sector::new(points, lines, 0.0, 2.5)
+}
+
+/// A `room` with nothing on its lines.
+pub fn plain_room(min: Vec2, max: Vec2, walls: [Wall; 4]) -> Sector {
+    room(min, max, walls.map(|wall| (wall, None)), |line, ()| line)
+}
+
+/// Updates the game in steps of 0.05 seconds.
+pub fn run(game_state: &mut GameState, seconds: f32) {
+    for _ in 0..(seconds / 0.05) as usize {
+        game_state.update(0.05);
+    }
+}
+
+/// Renders on one thread with a render distance of 10 unless `settings`
+/// say otherwise.
+pub fn settings() -> SceneSettings {
+    SceneSettings {
+        render_distance: 10.0,
+        threads: 1,
+        ..SceneSettings::default()
+    }
+}
+
+pub fn render(game_state: &GameState, width: u32, height: u32, settings: &SceneSettings) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    render_scene(&mut canvas, game_state, settings);
+    canvas
+}
+
+/// Red, green and blue of the pixel at `x` across and `y` down.
+pub fn pixel(canvas: &Canvas, x: u32, y: u32) -> [u8; 3] {
+    // Stored column by column as BGRA
+    let offset = ((x * canvas.height() + y) * 4) as usize;
+    let pixel = &canvas.pixels()[offset..offset + 3];
+    [pixel[2], pixel[1], pixel[0]]
+}
+
+/// Red, green and blue of the middle of a 64 by 48 view.
+pub fn middle_pixel(game_state: &GameState, settings: &SceneSettings) -> [u8; 3] {
+    let canvas = render(game_state, 64, 48, settings);
+    pixel(&canvas, 32, 24)
+}
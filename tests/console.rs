@@ -0,0 +1,191 @@
+use std::{env, fs};
+
+use glam::{Vec2, Vec3};
+use raycaster::{
+    console::{Console, Cvar, CvarValue, Cvars},
+    game::GameState,
+    level::{LevelState, Line, Sector, Wall},
+    reload::LevelWatcher,
+};
+
+/// A cvar owner standing in for the renderer.
+#[derive(Default)]
+struct Settings {
+    show_map: bool,
+    label: String,
+}
+
+impl Cvars for Settings {
+    fn cvars(&self) -> Vec<Cvar> {
+        vec![
+            Cvar {
+                name: "show_map",
+                description: "",
+                value: CvarValue::Bool(self.show_map),
+            },
+            Cvar {
+                name: "label",
+                description: "",
+                value: CvarValue::Text(self.label.clone()),
+            },
+        ]
+    }
+
+    fn set_cvar(&mut self, name: &str, value: CvarValue) -> bool {
+        match (name, value) {
+            ("show_map", CvarValue::Bool(value)) => self.show_map = value,
+            ("label", CvarValue::Text(value)) => self.label = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[test]
+fn cvar_values_parse_as_their_type() {
+    let boolean = CvarValue::Bool(false);
+    assert_eq!(boolean.parse_as("on"), Ok(CvarValue::Bool(true)));
+    assert_eq!(boolean.parse_as("0"), Ok(CvarValue::Bool(false)));
+    assert!(boolean.parse_as("maybe").is_err());
+
+    assert_eq!(CvarValue::Int(0).parse_as("-3"), Ok(CvarValue::Int(-3)));
+    assert!(CvarValue::Int(0).parse_as("1.5").is_err());
+    assert_eq!(
+        CvarValue::Float(0.0).parse_as("1.5"),
+        Ok(CvarValue::Float(1.5))
+    );
+    assert_eq!(
+        CvarValue::Text(String::new()).parse_as("a b"),
+        Ok(CvarValue::Text("a b".to_string()))
+    );
+}
+
+#[test]
+fn cvars_can_be_read_set_and_toggled() {
+    let mut console = Console::default();
+    let mut game_state = GameState::default();
+    let mut settings = Settings::default();
+
+    let mut run = |line: &str, game_state: &mut GameState| {
+        console.execute(line, game_state, &mut [&mut settings])
+    };
+    assert_eq!(
+        run("move_speed 2.5", &mut game_state),
+        Ok("move_speed 2.5".to_string())
+    );
+    assert_eq!(
+        run("move_speed", &mut game_state),
+        Ok("move_speed 2.5".to_string())
+    );
+    assert_eq!(run("toggle god", &mut game_state), Ok("god 1".to_string()));
+    assert!(game_state.god());
+
+    assert_eq!(
+        run("toggle show_map", &mut game_state),
+        Ok("show_map 1".to_string())
+    );
+    assert_eq!(
+        run("label  two words", &mut game_state),
+        Ok("label \"two words\"".to_string())
+    );
+    assert!(run("toggle label", &mut game_state).is_err());
+    assert!(run("god sometimes", &mut game_state).is_err());
+    assert!(settings.show_map);
+    assert_eq!(settings.label, "two words");
+}
+
+#[test]
+fn toggle_map_flips_show_map() {
+    let mut console = Console::default();
+    let mut game_state = GameState::default();
+    let mut settings = Settings::default();
+
+    let mut run = |line: &str| console.execute(line, &mut game_state, &mut [&mut settings]);
+    assert_eq!(run("toggle map"), Ok("show_map 1".to_string()));
+    assert_eq!(run("toggle map"), Ok("show_map 0".to_string()));
+    assert!(run("map").is_err());
+}
+
+#[test]
+fn reload_intervals_stay_in_range() {
+    let mut console = Console::default();
+    let mut game_state = GameState::default();
+    let mut watcher = LevelWatcher::default();
+
+    let mut run = |line: &str| console.execute(line, &mut game_state, &mut [&mut watcher]);
+    for (value, kept) in [("inf", "3600"), ("1e39", "3600"), ("-1", "0"), ("NaN", "0")] {
+        run(&format!("hot_reload_interval {value}")).unwrap();
+        assert_eq!(
+            run("hot_reload_interval"),
+            Ok(format!("hot_reload_interval {kept}"))
+        );
+    }
+}
+
+#[test]
+fn unknown_commands_are_errors() {
+    let mut console = Console::default();
+    let mut game_state = GameState::default();
+
+    assert_eq!(
+        console.execute("jump", &mut game_state, &mut []),
+        Err("unknown command or cvar \"jump\"".to_string())
+    );
+    assert!(console.execute("tp 1", &mut game_state, &mut []).is_err());
+    assert!(console.execute("tp x 2", &mut game_state, &mut []).is_err());
+    assert_eq!(
+        console.execute("   ", &mut game_state, &mut []),
+        Ok(String::new())
+    );
+}
+
+#[test]
+fn scripts_run_every_line_but_not_themselves() {
+    let path = env::temp_dir().join(format!("console-test-{}.cfg", std::process::id()));
+    let script = format!(
+        "god 1 // comments are skipped\nexec {}\nmove_speed 3\n",
+        path.display()
+    );
+    fs::write(&path, script).unwrap();
+
+    let mut console = Console::default();
+    let mut game_state = GameState::default();
+    let result = console.exec(&path, &mut game_state, &mut []);
+    fs::remove_file(&path).unwrap();
+
+    result.unwrap();
+    assert!(game_state.god());
+    assert_eq!(
+        console.execute("move_speed", &mut game_state, &mut []),
+        Ok("move_speed 3".to_string())
+    );
+    assert!(console
+        .output()
+        .iter()
+        .any(|line| line.contains("already running")));
+}
+
+#[test]
+fn invalid_levels_are_not_loaded() {
+    let path = env::temp_dir().join(format!("console-test-{}.json", std::process::id()));
+    let corners = vec![Vec2::ZERO, Vec2::Y, Vec2::ONE, Vec2::X];
+    let grey = Wall::Solid(Vec3::splat(0.5));
+    // The last line goes to a point the sector doesn't have
+    let lines = vec![
+        Line::new(grey, 0, 1),
+        Line::new(grey, 1, 2),
+        Line::new(grey, 2, 3),
+        Line::new(grey, 3, 9),
+    ];
+    let level = LevelState::new(vec![Sector::new(corners, lines, 0.0, 2.5)]);
+    level._save(&path).unwrap();
+
+    let mut console = Console::default();
+    let mut game_state = GameState::default();
+    let sectors = game_state.level().sectors().len();
+    let result = console.execute(&format!("map {}", path.display()), &mut game_state, &mut []);
+    fs::remove_file(&path).unwrap();
+
+    assert!(result.is_err());
+    assert_eq!(game_state.level().sectors().len(), sectors);
+}
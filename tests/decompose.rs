@@ -0,0 +1,110 @@
+use glam::{Vec2, Vec3};
+use raycaster::{
+    decompose::{decompose, Outline, Polygon},
+    level::{signed_area, LevelState, Sector, Wall},
+    validate::validate,
+};
+
+fn outline(points: &[(f32, f32)]) -> Outline {
+    let points = points.iter().map(|(x, y)| Vec2::new(*x, *y)).collect();
+    Outline::solid(points, Vec3::splat(0.5))
+}
+
+fn polygon(outline: Outline, holes: Vec<Outline>) -> Polygon {
+    Polygon {
+        outline,
+        holes,
+        base_height: 0.0,
+        height: 2.5,
+    }
+}
+
+fn is_convex(sector: &Sector) -> bool {
+    let points = sector.points();
+    let turns: Vec<f32> = (0..points.len())
+        .map(|index| {
+            let [a, b, c] = [0, 1, 2].map(|offset| points[(index + offset) % points.len()]);
+            (b - a).perp_dot(c - b)
+        })
+        .collect();
+    turns.iter().all(|turn| *turn <= 1e-5) || turns.iter().all(|turn| *turn >= -1e-5)
+}
+
+/// Decomposes `polygon` and checks the pieces are convex, cover the same
+/// area, are joined by portals both ways and make a valid level.
+fn check(polygon: &Polygon) -> LevelState {
+    let sectors = decompose(polygon, 0).unwrap();
+    assert!(sectors.iter().all(is_convex));
+
+    let area: f32 = sectors
+        .iter()
+        .map(|sector| signed_area(sector.points()).abs())
+        .sum();
+    let expected = signed_area(&polygon.outline.points).abs()
+        - polygon
+            .holes
+            .iter()
+            .map(|hole| signed_area(&hole.points).abs())
+            .sum::<f32>();
+    assert!((area - expected).abs() < 1e-3, "{area} != {expected}");
+
+    let level = LevelState::new(sectors);
+    for (sector_id, sector) in level.sectors().iter().enumerate() {
+        for (line_index, line) in sector.lines().iter().enumerate() {
+            if let Wall::Portal(target) = line.wall_type() {
+                let (other, _) = level.opposite_line(sector_id, line_index).unwrap();
+                assert_eq!(other, target);
+            }
+        }
+    }
+    assert_eq!(validate(&level), vec![]);
+    level
+}
+
+#[test]
+fn concave_rooms_split_into_convex_sectors() {
+    let l_shape = outline(&[
+        (0.0, 0.0),
+        (4.0, 0.0),
+        (4.0, 1.0),
+        (1.0, 1.0),
+        (1.0, 4.0),
+        (0.0, 4.0),
+    ]);
+    let level = check(&polygon(l_shape, vec![]));
+    assert_eq!(level.sectors().len(), 2);
+}
+
+#[test]
+fn pillars_are_left_out() {
+    let room = outline(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]);
+    let pillar = outline(&[(1.5, 1.5), (2.5, 1.5), (2.5, 2.5), (1.5, 2.5)]);
+    let level = check(&polygon(room, vec![pillar]));
+
+    assert!(level.sectors().len() >= 4);
+    assert_eq!(level.find_current_sector(Vec2::new(2.0, 2.0)), None);
+    assert!(level.find_current_sector(Vec2::new(0.5, 2.0)).is_some());
+}
+
+#[test]
+fn points_along_straight_edges_are_kept() {
+    // The L shape again, with extra points partway along three edges
+    let l_shape = outline(&[
+        (0.0, 0.0),
+        (2.0, 0.0),
+        (4.0, 0.0),
+        (4.0, 1.0),
+        (2.5, 1.0),
+        (1.0, 1.0),
+        (1.0, 4.0),
+        (0.0, 4.0),
+        (0.0, 2.0),
+    ]);
+    check(&polygon(l_shape, vec![]));
+}
+
+#[test]
+fn crossing_outlines_are_errors() {
+    let bow_tie = outline(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]);
+    assert!(decompose(&polygon(bow_tie, vec![]), 0).is_err());
+}
@@ -0,0 +1,157 @@
+#![allow(deprecated)]
+
+mod common;
+
+use common::{plain_room, GREY};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    canvas::Canvas,
+    editor::Editor,
+    font::Font,
+    game::GameState,
+    level::{LevelState, Wall},
+    mover::{MoverDef, MoverKind, Trigger},
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode,
+        WindowEvent,
+    },
+};
+
+/// Pixels per world unit in a new editor.
+const ZOOM: f32 = 100.0;
+
+fn door(sector: usize, trigger: Trigger) -> MoverDef {
+    MoverDef {
+        sector,
+        kind: MoverKind::Door { open: 2.0 },
+        trigger,
+        speed: 1.0,
+        wait: None,
+    }
+}
+
+/// Three rooms in a row from west to east joined by portals, each with a
+/// door. The middle one opens on entering the east room, the east one on
+/// entering the west room.
+fn level() -> LevelState {
+    LevelState::new(vec![
+        plain_room(
+            Vec2::new(-3.0, -1.0),
+            Vec2::new(-1.0, 1.0),
+            [GREY, GREY, Wall::Portal(1), GREY],
+        ),
+        plain_room(
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            [Wall::Portal(0), GREY, Wall::Portal(2), GREY],
+        ),
+        plain_room(
+            Vec2::new(1.0, -1.0),
+            Vec2::new(3.0, 1.0),
+            [Wall::Portal(1), GREY, GREY, GREY],
+        ),
+    ])
+    .with_movers(vec![
+        door(0, Trigger::Use),
+        door(1, Trigger::Enter(2)),
+        door(2, Trigger::Enter(0)),
+    ])
+}
+
+/// An 800 by 600 editor opened over the middle room.
+fn open_editor(game_state: &mut GameState) -> Editor {
+    let mut editor = Editor::new(PhysicalSize::new(800, 600));
+    press(&mut editor, game_state, VirtualKeyCode::E, true);
+    assert!(editor.is_active());
+    editor
+}
+
+fn press(editor: &mut Editor, game_state: &mut GameState, key: VirtualKeyCode, map_view: bool) {
+    let event = WindowEvent::KeyboardInput {
+        device_id: unsafe { DeviceId::dummy() },
+        input: KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::empty(),
+        },
+        is_synthetic: false,
+    };
+    editor.input(&event, game_state, map_view);
+}
+
+fn ctrl(editor: &mut Editor, game_state: &mut GameState, key: VirtualKeyCode) {
+    let modifiers = WindowEvent::ModifiersChanged(ModifiersState::CTRL);
+    editor.input(&modifiers, game_state, true);
+    press(editor, game_state, key, true);
+    let modifiers = WindowEvent::ModifiersChanged(ModifiersState::empty());
+    editor.input(&modifiers, game_state, true);
+}
+
+/// Clicks the world position `pos`, the player being in the middle of the
+/// screen.
+fn click(editor: &mut Editor, game_state: &mut GameState, pos: Vec2) {
+    let screen = (pos - game_state.pos()) * ZOOM + Vec2::new(400.0, 300.0);
+    let device_id = unsafe { DeviceId::dummy() };
+    let moved = WindowEvent::CursorMoved {
+        device_id,
+        position: PhysicalPosition::new(screen.x as f64, screen.y as f64),
+        modifiers: ModifiersState::empty(),
+    };
+    editor.input(&moved, game_state, true);
+    for state in [ElementState::Pressed, ElementState::Released] {
+        let event = WindowEvent::MouseInput {
+            device_id,
+            state,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::empty(),
+        };
+        editor.input(&event, game_state, true);
+    }
+}
+
+#[test]
+fn deleting_a_sector_renumbers_movers() {
+    let mut game_state = GameState::new(level(), Vec2::ZERO, Vec3::Y);
+    let mut editor = open_editor(&mut game_state);
+
+    click(&mut editor, &mut game_state, Vec2::new(-2.0, 0.0));
+    press(&mut editor, &mut game_state, VirtualKeyCode::Delete, true);
+
+    let edited = game_state.level();
+    assert_eq!(edited.sectors().len(), 2);
+    // The west room's door goes, and so does the door it set off
+    assert_eq!(edited.movers(), [door(0, Trigger::Enter(1))]);
+    assert_eq!(game_state.movers().len(), 1);
+
+    ctrl(&mut editor, &mut game_state, VirtualKeyCode::Z);
+    assert_eq!(game_state.level().movers(), level().movers());
+}
+
+#[test]
+fn a_new_level_clears_the_selection_and_undo() {
+    let mut game_state = GameState::new(level(), Vec2::ZERO, Vec3::Y);
+    let mut editor = open_editor(&mut game_state);
+    click(&mut editor, &mut game_state, Vec2::new(2.0, 0.0));
+    press(&mut editor, &mut game_state, VirtualKeyCode::K, true);
+    click(&mut editor, &mut game_state, Vec2::new(2.0, 0.0));
+
+    // As the console's map command would
+    let small = LevelState::new(vec![plain_room(
+        Vec2::splat(-1.0),
+        Vec2::splat(1.0),
+        [GREY; 4],
+    )]);
+    game_state.set_level(small);
+
+    // The east room is no more
+    let mut canvas = Canvas::new(800, 600);
+    editor.draw(&mut canvas, &Font::builtin(), &game_state);
+    press(&mut editor, &mut game_state, VirtualKeyCode::Delete, true);
+    ctrl(&mut editor, &mut game_state, VirtualKeyCode::Z);
+    assert_eq!(game_state.level().sectors().len(), 1);
+    assert!(!game_state.level().sector(0).sky());
+}
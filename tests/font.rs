@@ -0,0 +1,101 @@
+use std::io::ErrorKind;
+
+use glam::{Vec2, Vec3};
+use raycaster::{canvas::Canvas, font::Font, primitives::TextDescriptor};
+
+/// A PSF1 font of 256 glyphs two rows high, `A` a diagonal and every other
+/// glyph blank, followed by `table` when there is one.
+fn psf1(table: Option<&[u16]>) -> Vec<u8> {
+    let mode = if table.is_some() { 0x02 } else { 0x00 };
+    let mut bytes = vec![0x36, 0x04, mode, 2];
+    let mut glyphs = vec![0; 256 * 2];
+    glyphs[b'A' as usize * 2..][..2].copy_from_slice(&[0x80, 0x40]);
+    bytes.extend(glyphs);
+    for entry in table.unwrap_or_default() {
+        bytes.extend(entry.to_le_bytes());
+    }
+    bytes
+}
+
+/// A PSF2 font of `count` glyphs of `glyph_size` bytes each, all set.
+fn psf2(count: u32, glyph_size: u32, height: u32, width: u32) -> Vec<u8> {
+    let mut bytes = psf2_header(count, glyph_size, height, width);
+    bytes.extend(vec![0xff; (count * glyph_size) as usize]);
+    bytes
+}
+
+fn psf2_header(count: u32, glyph_size: u32, height: u32, width: u32) -> Vec<u8> {
+    let mut bytes = vec![0x72, 0xb5, 0x4a, 0x86];
+    for field in [0, 32, 0, count, glyph_size, height, width] {
+        bytes.extend(field.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn psf1_glyphs_map_to_their_code_points() {
+    let font = Font::from_psf(&psf1(None)).unwrap();
+    assert_eq!((font.width(), font.height()), (8, 2));
+
+    let glyph = font.glyph('A').unwrap();
+    assert!(font.pixel(glyph, 0, 0) && font.pixel(glyph, 1, 1));
+    assert!(!font.pixel(glyph, 1, 0) && !font.pixel(glyph, 0, 1));
+}
+
+#[test]
+fn psf1_unicode_tables_stop_at_the_last_glyph() {
+    // Glyph 0 is `B`, the rest have nothing until the table runs past the
+    // last glyph, where `C` would be glyph 256
+    let mut table = vec![b'B' as u16, 0xffff];
+    table.extend([0xffff; 255]);
+    table.extend([b'C' as u16, 0xffff]);
+
+    let font = Font::from_psf(&psf1(Some(&table))).unwrap();
+    assert_eq!(font.glyph('B'), Some(0));
+    assert_eq!(font.glyph('C'), None);
+}
+
+#[test]
+fn psf2_glyphs_drop_their_padding() {
+    // Two rows of 10 pixels, each glyph padded to 6 bytes
+    let font = Font::from_psf(&psf2(3, 6, 2, 10)).unwrap();
+    assert_eq!((font.width(), font.height()), (10, 2));
+    assert!(font.pixel(2, 9, 1));
+}
+
+#[test]
+fn bad_psf2_headers_are_invalid() {
+    for bytes in [
+        psf2(1, 0, 0, 0),
+        psf2(1, 0, 2, 0),
+        psf2(1, 1, 2, 8),
+        psf2(2, 2, 2, 8)[..34].to_vec(),
+        psf2_header(u32::MAX, u32::MAX, 2, 8),
+    ] {
+        let error = Font::from_psf(&bytes).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn text_is_drawn_with_the_glyph_pixels() {
+    let (width, height) = (16, 8);
+    let mut canvas = Canvas::new(width, height);
+    canvas.draw_text(
+        &Font::builtin(),
+        &TextDescriptor {
+            text: "!",
+            top_left: Vec2::ZERO,
+            color: Vec3::new(1.0, 0.0, 0.0),
+            scale: 1,
+            alpha: 1.0,
+        },
+    );
+
+    // Stored column by column as BGRA, `!` is a bar in the third column
+    let red = |x: u32, y: u32| canvas.pixels()[((x * height + y) * 4 + 2) as usize];
+    assert_eq!(red(2, 0), 255);
+    assert_eq!(red(2, 6), 255);
+    assert_eq!(red(2, 5), 0);
+    assert_eq!(red(1, 0), 0);
+}
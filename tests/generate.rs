@@ -0,0 +1,61 @@
+use raycaster::{
+    generate::{generate, Settings},
+    validate::{validate, Severity},
+};
+
+#[test]
+fn generated_levels_are_valid() {
+    for rooms in [1, 2, 5, 12, 30] {
+        for seed in 0..20 {
+            let settings = Settings {
+                rooms,
+                ..Settings::default()
+            };
+            let level = generate(seed, &settings).unwrap();
+            let errors: Vec<_> = validate(&level)
+                .into_iter()
+                .filter(|issue| issue.severity == Severity::Error)
+                .collect();
+            assert!(errors.is_empty(), "seed {seed}, {rooms} rooms: {errors:?}");
+        }
+    }
+}
+
+#[test]
+fn the_same_seed_gives_the_same_level() {
+    let settings = Settings::default();
+    let first = generate(7, &settings).unwrap();
+    let second = generate(7, &settings).unwrap();
+    assert_eq!(first.sectors().len(), second.sectors().len());
+    for (a, b) in first.sectors().iter().zip(second.sectors()) {
+        assert_eq!(a.points(), b.points());
+    }
+}
+
+#[test]
+fn bad_settings_are_errors() {
+    for settings in [
+        Settings {
+            rooms: 0,
+            ..Settings::default()
+        },
+        Settings {
+            loop_chance: 1.5,
+            ..Settings::default()
+        },
+        Settings {
+            loop_chance: -0.1,
+            ..Settings::default()
+        },
+        Settings {
+            loop_chance: f64::NAN,
+            ..Settings::default()
+        },
+        Settings {
+            max_step: -1.0,
+            ..Settings::default()
+        },
+    ] {
+        assert!(generate(0, &settings).is_err(), "{settings:?}");
+    }
+}
@@ -0,0 +1,135 @@
+use bincode::Options;
+use glam::{Vec2, Vec3};
+use raycaster::{
+    format::{decode, encode, migrate, Encoding, VERSION},
+    generate::{generate, Settings},
+    level::{LevelState, Wall},
+    mover::{MoverDef, MoverKind, Trigger},
+};
+use serde::Serialize;
+use serde_json::json;
+
+/// A sector as version 1 binary files stored it.
+#[derive(Serialize)]
+struct SectorV1 {
+    points: Vec<Vec2>,
+    lines: Vec<LineV1>,
+    base_height: f32,
+    height: f32,
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LineV1 {
+    wall_type: Wall,
+    point_1_id: usize,
+    point_2_id: usize,
+}
+
+fn levels() -> Vec<LevelState> {
+    vec![
+        LevelState::demo(),
+        LevelState::grid(16, 16),
+        generate(3, &Settings::default()).unwrap(),
+        LevelState::demo()
+            .with_movers(vec![MoverDef {
+                sector: 1,
+                kind: MoverKind::Lift {
+                    top: 1.0,
+                    bottom: 0.0,
+                },
+                trigger: Trigger::Enter(0),
+                speed: 1.0,
+                wait: Some(2.0),
+            }])
+            .with_script(Some("demo.rhai".to_string())),
+    ]
+}
+
+#[test]
+fn round_trips_in_both_encodings() {
+    for level in levels() {
+        let json = encode(&level, Encoding::Json).unwrap();
+        for encoding in [Encoding::Json, Encoding::Binary] {
+            let bytes = encode(&level, encoding).unwrap();
+            let read = decode(&bytes).unwrap();
+            assert_eq!(encode(&read, Encoding::Json).unwrap(), json);
+        }
+    }
+}
+
+#[test]
+fn binary_is_smaller() {
+    let level = LevelState::grid(16, 16);
+    let json = encode(&level, Encoding::Json).unwrap();
+    let binary = encode(&level, Encoding::Binary).unwrap();
+    assert!(binary.len() * 4 < json.len());
+}
+
+#[test]
+fn migrates_unversioned_files() {
+    let legacy = json!({
+        "sectors": [{
+            "points": [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
+            "lines": [
+                {"wall_type": {"Solid": [1.0, 0.0, 0.0]}, "point_1_id": 0, "point_2_id": 1},
+                {"wall_type": {"Solid": [1.0, 0.0, 0.0]}, "point_1_id": 1, "point_2_id": 2},
+                {"wall_type": {"Solid": [1.0, 0.0, 0.0]}, "point_1_id": 2, "point_2_id": 0}
+            ],
+            "base_height": 0.0,
+            "height": 2.0
+        }]
+    });
+
+    let migrated = migrate(legacy.clone()).unwrap();
+    assert_eq!(migrated["version"], json!(VERSION));
+    assert_eq!(migrated["sectors"][0]["name"], json!(null));
+    assert_eq!(migrated["movers"], json!([]));
+    assert_eq!(migrated["sectors"][0]["tag"], json!(null));
+    assert_eq!(migrated["sectors"][0]["sky"], json!(false));
+    assert_eq!(migrated["sectors"][0]["lines"][0]["special"], json!(null));
+    assert_eq!(migrated["sectors"][0]["lines"][0]["warp"], json!(null));
+    assert_eq!(migrated["sectors"][0]["lines"][0]["mask"], json!(null));
+    assert_eq!(migrated["sectors"][0]["lines"][0]["texture"], json!(null));
+    assert_eq!(migrated["script"], json!(null));
+
+    let level = decode(&serde_json::to_vec(&legacy).unwrap()).unwrap();
+    assert_eq!(level.sectors().len(), 1);
+    assert_eq!(level.sector(0).height(), 2.0);
+}
+
+#[test]
+fn reads_version_1_binary() {
+    let sectors = vec![SectorV1 {
+        points: vec![Vec2::ZERO, Vec2::Y, Vec2::ONE],
+        lines: (0..3)
+            .map(|index| LineV1 {
+                wall_type: Wall::Solid(Vec3::X),
+                point_1_id: index,
+                point_2_id: (index + 1) % 3,
+            })
+            .collect(),
+        base_height: 0.0,
+        height: 2.0,
+        name: Some("old".to_string()),
+    }];
+    let mut bytes = b"RCLV".to_vec();
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(bincode::DefaultOptions::new().serialize(&sectors).unwrap());
+
+    let read = decode(&bytes).unwrap();
+    assert_eq!(read.sectors().len(), 1);
+    assert_eq!(read.sector(0).name(), Some("old"));
+    assert_eq!(read.sector(0).lines()[2].point_ids(), (2, 0));
+    assert!(read.movers().is_empty());
+}
+
+#[test]
+fn rejects_newer_versions() {
+    let json = serde_json::to_vec(&json!({"version": VERSION + 1, "sectors": []})).unwrap();
+    assert!(decode(&json).is_err());
+
+    let mut binary = encode(&LevelState::demo(), Encoding::Binary).unwrap();
+    binary[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(decode(&binary).is_err());
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{run, settings, GREEN, GREY, RED};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::{GameState, MAX_STEP},
+    level::{LevelState, Line, Mask, MaskPattern, Sector, Wall},
+    monster::Monsters,
+    nav::NavGraph,
+    validate::validate,
+};
+
+const WINDOW: Mask = Mask {
+    color: Vec3::new(0.0, 0.0, 1.0),
+    opacity: 0.5,
+    pattern: MaskPattern::Pane,
+    blocking: true,
+};
+
+fn room(min: Vec2, max: Vec2, lines: [(Wall, Option<Mask>); 4]) -> Sector {
+    common::room(min, max, lines, Line::with_mask)
+}
+
+/// Two rooms side by side with `window` between them and a green wall at
+/// the top of the far one.
+fn windowed(window: Mask) -> LevelState {
+    LevelState::new(vec![
+        room(
+            Vec2::new(-1.0, -2.0),
+            Vec2::new(1.0, 0.0),
+            [
+                (GREY, None),
+                (Wall::Portal(1), Some(window)),
+                (GREY, None),
+                (GREY, None),
+            ],
+        ),
+        room(
+            Vec2::new(-1.0, 0.0),
+            Vec2::new(1.0, 2.0),
+            [
+                (GREY, None),
+                (GREEN, None),
+                (GREY, None),
+                (Wall::Portal(0), None),
+            ],
+        ),
+    ])
+}
+
+fn middle_pixel(game_state: &GameState) -> [u8; 3] {
+    common::middle_pixel(game_state, &settings())
+}
+
+#[test]
+fn mirrors_show_the_wall_behind_the_player() {
+    let level = LevelState::new(vec![room(
+        Vec2::new(-1.0, -1.0),
+        Vec2::new(1.0, 1.0),
+        [
+            (GREY, None),
+            (Wall::Mirror, None),
+            (GREY, None),
+            (RED, None),
+        ],
+    )]);
+    assert_eq!(validate(&level), vec![]);
+
+    let game_state = GameState::new(level, Vec2::new(0.0, -0.5), Vec3::Y);
+    let [red, green, blue] = middle_pixel(&game_state);
+    assert!(
+        red > 0 && green == 0 && blue == 0,
+        "{:?}",
+        [red, green, blue]
+    );
+}
+
+#[test]
+fn the_far_side_shows_through_a_window() {
+    let level = windowed(WINDOW);
+    assert_eq!(validate(&level), vec![]);
+
+    let game_state = GameState::new(level, Vec2::new(0.0, -1.5), Vec3::Y);
+    let [red, green, blue] = middle_pixel(&game_state);
+    assert!(
+        red == 0 && green > 0 && blue > 0,
+        "{:?}",
+        [red, green, blue]
+    );
+}
+
+#[test]
+fn blocking_masks_stop_the_player() {
+    let mut game_state = GameState::new(windowed(WINDOW), Vec2::new(0.0, -1.0), Vec3::Y);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 1.5);
+
+    assert_eq!(game_state.find_current_sector(), Some(0));
+    assert!(game_state.pos().y < 0.0);
+}
+
+#[test]
+fn blocking_masks_stop_monsters() {
+    // A path worked out while the window could be walked through
+    let open = windowed(Mask {
+        blocking: false,
+        ..WINDOW
+    });
+    let nav = NavGraph::new(&open, MAX_STEP);
+    let player = Vec3::new(0.0, 1.5, 1.5);
+
+    for (level, sector) in [(open, 1), (windowed(WINDOW), 0)] {
+        let mut monsters = Monsters::default();
+        let monster = monsters
+            .spawn("grunt", Vec2::new(0.0, -1.5), Vec2::Y)
+            .unwrap();
+        monster.set_tag(Some(1));
+        monsters.alert(1);
+        for _ in 0..40 {
+            monsters.update(0.05, &level, &nav, player);
+        }
+
+        let pos = monsters.iter().next().unwrap().pos();
+        assert_eq!(level.find_current_sector(pos), Some(sector));
+    }
+}
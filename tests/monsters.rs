@@ -0,0 +1,182 @@
+mod common;
+
+use common::{plain_room, GREY};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::MAX_STEP,
+    level::{LevelState, Wall},
+    monster::{MonsterDefs, Monsters},
+    nav::NavGraph,
+};
+
+/// Sees all the way round, chases what it sees, hits it when close and
+/// flinches when hurt.
+const WATCHER: &str = r#"{
+  "watcher": {
+    "health": 10,
+    "speed": 2.0,
+    "radius": 0.25,
+    "height": 1.4,
+    "color": [1.0, 0.0, 0.0],
+    "sight_range": 8.0,
+    "field_of_view": 360.0,
+    "start": "idle",
+    "states": {
+      "idle": {
+        "action": "Idle",
+        "transitions": [
+          { "when": ["SeesPlayer"], "to": "chase" },
+          { "when": ["Hurt"], "to": "flinch" },
+          { "when": ["Alerted"], "to": "chase" }
+        ]
+      },
+      "flinch": {
+        "action": "Idle",
+        "transitions": [{ "when": [{ "After": 0.5 }], "to": "idle" }]
+      },
+      "chase": {
+        "action": "Chase",
+        "transitions": [
+          { "when": [{ "PlayerWithin": 1.0 }], "to": "hit" },
+          { "when": [{ "LostPlayer": 1.0 }], "to": "idle" }
+        ]
+      },
+      "hit": {
+        "action": { "Attack": { "damage": 5, "range": 1.5, "cooldown": 1.0 } },
+        "transitions": [{ "when": [{ "PlayerBeyond": 2.0 }], "to": "chase" }]
+      }
+    }
+  }
+}"#;
+
+/// A room south of the origin and one north of it, the line between them
+/// `between` on both sides.
+fn level(between: Wall, back: Wall) -> LevelState {
+    LevelState::new(vec![
+        plain_room(
+            Vec2::new(-1.0, -3.0),
+            Vec2::new(1.0, 0.0),
+            [GREY, between, GREY, GREY],
+        ),
+        plain_room(
+            Vec2::new(-1.0, 0.0),
+            Vec2::new(1.0, 3.0),
+            [GREY, GREY, GREY, back],
+        ),
+    ])
+}
+
+fn open() -> LevelState {
+    level(Wall::Portal(1), Wall::Portal(0))
+}
+
+fn closed() -> LevelState {
+    level(GREY, GREY)
+}
+
+/// One watcher at `pos`, facing north.
+fn watcher(pos: Vec2) -> Monsters {
+    let mut monsters = Monsters::default();
+    monsters.set_defs(MonsterDefs::parse(WATCHER).unwrap());
+    monsters.spawn("watcher", pos, Vec2::Y).unwrap();
+    monsters
+}
+
+/// Updates in steps of 0.05 seconds, returning the damage dealt.
+fn run(
+    monsters: &mut Monsters,
+    seconds: f32,
+    level: &LevelState,
+    nav: &NavGraph,
+    player: Vec3,
+) -> i32 {
+    (0..(seconds / 0.05) as usize)
+        .map(|_| monsters.update(0.05, level, nav, player))
+        .sum()
+}
+
+fn state(monsters: &Monsters) -> &str {
+    monsters.iter().next().unwrap().state()
+}
+
+#[test]
+fn states_must_exist() {
+    let missing_start = WATCHER.replace("\"start\": \"idle\"", "\"start\": \"asleep\"");
+    assert!(MonsterDefs::parse(&missing_start).is_err());
+
+    let missing_target = WATCHER.replace("\"to\": \"flinch\"", "\"to\": \"cower\"");
+    assert!(MonsterDefs::parse(&missing_target).is_err());
+
+    assert!(MonsterDefs::parse(WATCHER).is_ok());
+    assert!(MonsterDefs::default().kinds().contains(&"grunt"));
+}
+
+#[test]
+fn getting_hurt_and_waiting_change_state() {
+    let level = open();
+    let nav = NavGraph::new(&level, MAX_STEP);
+    let mut monsters = watcher(Vec2::new(0.0, -1.0));
+    // Too far off to be seen
+    let player = Vec3::new(100.0, 100.0, 1.5);
+
+    run(&mut monsters, 0.1, &level, &nav, player);
+    assert_eq!(state(&monsters), "idle");
+
+    monsters.iter_mut().next().unwrap().hurt(3);
+    run(&mut monsters, 0.1, &level, &nav, player);
+    assert_eq!(state(&monsters), "flinch");
+    assert_eq!(monsters.iter().next().unwrap().health(), 7);
+
+    run(&mut monsters, 0.6, &level, &nav, player);
+    assert_eq!(state(&monsters), "idle");
+}
+
+#[test]
+fn seen_players_are_chased_and_hit() {
+    let level = open();
+    let nav = NavGraph::new(&level, MAX_STEP);
+    let mut monsters = watcher(Vec2::new(0.0, -2.5));
+    let player = Vec3::new(0.0, 2.0, 1.5);
+
+    run(&mut monsters, 0.05, &level, &nav, player);
+    assert_eq!(state(&monsters), "chase");
+
+    let damage = run(&mut monsters, 3.0, &level, &nav, player);
+    assert_eq!(state(&monsters), "hit");
+    assert!(damage > 0);
+    assert_eq!(
+        level.find_current_sector(monsters.iter().next().unwrap().pos()),
+        Some(1)
+    );
+
+    // Backing off starts the chase again
+    run(&mut monsters, 0.05, &level, &nav, Vec3::new(0.0, -2.5, 1.5));
+    assert_eq!(state(&monsters), "chase");
+}
+
+#[test]
+fn monsters_see_through_portals_but_not_walls() {
+    let player = Vec3::new(0.0, 2.0, 1.5);
+    for (level, seen) in [(open(), "chase"), (closed(), "idle")] {
+        let nav = NavGraph::new(&level, MAX_STEP);
+        let mut monsters = watcher(Vec2::new(0.0, -2.0));
+        run(&mut monsters, 0.05, &level, &nav, player);
+        assert_eq!(state(&monsters), seen);
+    }
+}
+
+#[test]
+fn walls_between_sectors_stop_monsters() {
+    // A path worked out while the way was open would lead straight through
+    let nav = NavGraph::new(&open(), MAX_STEP);
+    let player = Vec3::new(0.0, 2.5, 1.5);
+
+    for (level, sector) in [(open(), 1), (closed(), 0)] {
+        let mut monsters = watcher(Vec2::new(0.0, -1.0));
+        monsters.iter_mut().next().unwrap().set_tag(Some(1));
+        monsters.alert(1);
+        run(&mut monsters, 2.0, &level, &nav, player);
+        let pos = monsters.iter().next().unwrap().pos();
+        assert_eq!(level.find_current_sector(pos), Some(sector));
+    }
+}
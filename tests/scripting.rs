@@ -0,0 +1,103 @@
+mod common;
+
+use common::{plain_room, run, GREY};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::{GameEvent, GameState},
+    level::{LevelState, Wall},
+    script::Scripts,
+};
+
+/// Two rooms side by side, "hall" below and "vault" above it.
+fn level() -> LevelState {
+    let room = |min: Vec2, max: Vec2, top: Wall, bottom: Wall| {
+        plain_room(min, max, [GREY, top, GREY, bottom])
+    };
+    LevelState::new(vec![
+        room(Vec2::new(-1.0, -2.0), Vec2::ZERO, Wall::Portal(1), GREY).with_name("hall"),
+        room(
+            Vec2::new(-1.0, 0.0),
+            Vec2::new(1.0, 2.0),
+            GREY,
+            Wall::Portal(0),
+        )
+        .with_name("vault"),
+    ])
+}
+
+fn game_state(source: &str) -> GameState {
+    let mut game_state = GameState::new(level(), Vec2::new(0.0, -1.0), Vec3::Y);
+    game_state.set_scripts(Some(Scripts::compile("test.rhai", source).unwrap()));
+    game_state
+}
+
+#[test]
+fn entering_a_sector_runs_its_hook() {
+    let mut game_state = game_state(
+        r#"
+        fn on_enter_sector(sector) {
+            if sector == find_sector("vault") {
+                move_sector(sector, 0.5, sector_ceiling(sector));
+                message("the floor shifts");
+            }
+        }
+        "#,
+    );
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 1.5);
+
+    assert_eq!(game_state.find_current_sector(), Some(1));
+    assert_eq!(game_state.level().sector(1).base_height(), 0.5);
+    assert_eq!(game_state.level().sector(1).height(), 2.0);
+    assert_eq!(game_state.message(), Some("the floor shifts"));
+}
+
+#[test]
+fn hooks_keep_state_in_this() {
+    let mut game_state = game_state(
+        r#"
+        fn on_tick(dt) {
+            this.ticks = (this.ticks ?? 0) + 1;
+            if this.ticks == 10 {
+                teleport(0, 1);
+            }
+        }
+        "#,
+    );
+    run(&mut game_state, 0.45);
+    assert_eq!(game_state.find_current_sector(), Some(0));
+    run(&mut game_state, 0.05);
+    assert_eq!(game_state.pos(), Vec2::new(0.0, 1.0));
+}
+
+#[test]
+fn runtime_errors_stop_the_script() {
+    let mut game_state = game_state(
+        r#"
+        fn on_tick(dt) {
+            move_sector(7, 0, 1);
+        }
+        "#,
+    );
+    game_state.update(0.05);
+
+    assert!(game_state.scripts().is_none());
+    let events = game_state.take_events();
+    assert!(matches!(
+        &events[..],
+        [GameEvent::ScriptError(e)] if e.contains("there is no sector 7")
+    ));
+}
+
+#[test]
+fn scripts_are_sandboxed() {
+    let mut game_state = game_state("fn on_tick(dt) { loop {} }");
+    game_state.update(0.05);
+    assert!(game_state.scripts().is_none());
+
+    let scripts = Scripts::compile("test.rhai", r#"import "other" as other;"#).unwrap();
+    game_state.set_scripts(Some(scripts));
+    assert!(game_state.scripts().is_none());
+
+    assert!(Scripts::compile("test.rhai", r#"eval("1")"#).is_err());
+}
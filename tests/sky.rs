@@ -0,0 +1,96 @@
+mod common;
+
+use common::{pixel, plain_room, render, settings, GREY};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::GameState,
+    level::{LevelState, Wall},
+    scene::SceneSettings,
+    sky::Sky,
+};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 480;
+
+/// A long room open to the sky with its top line a portal into a lower
+/// room north of it, which is open to the sky when `sky_beyond` is set.
+fn level(sky_beyond: bool) -> LevelState {
+    let room = |min: Vec2, max: Vec2, top: Wall, bottom: Wall, height: f32| {
+        let mut room = plain_room(min, max, [GREY, top, GREY, bottom]);
+        room.set_height(height);
+        room
+    };
+    let beyond = room(
+        Vec2::new(-1.0, 0.0),
+        Vec2::new(1.0, 2.0),
+        GREY,
+        Wall::Portal(0),
+        2.0,
+    );
+    LevelState::new(vec![
+        room(
+            Vec2::new(-1.0, -20.0),
+            Vec2::new(1.0, 0.0),
+            Wall::Portal(1),
+            GREY,
+            6.0,
+        )
+        .with_sky(),
+        if sky_beyond {
+            beyond.with_sky()
+        } else {
+            beyond
+        },
+    ])
+}
+
+/// Red, green and blue of the middle column at `row`.
+fn middle_column(game_state: &GameState, sky: Sky, row: u32) -> [u8; 3] {
+    let settings = SceneSettings {
+        render_distance: 30.0,
+        sky,
+        ..settings()
+    };
+    pixel(
+        &render(game_state, WIDTH, HEIGHT, &settings),
+        WIDTH / 2,
+        row,
+    )
+}
+
+fn blue_sky() -> Sky {
+    Sky::new(1, 1, vec![Vec3::new(0.0, 0.0, 1.0)]).unwrap()
+}
+
+#[test]
+fn the_sky_is_drawn_above_sky_sectors() {
+    let game_state = GameState::new(level(true), Vec2::new(0.0, -10.0), Vec3::NEG_Y);
+    assert_eq!(middle_column(&game_state, blue_sky(), 0), [0, 0, 255]);
+}
+
+#[test]
+fn the_sky_turns_with_the_view() {
+    let red_and_green = Sky::new(
+        2,
+        1,
+        vec![Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)],
+    )
+    .unwrap();
+    let south = GameState::new(level(true), Vec2::new(0.0, -10.0), Vec3::NEG_Y);
+    let north = GameState::new(level(true), Vec2::new(0.0, -10.0), Vec3::Y);
+
+    assert_eq!(middle_column(&south, red_and_green.clone(), 0), [255, 0, 0]);
+    assert_eq!(middle_column(&north, red_and_green, 0), [0, 255, 0]);
+}
+
+#[test]
+fn upper_walls_between_skies_are_left_open() {
+    // Between the two rooms' ceilings where they meet at the portal
+    let row = 190;
+
+    let open = GameState::new(level(true), Vec2::new(0.0, -10.0), Vec3::Y);
+    assert_eq!(middle_column(&open, blue_sky(), row), [0, 0, 255]);
+
+    let roofed = GameState::new(level(false), Vec2::new(0.0, -10.0), Vec3::Y);
+    assert_ne!(middle_column(&roofed, blue_sky(), row), [0, 0, 255]);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{pixel, plain_room, render, settings, GREEN, RED};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::GameState,
+    level::{LevelState, Wall},
+};
+
+/// The player far back in a green room south of the origin, looking north
+/// into a red room whose floor is at `floor` and ceiling level with the
+/// green room's.
+fn game_state(floor: f32) -> GameState {
+    let mut far = plain_room(
+        Vec2::new(-1.0, 0.0),
+        Vec2::new(1.0, 4.0),
+        [RED, RED, RED, Wall::Portal(0)],
+    );
+    far.set_base_height(floor);
+    far.set_height(2.5 - floor);
+    let level = LevelState::new(vec![
+        plain_room(
+            Vec2::new(-1.0, -5.0),
+            Vec2::new(1.0, 0.0),
+            [GREEN, Wall::Portal(1), GREEN, GREEN],
+        ),
+        far,
+    ]);
+    GameState::new(level, Vec2::new(0.0, -4.5), Vec3::Y)
+}
+
+/// Rows of the middle column drawn in the grey of a step.
+fn step_rows(game_state: &GameState) -> Vec<u32> {
+    let canvas = render(game_state, 64, 240, &settings());
+    (0..canvas.height())
+        .filter(|y| {
+            let [red, green, blue] = pixel(&canvas, 32, *y);
+            red > 0 && red == green && green == blue
+        })
+        .collect()
+}
+
+#[test]
+fn steps_up_are_drawn() {
+    assert!(!step_rows(&game_state(0.5)).is_empty());
+}
+
+#[test]
+fn steps_down_are_not_drawn_from_behind() {
+    assert_eq!(step_rows(&game_state(-1.0)), Vec::<u32>::new());
+}
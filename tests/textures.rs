@@ -0,0 +1,142 @@
+mod common;
+
+use common::{run, settings, GREY};
+use glam::{Vec2, Vec3, Vec4};
+use raycaster::{
+    game::GameState,
+    level::{Decal, LevelState, Line},
+    scene::SceneSettings,
+    sky::Sky,
+    texture::{Image, Texture, Textures},
+};
+
+const RED: Vec4 = Vec4::new(1.0, 0.0, 0.0, 1.0);
+const GREEN: Vec4 = Vec4::new(0.0, 1.0, 0.0, 1.0);
+
+/// A square room whose top line, straight ahead of the player, is grey
+/// with `texture` laid over it.
+fn level(texture: Option<&str>) -> LevelState {
+    LevelState::new(vec![common::room(
+        Vec2::new(-1.0, -1.0),
+        Vec2::new(1.0, 1.0),
+        [(GREY, None), (GREY, texture), (GREY, None), (GREY, None)],
+        Line::with_texture,
+    )])
+}
+
+fn game_state(level: LevelState) -> GameState {
+    GameState::new(level, Vec2::new(0.0, -0.5), Vec3::Y)
+}
+
+fn middle_pixel(game_state: &GameState, textures: Textures) -> [u8; 3] {
+    common::middle_pixel(
+        game_state,
+        &SceneSettings {
+            textures,
+            ..settings()
+        },
+    )
+}
+
+fn textures(texture: Texture) -> Textures {
+    let mut textures = Textures::default();
+    textures.insert("test", texture);
+    textures
+}
+
+#[test]
+fn images_need_a_pixel_for_every_position() {
+    assert!(Image::new(2, 1, vec![RED, GREEN]).is_some());
+    assert!(Image::new(2, 2, vec![RED, GREEN]).is_none());
+    assert!(Image::new(0, 2, vec![]).is_none());
+    // 641 times 6700417 wraps round to 1 in a u32
+    assert!(Image::new(641, 6_700_417, vec![RED]).is_none());
+    assert!(Sky::new(641, 6_700_417, vec![Vec3::ONE]).is_none());
+}
+
+#[test]
+fn textured_walls_show_the_texture() {
+    let red = Texture::new(vec![Image::solid(RED)], 0.0, Vec2::ZERO, Vec2::ONE).unwrap();
+    let game_state = game_state(level(Some("test")));
+
+    let [red, green, blue] = middle_pixel(&game_state, textures(red));
+    assert!(
+        red > 0 && green == 0 && blue == 0,
+        "{:?}",
+        [red, green, blue]
+    );
+}
+
+#[test]
+fn animated_textures_change_frames_over_time() {
+    let flashing = Texture::new(
+        vec![Image::solid(RED), Image::solid(GREEN)],
+        1.0,
+        Vec2::ZERO,
+        Vec2::ONE,
+    )
+    .unwrap();
+    let mut game_state = game_state(level(Some("test")));
+
+    let [red, green, _] = middle_pixel(&game_state, textures(flashing.clone()));
+    assert!(red > 0 && green == 0, "{:?}", [red, green]);
+
+    run(&mut game_state, 1.5);
+    let [red, green, _] = middle_pixel(&game_state, textures(flashing));
+    assert!(red == 0 && green > 0, "{:?}", [red, green]);
+}
+
+#[test]
+fn scrolling_textures_move_along_the_wall() {
+    // Red for the first 2 units of every 4 along the wall, green after
+    let halves = Image::new(2, 1, vec![RED, GREEN]).unwrap();
+    let scrolling =
+        Texture::new(vec![halves], 0.0, Vec2::new(2.0, 0.0), Vec2::new(4.0, 1.0)).unwrap();
+    let mut game_state = game_state(level(Some("test")));
+
+    let [red, green, _] = middle_pixel(&game_state, textures(scrolling.clone()));
+    assert!(red > 0 && green == 0, "{:?}", [red, green]);
+
+    run(&mut game_state, 1.25);
+    let [red, green, _] = middle_pixel(&game_state, textures(scrolling));
+    assert!(red == 0 && green > 0, "{:?}", [red, green]);
+}
+
+#[test]
+fn decals_are_drawn_over_the_wall() {
+    let mut level = level(None);
+    level.add_decal(
+        0,
+        1,
+        Decal {
+            along: 1.0,
+            height: 1.25,
+            size: Vec2::new(0.5, 2.5),
+            texture: None,
+            color: Vec3::new(0.0, 0.0, 1.0),
+        },
+    );
+    let game_state = game_state(level);
+
+    let [red, green, blue] = middle_pixel(&game_state, Textures::default());
+    assert!(
+        red == 0 && green == 0 && blue > 0,
+        "{:?}",
+        [red, green, blue]
+    );
+}
+
+#[test]
+fn shots_leave_bullet_holes() {
+    let mut game_state = game_state(level(None));
+    // The pistol
+    game_state.input_mut().select_weapon(1);
+    game_state.update(0.05);
+    game_state.input_mut().fire(true);
+    game_state.update(0.05);
+
+    let decals = game_state.level().sectors()[0].lines()[1].decals();
+    assert!(!decals.is_empty());
+    assert_eq!(decals[0].texture.as_deref(), Some("bullet_hole"));
+    assert!((decals[0].along - 1.0).abs() < 0.1, "{:?}", decals[0]);
+}
@@ -0,0 +1,53 @@
+use glam::{Vec2, Vec3};
+use raycaster::{
+    canvas::Canvas,
+    game::GameState,
+    level::LevelState,
+    scene::{render_scene, SceneSettings},
+};
+
+fn render(game_state: &GameState, width: u32, height: u32, threads: usize) -> Vec<u8> {
+    let mut canvas = Canvas::new(width, height);
+    let settings = SceneSettings {
+        render_distance: 20.0,
+        threads,
+        ..SceneSettings::default()
+    };
+    render_scene(&mut canvas, game_state, &settings);
+    canvas.pixels().to_vec()
+}
+
+fn assert_same_for_any_threads(game_state: &GameState) {
+    // 101 and 97 don't divide evenly into any of the bands
+    for (width, height) in [(128, 96), (101, 97)] {
+        let single = render(game_state, width, height, 1);
+        assert!(
+            single.chunks(4).any(|pixel| pixel != &single[..4]),
+            "nothing drawn"
+        );
+        for threads in [2, 3, 4, 7] {
+            assert!(
+                single == render(game_state, width, height, threads),
+                "{width}x{height} differs with {threads} threads"
+            );
+        }
+    }
+}
+
+#[test]
+fn threaded_demo_matches_a_single_thread() {
+    for rot in [Vec3::NEG_Y, Vec3::X, Vec3::new(1.0, 1.0, 0.0).normalize()] {
+        assert_same_for_any_threads(&GameState::new(LevelState::demo(), Vec2::ZERO, rot));
+    }
+}
+
+#[test]
+fn threaded_grid_matches_a_single_thread() {
+    for rot in [Vec3::Y, Vec3::new(1.0, 0.5, 0.0).normalize()] {
+        assert_same_for_any_threads(&GameState::new(
+            LevelState::grid(16, 16),
+            Vec2::splat(8.5),
+            rot,
+        ));
+    }
+}
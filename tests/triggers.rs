@@ -0,0 +1,143 @@
+mod common;
+
+use common::{run, GREY};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::{GameEvent, GameState},
+    level::{Action, Activation, LevelState, Line, Sector, Special, Wall},
+    mover::{Motion, MoverDef, MoverKind, Trigger},
+};
+
+fn room(min: Vec2, max: Vec2, lines: [(Wall, Option<Special>); 4]) -> Sector {
+    common::room(min, max, lines, Line::with_special)
+}
+
+fn special(activation: Activation, action: Action, tag: Option<u32>) -> Special {
+    Special {
+        activation,
+        action,
+        tag,
+        repeat: false,
+    }
+}
+
+/// A start room with a switch on its right wall and a secret to shoot
+/// behind the player, a door tagged 5 the switch opens, a room past it whose
+/// entrance teleports to a far room tagged 9, and an exit switch there.
+fn level() -> LevelState {
+    let start = room(
+        Vec2::new(-1.0, -2.0),
+        Vec2::new(1.0, 0.0),
+        [
+            (GREY, None),
+            (Wall::Portal(1), None),
+            (
+                GREY,
+                Some(special(Activation::Use, Action::Movers, Some(5))),
+            ),
+            (GREY, Some(special(Activation::Shoot, Action::Secret, None))),
+        ],
+    );
+    let door = room(
+        Vec2::new(-1.0, 0.0),
+        Vec2::new(1.0, 0.3),
+        [
+            (GREY, None),
+            (Wall::Portal(2), None),
+            (GREY, None),
+            (Wall::Portal(0), None),
+        ],
+    )
+    .with_tag(5);
+    let beyond = room(
+        Vec2::new(-1.0, 0.3),
+        Vec2::new(1.0, 2.3),
+        [
+            (GREY, None),
+            (GREY, None),
+            (GREY, None),
+            (
+                Wall::Portal(1),
+                Some(special(Activation::Cross, Action::Teleport, Some(9))),
+            ),
+        ],
+    );
+    let far = room(
+        Vec2::new(10.0, 10.0),
+        Vec2::new(12.0, 12.0),
+        [
+            (GREY, None),
+            (
+                GREY,
+                Some(special(
+                    Activation::Use,
+                    Action::Exit {
+                        next: Some("next.json".to_string()),
+                    },
+                    None,
+                )),
+            ),
+            (GREY, None),
+            (GREY, None),
+        ],
+    )
+    .with_tag(9);
+
+    LevelState::new(vec![start, door, beyond, far]).with_movers(vec![MoverDef {
+        sector: 1,
+        kind: MoverKind::Door { open: 2.0 },
+        trigger: Trigger::Use,
+        speed: 4.0,
+        wait: None,
+    }])
+}
+
+#[test]
+fn shut_door_blocks_until_the_switch_is_used() {
+    let mut game_state = GameState::new(level(), Vec2::new(0.0, -1.0), Vec3::Y);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 2.0);
+    game_state.input_mut().forward(false);
+    assert_eq!(game_state.find_current_sector(), Some(0));
+
+    game_state.set_rot(Vec3::X);
+    game_state.input_mut().press_use();
+    run(&mut game_state, 1.0);
+    assert!(matches!(
+        game_state.movers()[0].motion(),
+        Motion::Waiting(_)
+    ));
+    assert_eq!(game_state.level().sector(1).height(), 2.0);
+}
+
+#[test]
+fn crossing_into_the_room_teleports_and_the_exit_reports() {
+    let mut game_state = GameState::new(level(), Vec2::new(0.0, -1.0), Vec3::X);
+    game_state.input_mut().press_use();
+    run(&mut game_state, 1.0);
+
+    game_state.set_rot(Vec3::Y);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 1.5);
+    game_state.input_mut().forward(false);
+    assert_eq!(game_state.find_current_sector(), Some(3));
+
+    game_state.teleport(Vec2::new(11.0, 11.5));
+    game_state.input_mut().press_use();
+    run(&mut game_state, 0.1);
+    assert_eq!(
+        game_state.take_events(),
+        vec![GameEvent::Exit(Some("next.json".to_string()))]
+    );
+}
+
+#[test]
+fn secrets_only_count_once() {
+    let mut game_state = GameState::new(level(), Vec2::new(0.0, -1.0), Vec3::NEG_Y);
+    game_state.input_mut().select_weapon(1);
+    game_state.input_mut().fire(true);
+    run(&mut game_state, 2.0);
+
+    assert_eq!(game_state.secrets_found(), 1);
+    assert_eq!(game_state.take_events(), vec![GameEvent::Secret]);
+}
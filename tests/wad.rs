@@ -0,0 +1,242 @@
+use std::io::ErrorKind;
+
+use glam::{Vec2, Vec3};
+use raycaster::{level::Wall, validate::validate, wad::Wad};
+
+/// A PWAD of `lumps` in order, the directory after them.
+fn wad(lumps: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+    let mut directory = vec![];
+    for (name, lump) in lumps {
+        directory.extend((12 + data.len() as i32).to_le_bytes());
+        directory.extend((lump.len() as i32).to_le_bytes());
+        let mut name = name.as_bytes().to_vec();
+        name.resize(8, 0);
+        directory.extend(name);
+        data.extend(lump);
+    }
+
+    let mut bytes = b"PWAD".to_vec();
+    bytes.extend((lumps.len() as i32).to_le_bytes());
+    bytes.extend((12 + data.len() as i32).to_le_bytes());
+    bytes.extend(data);
+    bytes.extend(directory);
+    bytes
+}
+
+/// Little-endian 16 bit fields one after another.
+fn record(fields: &[i32]) -> Vec<u8> {
+    fields
+        .iter()
+        .flat_map(|field| (*field as i16).to_le_bytes())
+        .collect()
+}
+
+fn name(name: &str) -> Vec<u8> {
+    let mut bytes = name.as_bytes().to_vec();
+    bytes.resize(8, 0);
+    bytes
+}
+
+fn sidedef(texture: &str, sector: i32) -> Vec<u8> {
+    let mut bytes = record(&[0, 0]);
+    bytes.extend(name("-"));
+    bytes.extend(name("-"));
+    bytes.extend(name(texture));
+    bytes.extend(record(&[sector]));
+    bytes
+}
+
+/// A 128 unit square split down the middle by one BSP node into two
+/// subsectors, the right one a step up, with the player start on the left.
+fn split_square() -> Vec<u8> {
+    square(&[(0, 128), (16, 128)], [0x8001, 0x8000])
+}
+
+/// `split_square` with other floors and ceilings, and other children for
+/// its node.
+fn square(floors_and_ceilings: &[(i32, i32)], children: [i32; 2]) -> Vec<u8> {
+    let vertices = [(0, 0), (64, 0), (128, 0), (128, 128), (64, 128), (0, 128)]
+        .iter()
+        .flat_map(|(x, y)| record(&[*x, *y]))
+        .collect();
+    // Every line but the middle one is one-sided
+    let linedefs = [
+        (0, 5, 0, -1),
+        (5, 4, 0, -1),
+        (4, 1, 1, 2),
+        (1, 0, 0, -1),
+        (4, 3, 3, -1),
+        (3, 2, 3, -1),
+        (2, 1, 3, -1),
+    ]
+    .iter()
+    .flat_map(|(v1, v2, right, left)| record(&[*v1, *v2, 0, 0, 0, *right, *left]))
+    .collect();
+    let sidedefs = [
+        sidedef("STARTAN", 0),
+        sidedef("-", 0),
+        sidedef("-", 1),
+        sidedef("BRICK", 1),
+    ]
+    .concat();
+    let sectors = floors_and_ceilings
+        .iter()
+        .flat_map(|(floor, ceiling)| {
+            let mut bytes = record(&[*floor, *ceiling]);
+            bytes.extend(name("FLAT"));
+            bytes.extend(name("FLAT"));
+            bytes.extend(record(&[160, 0, 0]));
+            bytes
+        })
+        .collect();
+    // Each subsector's segs go clockwise round it
+    let segs = [
+        (0, 5, 0, 0),
+        (5, 4, 1, 0),
+        (4, 1, 2, 0),
+        (1, 0, 3, 0),
+        (1, 4, 2, 1),
+        (4, 3, 4, 0),
+        (3, 2, 5, 0),
+        (2, 1, 6, 0),
+    ]
+    .iter()
+    .flat_map(|(v1, v2, linedef, direction)| record(&[*v1, *v2, 0, *linedef, *direction, 0]))
+    .collect();
+    let ssectors = record(&[4, 0, 4, 4]);
+    // Straight up the middle, the right subsector is 1 and the left 0
+    let mut node = vec![64, 0, 0, 128, 128, 0, 64, 128, 128, 0, 0, 64];
+    node.extend(children);
+    let nodes = record(&node);
+    let things = record(&[32, 64, 0, 1, 7]);
+
+    wad(&[
+        ("MAP01", vec![]),
+        ("THINGS", things),
+        ("LINEDEFS", linedefs),
+        ("SIDEDEFS", sidedefs),
+        ("VERTEXES", vertices),
+        ("SEGS", segs),
+        ("SSECTORS", ssectors),
+        ("NODES", nodes),
+        ("SECTORS", sectors),
+        ("MUSIC", vec![1, 2, 3]),
+    ])
+}
+
+fn invalid(bytes: Vec<u8>) -> bool {
+    Wad::from_bytes(bytes).err().map(|e| e.kind()) == Some(ErrorKind::InvalidData)
+}
+
+#[test]
+fn the_directory_lists_the_maps() {
+    let mut bytes = split_square();
+    let wad = Wad::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(wad.maps(), vec!["MAP01"]);
+
+    // Lump names are read up to their first zero, in upper case
+    let names = bytes.len() - 10 * 16 + 8;
+    bytes[names..names + 8].copy_from_slice(b"e1m1\0xyz");
+    assert_eq!(Wad::from_bytes(bytes).unwrap().maps(), vec!["E1M1"]);
+}
+
+#[test]
+fn subsectors_become_sectors_joined_by_portals() {
+    let map = Wad::from_bytes(split_square())
+        .unwrap()
+        .import_map("MAP01")
+        .unwrap();
+    let level = &map.level;
+    assert_eq!(level.sectors().len(), 2);
+    assert_eq!(validate(level), vec![]);
+
+    let portals = |sector_id: usize| {
+        level
+            .sector(sector_id)
+            .lines()
+            .iter()
+            .filter_map(|line| match line.wall_type() {
+                Wall::Portal(target) => Some(target),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(portals(0), vec![1]);
+    assert_eq!(portals(1), vec![0]);
+    assert_eq!(level.sector(1).base_height(), 0.5);
+    assert_eq!(level.sector(0).height(), 4.0);
+
+    // Doom's y runs the other way
+    assert_eq!(map.start, Some((Vec2::new(1.0, -2.0), Vec3::X)));
+    assert_eq!(level.find_current_sector(Vec2::new(1.0, -2.0)), Some(0));
+    assert_eq!(level.find_current_sector(Vec2::new(3.0, -2.0)), Some(1));
+}
+
+#[test]
+fn tall_sectors_keep_their_height() {
+    let bytes = square(&[(-20000, 20000), (16, 128)], [0x8001, 0x8000]);
+    let map = Wad::from_bytes(bytes).unwrap().import_map("MAP01").unwrap();
+    assert_eq!(map.level.sector(0).base_height(), -625.0);
+    assert_eq!(map.level.sector(0).height(), 1250.0);
+}
+
+#[test]
+fn nodes_that_lead_back_round_are_invalid() {
+    // The node as its own child, or with a child numbered after it
+    for children in [[0, 0x8000], [0x8001, 1]] {
+        let wad = Wad::from_bytes(square(&[(0, 128), (16, 128)], children)).unwrap();
+        assert_eq!(
+            wad.import_map("MAP01").err().unwrap().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
+
+#[test]
+fn missing_maps_and_lumps_are_errors() {
+    let square = Wad::from_bytes(split_square()).unwrap();
+    assert!(square.import_map("MAP02").is_err());
+
+    let without_segs = wad(&[("MAP01", vec![]), ("THINGS", vec![]), ("VERTEXES", vec![])]);
+    let without_segs = Wad::from_bytes(without_segs).unwrap();
+    assert_eq!(
+        without_segs.import_map("MAP01").err().unwrap().kind(),
+        ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn truncated_wads_are_invalid() {
+    let bytes = split_square();
+    assert!(invalid(bytes[..8].to_vec()));
+    assert!(invalid(bytes[..bytes.len() - 1].to_vec()));
+    assert!(invalid(b"JUNK\0\0\0\0\0\0\0\0".to_vec()));
+
+    // A lump that runs past the end
+    let mut bytes = wad(&[("MUSIC", vec![1, 2, 3])]);
+    let size = bytes.len() - 12;
+    bytes[size..size + 4].copy_from_slice(&100_i32.to_le_bytes());
+    assert!(invalid(bytes));
+}
+
+#[test]
+fn negative_counts_offsets_and_sizes_are_invalid() {
+    let field = |at: usize, value: i32| {
+        let mut bytes = wad(&[("MUSIC", vec![1, 2, 3])]);
+        let at = if at < 12 {
+            at
+        } else {
+            bytes.len() - 16 + at - 12
+        };
+        bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    };
+    // The count, the directory's offset, then the lump's offset and size
+    for at in [4, 8, 12, 16] {
+        assert!(invalid(field(at, -1)), "field at {at}");
+        assert!(invalid(field(at, i32::MIN)), "field at {at}");
+    }
+    assert!(invalid(field(4, i32::MAX)));
+    assert!(invalid(field(16, i32::MAX)));
+}
@@ -0,0 +1,119 @@
+use std::f32::consts::FRAC_PI_2;
+
+mod common;
+
+use common::{middle_pixel, run, settings, GREY, RED};
+use glam::{Vec2, Vec3};
+use raycaster::{
+    game::GameState,
+    level::{LevelState, Line, Sector, Wall, Warp},
+    validate::{validate, Severity},
+};
+
+fn room(min: Vec2, max: Vec2, lines: [(Wall, Option<Warp>); 4]) -> Sector {
+    common::room(min, max, lines, Line::with_warp)
+}
+
+/// A room whose top line comes out of the left wall of a room far away,
+/// turned a quarter to the right, with a red wall opposite.
+fn level() -> LevelState {
+    let warp = Warp {
+        offset: Vec2::new(10.0, 11.0),
+        angle: -FRAC_PI_2,
+        height: 0.0,
+    };
+    let near = room(
+        Vec2::new(-1.0, -2.0),
+        Vec2::new(1.0, 0.0),
+        [
+            (GREY, None),
+            (Wall::Portal(1), Some(warp)),
+            (GREY, None),
+            (GREY, None),
+        ],
+    );
+    let far = room(
+        Vec2::new(10.0, 10.0),
+        Vec2::new(12.0, 12.0),
+        [
+            (Wall::Portal(0), Some(warp.inverse())),
+            (GREY, None),
+            (RED, None),
+            (GREY, None),
+        ],
+    );
+    LevelState::new(vec![near, far])
+}
+
+#[test]
+fn warped_portals_validate() {
+    assert_eq!(validate(&level()), vec![]);
+    assert_eq!(level().opposite_line(0, 1), Some((1, 0)));
+}
+
+#[test]
+fn warps_into_sectors_with_broken_lines_are_errors() {
+    let mut sectors = level().sectors().to_vec();
+    let far = &sectors[1];
+    // A line to a point the far room doesn't have, ahead of the one back
+    let lines = [Line::new(GREY, 0, 9)]
+        .into_iter()
+        .chain(far.lines().iter().cloned())
+        .collect();
+    sectors[1] = Sector::new(far.points().to_vec(), lines, 0.0, 2.5);
+
+    let issues = validate(&LevelState::new(sectors));
+    assert!(issues
+        .iter()
+        .any(|issue| issue.severity == Severity::Error && issue.sector == 1));
+}
+
+#[test]
+fn walking_through_a_warp_turns_the_player() {
+    let mut game_state = GameState::new(level(), Vec2::new(0.0, -1.0), Vec3::Y);
+    game_state.input_mut().forward(true);
+    run(&mut game_state, 1.5);
+
+    assert_eq!(game_state.find_current_sector(), Some(1));
+    assert!(game_state.pos().y > 10.5 && game_state.pos().y < 11.5);
+    assert!(game_state.rot().abs_diff_eq(Vec3::X, 1e-4));
+}
+
+#[test]
+fn traces_carry_on_through_warps() {
+    let level = level();
+    let from = Vec3::new(0.0, -1.0, 1.0);
+    assert_eq!(level.trace(from, Vec3::new(0.0, 1.0, 1.0)), None);
+    let hit = level.trace_hit(from, Vec3::new(0.0, 3.0, 1.0)).unwrap();
+    assert_eq!(hit.line, Some((1, 2)));
+}
+
+#[test]
+fn the_far_room_is_drawn_through_the_warp() {
+    let game_state = GameState::new(level(), Vec2::new(0.0, -1.5), Vec3::Y);
+    let [red, green, blue] = middle_pixel(&game_state, &settings());
+    assert!(
+        red > 0 && green == 0 && blue == 0,
+        "{:?}",
+        [red, green, blue]
+    );
+}
+
+#[test]
+fn monsters_stay_on_their_side_of_a_warp() {
+    let mut game_state = GameState::new(level(), Vec2::new(11.0, 11.0), Vec3::X);
+    let monster = game_state
+        .monsters_mut()
+        .spawn("grunt", Vec2::new(0.0, -1.0), Vec2::Y)
+        .unwrap();
+    monster.set_tag(Some(1));
+    game_state.monsters_mut().alert(1);
+    run(&mut game_state, 3.0);
+
+    let monster = game_state.monsters().iter().next().unwrap();
+    assert_eq!(monster.state(), "chase");
+    assert_eq!(
+        game_state.level().find_current_sector(monster.pos()),
+        Some(0)
+    );
+}